@@ -0,0 +1,237 @@
+use std::{env, fs};
+use std::sync::LazyLock;
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use ed25519_dalek::{SigningKey, VerifyingKey};
+use jsonwebtoken::{Algorithm, EncodingKey, DecodingKey};
+use jsonwebtoken::jwk::{
+    Jwk, JwkSet, CommonParameters, AlgorithmParameters, PublicKeyUse, KeyAlgorithm, EllipticCurve,
+    OctetKeyPairParameters, OctetKeyPairType, RSAKeyParameters, RSAKeyType,
+};
+use rand::{rngs::OsRng, RngCore};
+use rsa::{RsaPrivateKey, RsaPublicKey, traits::PublicKeyParts};
+use rsa::pkcs1::{DecodeRsaPrivateKey, DecodeRsaPublicKey, EncodeRsaPrivateKey};
+use rsa::pkcs8::{DecodePrivateKey, DecodePublicKey, EncodePrivateKey};
+
+// Keys used to sign and verify the JWTs, loaded once from JWT_KEYS_DIR & JWT_ACTIVE_KID
+static JWT_KEYS: LazyLock<JwtKeys> = LazyLock::new(|| {
+
+    JwtKeys::from_env().unwrap_or_else(|e| panic!("Invalid JWT keys configuration: {e}"))
+});
+
+/*
+ * Load the JWT keys at startup, so a bad configuration stops the server right away
+ */
+pub fn init_jwt_keys() {
+    LazyLock::force(&JWT_KEYS);
+}
+
+pub fn jwt_keys() -> &'static JwtKeys {
+    &JWT_KEYS
+}
+
+// A key identified by its kid (the name of its file)
+pub struct JwtKey {
+    pub kid: String,
+    pub algorithm: Algorithm,
+    encoding: Option<EncodingKey>, // None for a public key only (can still verify old tokens)
+    pub decoding: DecodingKey,
+    jwk: Jwk,
+}
+
+pub struct JwtKeys {
+    keys: Vec<JwtKey>,
+    active: usize, // index of the key used to sign
+}
+
+impl JwtKeys {
+
+    /*
+     * Read all <kid>.pem files of JWT_KEYS_DIR (Ed25519 or RSA, private or public only)
+     * - JWT_ACTIVE_KID is the key used to sign, it can be omitted if there is only one key
+     * - Without JWT_KEYS_DIR, the start fails unless JWT_EPHEMERAL_KEY=true (development only)
+     */
+    fn from_env() -> Result<Self, String> {
+
+        let dir = match env::var("JWT_KEYS_DIR") {
+            Ok(dir) if !dir.is_empty() => dir,
+            _ if env::var("JWT_EPHEMERAL_KEY").as_deref() == Ok("true") => return Ok(Self::ephemeral()),
+            _ => return Err(String::from("JWT_KEYS_DIR must be set (or JWT_EPHEMERAL_KEY=true for development)")),
+        };
+
+        let entries = fs::read_dir(&dir)
+            .map_err(|e| format!("can't read {dir}: {e}"))?;
+
+        let mut keys = Vec::new();
+
+        for entry in entries {
+
+            let path = entry.map_err(|e| e.to_string())?.path();
+
+            if path.extension().is_none_or(|extension| extension != "pem") {
+                continue;
+            }
+
+            let kid = path.file_stem().and_then(|stem| stem.to_str())
+                .ok_or(format!("invalid key file name {}", path.display()))?
+                .to_string();
+
+            let pem = fs::read_to_string(&path)
+                .map_err(|e| format!("can't read {}: {e}", path.display()))?;
+
+            keys.push(parse_key(kid, &pem)?);
+        }
+
+        // Same order on every start
+        keys.sort_by(|a, b| a.kid.cmp(&b.kid));
+
+        let active = match env::var("JWT_ACTIVE_KID") {
+            Ok(kid) if !kid.is_empty() => keys.iter().position(|key| key.kid == kid)
+                .ok_or(format!("no key {kid}.pem in {dir}"))?,
+            _ if keys.len() == 1 => 0,
+            _ => return Err(format!("{} keys in {dir}, JWT_ACTIVE_KID must be set", keys.len())),
+        };
+
+        if keys[active].encoding.is_none() {
+            return Err(format!("the active key {} is a public key, it can't sign", keys[active].kid));
+        }
+
+        Ok(JwtKeys { keys, active })
+    }
+
+    /*
+     * Temporary key, tokens won't survive a restart and other instances can't verify them
+     */
+    fn ephemeral() -> Self {
+
+        let mut bytes = [0u8; 4];
+        OsRng.fill_bytes(&mut bytes);
+        let kid = format!("ephemeral-{}", hex::encode(bytes));
+
+        eprintln!("Warning: JWT_EPHEMERAL_KEY=true, using the temporary key {kid} (development only, don't use it in production)");
+
+        let signing_key = SigningKey::generate(&mut OsRng);
+        let key = ed25519_key(kid, &signing_key.verifying_key(), Some(&signing_key))
+            .expect("Failed to create the temporary JWT key");
+
+        JwtKeys { keys: vec![key], active: 0 }
+    }
+
+    /*
+     * The key used to sign the new tokens
+     */
+    pub fn signing_key(&self) -> (&JwtKey, &EncodingKey) {
+
+        let key = &self.keys[self.active];
+
+        // Checked when loading the keys
+        (key, key.encoding.as_ref().expect("The active JWT key can't sign"))
+    }
+
+    pub fn find(&self, kid: &str) -> Option<&JwtKey> {
+        self.keys.iter().find(|key| key.kid == kid)
+    }
+
+    /*
+     * Public part of all the keys, for the /.well-known/jwks.json endpoint
+     */
+    pub fn jwks(&self) -> JwkSet {
+
+        JwkSet { keys: self.keys.iter().map(|key| key.jwk.clone()).collect() }
+    }
+}
+
+/*
+ * Parse a PEM key, trying each supported format
+ */
+fn parse_key(kid: String, pem: &str) -> Result<JwtKey, String> {
+
+    if let Ok(private_key) = SigningKey::from_pkcs8_pem(pem) {
+        return ed25519_key(kid, &private_key.verifying_key(), Some(&private_key));
+    }
+
+    if let Ok(public_key) = VerifyingKey::from_public_key_pem(pem) {
+        return ed25519_key(kid, &public_key, None);
+    }
+
+    if let Ok(private_key) = RsaPrivateKey::from_pkcs8_pem(pem).or_else(|_| RsaPrivateKey::from_pkcs1_pem(pem)) {
+        return rsa_key(kid, &private_key.to_public_key(), Some(&private_key));
+    }
+
+    if let Ok(public_key) = RsaPublicKey::from_public_key_pem(pem).or_else(|_| RsaPublicKey::from_pkcs1_pem(pem)) {
+        return rsa_key(kid, &public_key, None);
+    }
+
+    Err(format!("{kid}.pem is not an Ed25519 or RSA key"))
+}
+
+fn ed25519_key(kid: String, public_key: &VerifyingKey, private_key: Option<&SigningKey>) -> Result<JwtKey, String> {
+
+    let encoding = match private_key {
+        Some(private_key) => Some(EncodingKey::from_ed_der(
+            private_key.to_pkcs8_der().map_err(|e| e.to_string())?.as_bytes()
+        )),
+        None => None,
+    };
+
+    let x = URL_SAFE_NO_PAD.encode(public_key.as_bytes());
+
+    let decoding = DecodingKey::from_ed_components(&x)
+        .map_err(|e| e.to_string())?;
+
+    let parameters = AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+        key_type: OctetKeyPairType::OctetKeyPair,
+        curve: EllipticCurve::Ed25519,
+        x,
+    });
+
+    Ok(JwtKey {
+        jwk: build_jwk(&kid, KeyAlgorithm::EdDSA, parameters),
+        kid,
+        algorithm: Algorithm::EdDSA,
+        encoding,
+        decoding,
+    })
+}
+
+fn rsa_key(kid: String, public_key: &RsaPublicKey, private_key: Option<&RsaPrivateKey>) -> Result<JwtKey, String> {
+
+    let encoding = match private_key {
+        Some(private_key) => Some(EncodingKey::from_rsa_der(
+            private_key.to_pkcs1_der().map_err(|e| e.to_string())?.as_bytes()
+        )),
+        None => None,
+    };
+
+    let n = URL_SAFE_NO_PAD.encode(public_key.n().to_bytes_be());
+    let e = URL_SAFE_NO_PAD.encode(public_key.e().to_bytes_be());
+
+    let decoding = DecodingKey::from_rsa_components(&n, &e)
+        .map_err(|e| e.to_string())?;
+
+    let parameters = AlgorithmParameters::RSA(RSAKeyParameters {
+        key_type: RSAKeyType::RSA,
+        n,
+        e,
+    });
+
+    Ok(JwtKey {
+        jwk: build_jwk(&kid, KeyAlgorithm::RS256, parameters),
+        kid,
+        algorithm: Algorithm::RS256,
+        encoding,
+        decoding,
+    })
+}
+
+fn build_jwk(kid: &str, algorithm: KeyAlgorithm, parameters: AlgorithmParameters) -> Jwk {
+
+    Jwk {
+        common: CommonParameters {
+            public_key_use: Some(PublicKeyUse::Signature),
+            key_algorithm: Some(algorithm),
+            key_id: Some(kid.to_string()),
+            ..Default::default()
+        },
+        algorithm: parameters,
+    }
+}
@@ -0,0 +1,170 @@
+use std::env;
+use std::sync::LazyLock;
+use argon2::{Argon2, PasswordHasher, PasswordVerifier, password_hash::{PasswordHash, SaltString}};
+use axum::http::StatusCode;
+use rand::rngs::OsRng;
+use sqlx::{PgPool, Row};
+
+// Default policy, can be overridden with the LOGIN_* env variables
+const DEFAULT_MAX_FAILURES: i32 = 5;           // failures of an account before it is locked
+const DEFAULT_IP_MAX_FAILURES: i32 = 20;       // failures of an IP before it is locked
+const DEFAULT_FAILURE_WINDOW_MINUTES: i64 = 15; // failures older than this are forgotten
+const DEFAULT_LOCKOUT_SECONDS: i64 = 30;        // first lock duration, doubled at each new failure
+const DEFAULT_LOCKOUT_MAX_MINUTES: i64 = 60;
+
+// Hash checked when the username doesn't exist, so the response takes the same time
+static DUMMY_HASH: LazyLock<String> = LazyLock::new(|| {
+
+    let salt = SaltString::generate(&mut OsRng);
+
+    Argon2::default()
+        .hash_password(b"not a real password", &salt)
+        .expect("Failed to hash dummy password")
+        .to_string()
+});
+
+/*
+ * Compute the dummy hash at startup, so the first login of an unknown user isn't slower
+ */
+pub fn init_dummy_hash() {
+    LazyLock::force(&DUMMY_HASH);
+}
+
+// Failed attempts policy
+pub struct LoginPolicy {
+    pub max_failures: i32,
+    pub ip_max_failures: i32,
+    pub failure_window: chrono::Duration,
+    pub lockout: chrono::Duration,
+    pub lockout_max: chrono::Duration,
+}
+
+impl LoginPolicy {
+
+    pub fn from_env() -> Self {
+
+        let read = |name: &str, default: i64| -> i64 {
+            env::var(name).ok().and_then(|value| value.parse().ok()).unwrap_or(default)
+        };
+
+        LoginPolicy {
+            max_failures: read("LOGIN_MAX_FAILURES", DEFAULT_MAX_FAILURES as i64) as i32,
+            ip_max_failures: read("LOGIN_IP_MAX_FAILURES", DEFAULT_IP_MAX_FAILURES as i64) as i32,
+            failure_window: chrono::Duration::minutes(read("LOGIN_FAILURE_WINDOW_MINUTES", DEFAULT_FAILURE_WINDOW_MINUTES)),
+            lockout: chrono::Duration::seconds(read("LOGIN_LOCKOUT_SECONDS", DEFAULT_LOCKOUT_SECONDS)),
+            lockout_max: chrono::Duration::minutes(read("LOGIN_LOCKOUT_MAX_MINUTES", DEFAULT_LOCKOUT_MAX_MINUTES)),
+        }
+    }
+
+    /*
+     * Lock duration after a number of failures (exponential backoff)
+     */
+    fn lock_duration(&self, failures: i32, max_failures: i32) -> Option<chrono::Duration> {
+
+        if failures < max_failures {
+            return None;
+        }
+
+        // 2^n, with n capped to not overflow
+        let factor = 1i32 << (failures - max_failures).min(20);
+
+        Some((self.lockout * factor).min(self.lockout_max))
+    }
+}
+
+pub fn account_key(username: &str) -> String {
+    format!("user:{}", username.to_lowercase())
+}
+
+pub fn ip_key(ip: &str) -> String {
+    format!("ip:{ip}")
+}
+
+pub fn two_factor_key(user_id: i32) -> String {
+    format!("2fa:{user_id}")
+}
+
+/*
+ * Verify a password against a stored hash
+ * - If there is no stored hash (unknown user) a dummy hash is checked, to take the same time
+ */
+pub fn verify_password(password: &str, stored_hash: Option<&str>) -> bool {
+
+    let is_known = stored_hash.is_some();
+    let hash = stored_hash.unwrap_or(&DUMMY_HASH);
+
+    let is_valid = match PasswordHash::new(hash) {
+        Ok(parsed_hash) => Argon2::default().verify_password(password.as_bytes(), &parsed_hash).is_ok(),
+        Err(_) => false,
+    };
+
+    is_known && is_valid
+}
+
+/*
+ * Return 429 if one of the keys is currently locked
+ */
+pub async fn ensure_not_locked(pool: &PgPool, keys: &[String]) -> Result<(), StatusCode> {
+
+    let query = sqlx::query("SELECT 1 FROM login_failures WHERE key = ANY($1) AND locked_until > NOW() LIMIT 1")
+        .bind(keys);
+
+    let row = query.fetch_optional(pool).await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    match row {
+        Some(_) => Err(StatusCode::TOO_MANY_REQUESTS),
+        None => Ok(()),
+    }
+}
+
+/*
+ * Count a failed attempt for a key, and lock it if there was too many
+ */
+pub async fn record_failure(pool: &PgPool, policy: &LoginPolicy, key: &str, max_failures: i32) -> Result<(), StatusCode> {
+
+    let now = chrono::Utc::now().naive_utc();
+
+    // Restart counting if the last failure is too old
+    let query = sqlx::query("
+        INSERT INTO login_failures (key, failures, last_failure_at) VALUES ($1, 1, $2)
+        ON CONFLICT (key) DO UPDATE SET
+            failures = CASE WHEN login_failures.last_failure_at < $3 THEN 1 ELSE login_failures.failures + 1 END,
+            last_failure_at = $2
+        RETURNING failures;
+    ")
+    .bind(key)
+    .bind(now)
+    .bind(now - policy.failure_window);
+
+    let row = query.fetch_one(pool).await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let failures: i32 = row.get("failures");
+
+    if let Some(duration) = policy.lock_duration(failures, max_failures) {
+
+        sqlx::query("UPDATE login_failures SET locked_until = $2 WHERE key = $1")
+            .bind(key)
+            .bind(now + duration)
+            .execute(pool)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    }
+
+    Ok(())
+}
+
+/*
+ * Forget the failures of a key after a successful login
+ */
+pub async fn clear_failures(pool: &PgPool, key: &str) -> Result<(), StatusCode> {
+
+    sqlx::query("DELETE FROM login_failures WHERE key = $1")
+        .bind(key)
+        .execute(pool)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(())
+}
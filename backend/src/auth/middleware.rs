@@ -1,48 +1,81 @@
 use axum::{
     body::Body,
-    http::{Request, StatusCode},
+    extract::State,
+    http::{Request, StatusCode, HeaderMap},
     response::Response,
     middleware::Next,
 };
 use headers::{authorization::Bearer, Authorization, HeaderMapExt};
-use crate::auth::token_handler::verify_jwt;
+use sqlx::PgPool;
+use crate::auth::token_handler::{verify_jwt, API_TOKEN_PREFIX};
+use crate::handlers::session_handlers::touch_session;
+use crate::handlers::api_token_handlers::touch_api_token;
 use crate::models::auth::AuthUser;
 
-pub async fn get_auth_user(mut req: Request<Body>, next: Next) -> Result<Response, StatusCode> {
+// AuthUser used when no valid token was given
+const NOT_CONNECTED: AuthUser = AuthUser {
+    user_id: -1,
+    session_id: -1,
+    is_connected: false,
+    scopes: None,
+};
+
+pub async fn get_auth_user(State(pool): State<PgPool>, mut req: Request<Body>, next: Next) -> Result<Response, StatusCode> {
+
+    let auth_user = authenticate(&pool, req.headers()).await;
+
+    // Save the user in the extension
+    req.extensions_mut().insert(auth_user);
+
+    // Go to next request (handler)
+    Ok(next.run(req).await)
+}
+
+/*
+ * Get the user of the token sent in the Authorization header
+ * - Also used by long requests (ex: /stream) to check the token was not revoked meanwhile
+ */
+pub async fn authenticate(pool: &PgPool, headers: &HeaderMap) -> AuthUser {
 
-    // Get client JWT token value
-    let auth_user = match req.headers().typed_get::<Authorization<Bearer>>() {
+    // Get client token value (a JWT or an API token)
+    match headers.typed_get::<Authorization<Bearer>>() {
 
-        // If there is a token
+        // If there is an API token, the user gets only the scopes of the token
+        Some(auth_header) if auth_header.token().starts_with(API_TOKEN_PREFIX) => {
+
+            match touch_api_token(pool, auth_header.token()).await {
+
+                Some((user_id, scopes)) => AuthUser {
+                    user_id,
+                    session_id: -1,
+                    is_connected: true,
+                    scopes: Some(scopes),
+                },
+
+                // If token is unknown, revoked or expired return no user
+                None => NOT_CONNECTED,
+            }
+        }
+
+        // If there is a JWT
         Some(auth_header) => {
 
             match verify_jwt(auth_header.token()) {
 
-                // If token is valide, return auth user
-                Ok(claims) => AuthUser {
+                // If token is valide and its session was not revoked, return auth user
+                Ok(claims) if touch_session(pool, claims.sid, claims.sub).await => AuthUser {
                     user_id: claims.sub,
+                    session_id: claims.sid,
                     is_connected: true,
+                    scopes: None,
                 },
 
-                // If token is not valid return no user
-                Err(_) => AuthUser {
-                    user_id: -1,
-                    is_connected: false,
-                },
+                // If token is not valid (or its session was revoked) return no user
+                _ => NOT_CONNECTED,
             }
         }
 
         // If there is no token, return AuthUser Not connected
-        None => AuthUser {
-            user_id: -1,
-            is_connected: false,
-        },
-    };
-
-    // Save the user in the extension
-    req.extensions_mut().insert(auth_user);
-
-    // Go to next request (handler)
-    Ok(next.run(req).await)
+        None => NOT_CONNECTED,
+    }
 }
-
@@ -1,2 +1,7 @@
 pub mod token_handler;
 pub mod middleware;
+pub mod totp_handler;
+pub mod login_guard;
+pub mod jwt_keys;
+pub mod scopes;
+pub mod permissions;
@@ -0,0 +1,73 @@
+use axum::http::StatusCode;
+use sqlx::{PgPool, Row};
+use crate::models::auth::AuthUser;
+
+// Permissions given by the roles (see the roles & permissions tables)
+pub const POST_DELETE_ANY: &str = "post.delete.any";   // delete the posts of other users
+pub const USER_LIST: &str = "user.list";               // see all users and their data
+pub const USER_SUSPEND: &str = "user.suspend";         // suspend and unsuspend users
+pub const USER_UPDATE_ANY: &str = "user.update.any";   // update the data of other users
+pub const USER_DELETE: &str = "user.delete";
+pub const USER_ROLE_ASSIGN: &str = "user.role.assign"; // change the role of an user
+
+// Role of new users
+pub const DEFAULT_ROLE: &str = "user";
+
+/*
+ * This function is used to know if an user has a permission
+ * - Not connected users have no permission
+ */
+pub async fn get_has_permission(pool: &PgPool, auth_user: &AuthUser, permission: &str) -> Result<bool, StatusCode> {
+
+    if !auth_user.is_connected {
+        return Ok(false);
+    }
+
+    let query = sqlx::query("
+        SELECT 1
+        FROM users u
+        JOIN role_permissions rp ON rp.role_id = u.role_id
+        JOIN permissions p ON p.id = rp.permission_id
+        WHERE u.id = $1 AND p.name = $2;
+    ")
+    .bind(auth_user.user_id)
+    .bind(permission);
+
+    let row = query.fetch_optional(pool).await
+        .map_err(|e| {
+            eprintln!("Error while checking permission {permission}: {e}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    Ok(row.is_some())
+}
+
+/*
+ * Guard of the handlers reserved to some roles
+ * - Returns 401 if the user is not connected and 403 if he doesn't have the permission
+ */
+pub async fn require_permission(pool: &PgPool, auth_user: &AuthUser, permission: &str) -> Result<(), StatusCode> {
+
+    if !auth_user.is_connected {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    match get_has_permission(pool, auth_user, permission).await? {
+        true => Ok(()),
+        false => Err(StatusCode::FORBIDDEN),
+    }
+}
+
+/*
+ * Get the id of a role from its name, None if the role doesn't exist
+ */
+pub async fn get_role_id(pool: &PgPool, role: &str) -> Result<Option<i32>, StatusCode> {
+
+    let query = sqlx::query("SELECT id FROM roles WHERE name = $1")
+        .bind(role);
+
+    let row = query.fetch_optional(pool).await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(row.map(|row| row.get("id")))
+}
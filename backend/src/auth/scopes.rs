@@ -0,0 +1,14 @@
+// Scopes an API token can be given
+// - Login sessions (JWTs) are not limited by scopes
+pub const POSTS_READ: &str = "posts:read";
+pub const POSTS_WRITE: &str = "posts:write";
+pub const USERS_READ: &str = "users:read";
+pub const USERS_WRITE: &str = "users:write";
+pub const MESSAGES_READ: &str = "messages:read";
+pub const MESSAGES_WRITE: &str = "messages:write";
+
+pub const ALL_SCOPES: [&str; 6] = [POSTS_READ, POSTS_WRITE, USERS_READ, USERS_WRITE, MESSAGES_READ, MESSAGES_WRITE];
+
+pub fn is_valid_scope(scope: &str) -> bool {
+    ALL_SCOPES.contains(&scope)
+}
@@ -1,33 +1,175 @@
-use jsonwebtoken::{encode, decode, Header, Validation, EncodingKey, DecodingKey};
+use jsonwebtoken::{encode, decode, decode_header, Header, Validation, errors::ErrorKind};
+use serde::{Serialize, de::DeserializeOwned};
+use rand::{rngs::OsRng, RngCore};
+use sha2::{Digest, Sha256};
 use std::env;
-use crate::models::auth::Claims;
+use crate::models::auth::{Claims, ChallengeClaims};
+use crate::auth::jwt_keys::jwt_keys;
 
-pub fn create_jwt(user_id: i32) -> String {
+// Default lifetimes, can be overridden with ACCESS_TOKEN_MINUTES, REFRESH_TOKEN_DAYS,
+// PASSWORD_RESET_MINUTES, EMAIL_VERIFICATION_HOURS & TWO_FACTOR_CHALLENGE_MINUTES
+const DEFAULT_ACCESS_TOKEN_MINUTES: i64 = 15;
+const DEFAULT_REFRESH_TOKEN_DAYS: i64 = 30;
+const DEFAULT_PASSWORD_RESET_MINUTES: i64 = 60;
+const DEFAULT_EMAIL_VERIFICATION_HOURS: i64 = 48;
+const DEFAULT_TWO_FACTOR_CHALLENGE_MINUTES: i64 = 5;
 
-    let secret = env::var("JWT_SECRET").expect("JWT_SECRET must be set");
+// Prefix of the personal access tokens, so the middleware knows it's not a JWT
+pub const API_TOKEN_PREFIX: &str = "feur_pat_";
+
+// Audience of the 2FA challenge tokens
+const TWO_FACTOR_AUDIENCE: &str = "2fa";
+
+/*
+ * Read a lifetime from the env or fallback to the default value
+ */
+fn env_duration(name: &str, default: i64) -> i64 {
+
+    env::var(name)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+/*
+ * How long an access token (JWT) is valid
+ */
+pub fn access_token_duration() -> chrono::Duration {
+
+    chrono::Duration::minutes(env_duration("ACCESS_TOKEN_MINUTES", DEFAULT_ACCESS_TOKEN_MINUTES))
+}
+
+/*
+ * How long a refresh token is valid
+ */
+pub fn refresh_token_duration() -> chrono::Duration {
+
+    chrono::Duration::days(env_duration("REFRESH_TOKEN_DAYS", DEFAULT_REFRESH_TOKEN_DAYS))
+}
+
+/*
+ * How long a password reset token (sent by mail) is valid
+ */
+pub fn password_reset_duration() -> chrono::Duration {
+
+    chrono::Duration::minutes(env_duration("PASSWORD_RESET_MINUTES", DEFAULT_PASSWORD_RESET_MINUTES))
+}
+
+/*
+ * How long an email verification token (sent by mail) is valid
+ */
+pub fn email_verification_duration() -> chrono::Duration {
+
+    chrono::Duration::hours(env_duration("EMAIL_VERIFICATION_HOURS", DEFAULT_EMAIL_VERIFICATION_HOURS))
+}
+
+/*
+ * Sign claims with the active key, its kid is put in the header so the right key is used to verify
+ */
+fn sign<T: Serialize>(claims: &T) -> String {
+
+    let (key, encoding_key) = jwt_keys().signing_key();
+
+    let mut header = Header::new(key.algorithm);
+    header.kid = Some(key.kid.clone());
+
+    encode(&header, claims, encoding_key).unwrap()
+}
+
+/*
+ * Verify a token with the key named by its kid (tokens without kid are refused)
+ */
+fn verify<T: DeserializeOwned + Clone>(token: &str, audience: Option<&str>) -> Result<T, jsonwebtoken::errors::Error> {
+
+    let header = decode_header(token)?;
+
+    let key = header.kid.as_deref()
+        .and_then(|kid| jwt_keys().find(kid))
+        .ok_or(ErrorKind::InvalidToken)?;
+
+    // Only the algorithm of the key is accepted
+    let mut validation = Validation::new(key.algorithm);
+
+    if let Some(audience) = audience {
+        validation.set_audience(&[audience]);
+    }
+
+    let data = decode::<T>(token, &key.decoding, &validation)?;
+
+    Ok(data.claims)
+}
+
+pub fn create_jwt(user_id: i32, session_id: i32) -> String {
 
     let expiration = chrono::Utc::now()
-        .checked_add_signed(chrono::Duration::hours(24))
+        .checked_add_signed(access_token_duration())
         .unwrap()
         .timestamp() as usize;
 
     let claims = Claims{
         sub: user_id,
+        sid: session_id,
         exp: expiration
     };
 
-    encode(&Header::default(), &claims, &EncodingKey::from_secret(secret.as_ref())).unwrap()
+    sign(&claims)
 }
 
 pub fn verify_jwt(token: &str) -> Result<Claims, jsonwebtoken::errors::Error> {
 
-    let secret = env::var("JWT_SECRET").expect("JWT_SECRET must be set");
+    verify(token, None)
+}
 
-    let data = decode::<Claims>(
-        token,
-        &DecodingKey::from_secret(secret.as_ref()),
-        &Validation::default(),
-    )?;
+/*
+ * Create the short-lived token proving that the user gave a good password, but still needs a 2FA code
+ */
+pub fn create_challenge_jwt(user_id: i32) -> String {
 
-    Ok(data.claims)
+    let expiration = chrono::Utc::now()
+        .checked_add_signed(chrono::Duration::minutes(env_duration("TWO_FACTOR_CHALLENGE_MINUTES", DEFAULT_TWO_FACTOR_CHALLENGE_MINUTES)))
+        .unwrap()
+        .timestamp() as usize;
+
+    let claims = ChallengeClaims {
+        sub: user_id,
+        aud: TWO_FACTOR_AUDIENCE.to_string(),
+        jti: generate_opaque_token(),
+        exp: expiration
+    };
+
+    sign(&claims)
+}
+
+pub fn verify_challenge_jwt(token: &str) -> Result<ChallengeClaims, jsonwebtoken::errors::Error> {
+
+    verify(token, Some(TWO_FACTOR_AUDIENCE))
+}
+
+/*
+ * Generate a new opaque token (32 random bytes, hex encoded)
+ * - Only the client keeps this value, the DB stores its hash
+ */
+pub fn generate_opaque_token() -> String {
+
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+
+    hex::encode(bytes)
+}
+
+/*
+ * Generate a new personal access token (prefix + opaque token)
+ */
+pub fn generate_api_token() -> String {
+
+    format!("{API_TOKEN_PREFIX}{}", generate_opaque_token())
+}
+
+/*
+ * Hash an opaque token before storing or looking it up in the DB
+ * - SHA-256 is enough here since the token is random and not a password
+ */
+pub fn hash_token(token: &str) -> String {
+
+    hex::encode(Sha256::digest(token.as_bytes()))
 }
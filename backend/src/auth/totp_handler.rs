@@ -0,0 +1,76 @@
+use rand::{rngs::OsRng, RngCore};
+use totp_rs::{Algorithm, Secret, TOTP};
+
+// RFC 6238 parameters, the ones supported by every authenticator app
+const TOTP_DIGITS: usize = 6;
+const TOTP_STEP: u64 = 30;
+const TOTP_SKEW: u64 = 1; // accept the previous and the next code (clock drift)
+const TOTP_ISSUER: &str = "FEUR";
+
+// Number of one-time recovery codes given when 2FA is enabled
+pub const RECOVERY_CODES_COUNT: usize = 10;
+
+/*
+ * Build the TOTP generator for a base32 secret
+ */
+fn build_totp(secret: &str, username: &str) -> Option<TOTP> {
+
+    let bytes = Secret::Encoded(secret.to_string()).to_bytes().ok()?;
+
+    // ':' is the separator between issuer and account name in the URI
+    let account_name = username.replace(':', "");
+
+    // The skew is handled in verify_code, to know which step matched
+    TOTP::new(Algorithm::SHA1, TOTP_DIGITS, 0, TOTP_STEP, bytes, Some(TOTP_ISSUER.to_string()), account_name).ok()
+}
+
+/*
+ * Generate a new random secret (160 bits, as recommended by RFC 4226), base32 encoded
+ */
+pub fn generate_secret() -> String {
+
+    let mut bytes = [0u8; 20];
+    OsRng.fill_bytes(&mut bytes);
+
+    Secret::Raw(bytes.to_vec()).to_encoded().to_string()
+}
+
+/*
+ * Create the otpauth:// URI of a secret, authenticator apps can import it (usually with a QR code)
+ */
+pub fn get_otpauth_uri(secret: &str, username: &str) -> Option<String> {
+
+    build_totp(secret, username).map(|totp| totp.get_url())
+}
+
+/*
+ * Check a code for a secret and return the time step it was generated for
+ * - The step is used to refuse a code that was already used (replay)
+ */
+pub fn verify_code(secret: &str, code: &str, last_used_step: Option<i64>) -> Option<i64> {
+
+    let totp = build_totp(secret, "")?;
+
+    let current_step = chrono::Utc::now().timestamp() as u64 / TOTP_STEP;
+
+    // Check the codes of the steps around now
+    (current_step.saturating_sub(TOTP_SKEW)..=current_step + TOTP_SKEW)
+        .find(|step| totp.check(code, step * TOTP_STEP))
+        .map(|step| step as i64)
+        .filter(|step| last_used_step.is_none_or(|last| *step > last))
+}
+
+/*
+ * Generate the one-time recovery codes (format: xxxxx-xxxxx)
+ */
+pub fn generate_recovery_codes() -> Vec<String> {
+
+    (0..RECOVERY_CODES_COUNT)
+        .map(|_| {
+            let mut bytes = [0u8; 5];
+            OsRng.fill_bytes(&mut bytes);
+            let code = hex::encode(bytes);
+            format!("{}-{}", &code[..5], &code[5..])
+        })
+        .collect()
+}
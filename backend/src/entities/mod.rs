@@ -0,0 +1,296 @@
+use serde::{Serialize, Deserialize};
+
+/*
+ * Extraction of the entities of a post content: #hashtags, @mentions and http(s) URLs
+ * - Offsets are in characters (Unicode scalar values), start included and end excluded
+ * - Hashtags are lowercased, mentions keep the username as written (they are resolved with the database)
+ */
+
+// Limits of the columns storing the values
+const MAX_HASHTAG_LENGTH: usize = 100;
+const MAX_USERNAME_LENGTH: usize = 50;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EntityKind {
+    Hashtag,
+    Mention,
+    Url,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ParsedEntity {
+    pub kind: EntityKind,
+    pub start: usize,
+    pub end: usize,
+    pub value: String, // normalized value: tag without # (lowercase), username without @, URL
+}
+
+/*
+ * Find all the entities of a content, in order
+ */
+pub fn parse_entities(content: &str) -> Vec<ParsedEntity> {
+
+    let chars: Vec<char> = content.chars().collect();
+    let mut entities = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+
+        // An entity can't start in the middle of a word (ex: an email is not a mention)
+        if i > 0 && !is_boundary(chars[i - 1]) {
+            i += 1;
+            continue;
+        }
+
+        // URLs first, so the # and @ inside them are not parsed
+        let entity = parse_url(&chars, i)
+            .or_else(|| parse_hashtag(&chars, i))
+            .or_else(|| parse_mention(&chars, i));
+
+        match entity {
+            Some(entity) => {
+                i = entity.end;
+                entities.push(entity);
+            }
+            None => i += 1,
+        }
+    }
+
+    entities
+}
+
+fn is_boundary(c: char) -> bool {
+    !(is_word_char(c) || is_hash(c) || is_at(c))
+}
+
+fn is_word_char(c: char) -> bool {
+    // Combining diacritical marks are kept, for tags written with decomposed accents
+    c.is_alphanumeric() || c == '_' || ('\u{0300}'..='\u{036F}').contains(&c)
+}
+
+fn is_hash(c: char) -> bool {
+    c == '#' || c == '＃' // full width variant, used with CJK input methods
+}
+
+fn is_at(c: char) -> bool {
+    c == '@' || c == '＠'
+}
+
+fn parse_hashtag(chars: &[char], start: usize) -> Option<ParsedEntity> {
+
+    if !is_hash(chars[start]) {
+        return None;
+    }
+
+    let end = start + 1 + chars[start + 1..].iter().take_while(|c| is_word_char(**c)).count();
+    let tag = &chars[start + 1..end];
+
+    // The lowercase form is saved, it can be longer ('İ' is 2 characters in lowercase)
+    let value = tag.iter().collect::<String>().to_lowercase();
+
+    // Not a tag: empty, only digits (#1) or too long
+    if tag.is_empty() || tag.iter().all(|c| c.is_numeric()) || value.chars().count() > MAX_HASHTAG_LENGTH {
+        return None;
+    }
+
+    Some(ParsedEntity {
+        kind: EntityKind::Hashtag,
+        start,
+        end,
+        value,
+    })
+}
+
+fn parse_mention(chars: &[char], start: usize) -> Option<ParsedEntity> {
+
+    if !is_at(chars[start]) {
+        return None;
+    }
+
+    let mut end = start + 1 + chars[start + 1..].iter()
+        .take_while(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.'))
+        .count();
+
+    // The end of the sentence is not part of the username
+    while end > start + 1 && matches!(chars[end - 1], '.' | '-') {
+        end -= 1;
+    }
+
+    let username = &chars[start + 1..end];
+
+    if username.is_empty() || username.len() > MAX_USERNAME_LENGTH {
+        return None;
+    }
+
+    Some(ParsedEntity {
+        kind: EntityKind::Mention,
+        start,
+        end,
+        value: username.iter().collect(),
+    })
+}
+
+fn parse_url(chars: &[char], start: usize) -> Option<ParsedEntity> {
+
+    let scheme_length = ["https://", "http://"].iter()
+        .find(|scheme| starts_with_ignore_case(&chars[start..], scheme))
+        .map(|scheme| scheme.len())?;
+
+    let mut end = start + chars[start..].iter()
+        .take_while(|c| !c.is_whitespace() && !matches!(c, '<' | '>' | '"'))
+        .count();
+
+    // Remove the punctuation around the URL: trailing dots, commas... and unbalanced brackets
+    while end > start + scheme_length {
+
+        let last = chars[end - 1];
+        let url = &chars[start..end];
+
+        let is_unbalanced = match last {
+            ')' => count(url, ')') > count(url, '('),
+            ']' => count(url, ']') > count(url, '['),
+            _ => false,
+        };
+
+        if matches!(last, '.' | ',' | ';' | ':' | '!' | '?' | '\'') || is_unbalanced {
+            end -= 1;
+        } else {
+            break;
+        }
+    }
+
+    let url: String = chars[start..end].iter().collect();
+
+    // A host is needed
+    let host_length = url[scheme_length..].split(['/', '?', '#']).next().unwrap_or("").len();
+
+    if host_length == 0 {
+        return None;
+    }
+
+    // Scheme and host are case insensitive
+    let host_end = scheme_length + host_length;
+    let value = format!("{}{}", url[..host_end].to_lowercase(), &url[host_end..]);
+
+    Some(ParsedEntity { kind: EntityKind::Url, start, end, value })
+}
+
+fn starts_with_ignore_case(chars: &[char], prefix: &str) -> bool {
+
+    chars.len() >= prefix.len()
+        && chars.iter().zip(prefix.chars()).all(|(a, b)| a.to_ascii_lowercase() == b)
+}
+
+fn count(chars: &[char], target: char) -> usize {
+    chars.iter().filter(|c| **c == target).count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entity(kind: EntityKind, start: usize, end: usize, value: &str) -> ParsedEntity {
+        ParsedEntity { kind, start, end, value: value.to_string() }
+    }
+
+    #[test]
+    fn parses_all_kinds() {
+
+        let entities = parse_entities("Hi @alice, see #Rust at https://rust-lang.org");
+
+        assert_eq!(entities, vec![
+            entity(EntityKind::Mention, 3, 9, "alice"),
+            entity(EntityKind::Hashtag, 15, 20, "rust"),
+            entity(EntityKind::Url, 24, 45, "https://rust-lang.org"),
+        ]);
+    }
+
+    #[test]
+    fn empty_and_plain_content() {
+
+        assert!(parse_entities("").is_empty());
+        assert!(parse_entities("nothing to see here # @ http://").is_empty());
+    }
+
+    #[test]
+    fn offsets_are_in_characters() {
+
+        // The emoji is 4 bytes but a single character
+        let entities = parse_entities("🦀 #crabe");
+
+        assert_eq!(entities, vec![entity(EntityKind::Hashtag, 2, 8, "crabe")]);
+    }
+
+    #[test]
+    fn unicode_hashtags() {
+
+        assert_eq!(parse_entities("#Café"), vec![entity(EntityKind::Hashtag, 0, 5, "café")]);
+        assert_eq!(parse_entities("#日本語"), vec![entity(EntityKind::Hashtag, 0, 4, "日本語")]);
+        assert_eq!(parse_entities("＃タグ"), vec![entity(EntityKind::Hashtag, 0, 3, "タグ")]);
+
+        // e + combining acute accent
+        assert_eq!(parse_entities("#cafe\u{301}"), vec![entity(EntityKind::Hashtag, 0, 6, "cafe\u{301}")]);
+    }
+
+    #[test]
+    fn hashtag_edge_cases() {
+
+        // Only digits, in a word, doubled
+        assert!(parse_entities("#1 issue#2 abc#def ##tag").is_empty());
+
+        // Stops at the punctuation
+        assert_eq!(parse_entities("(#tag)."), vec![entity(EntityKind::Hashtag, 1, 5, "tag")]);
+        assert_eq!(parse_entities("#2024_recap"), vec![entity(EntityKind::Hashtag, 0, 11, "2024_recap")]);
+
+        // Too long for the hashtags table
+        assert!(parse_entities(&format!("#{}", "a".repeat(MAX_HASHTAG_LENGTH + 1))).is_empty());
+
+        // Too long once in lowercase ('İ' becomes 'i' + combining dot)
+        assert!(parse_entities(&format!("#{}", "İ".repeat(MAX_HASHTAG_LENGTH / 2 + 1))).is_empty());
+        assert_eq!(parse_entities("#İstanbul")[0].value, "i\u{307}stanbul");
+    }
+
+    #[test]
+    fn mention_edge_cases() {
+
+        // An email is not a mention
+        assert!(parse_entities("mail me at bob@example.com").is_empty());
+
+        // The end of the sentence is not part of the username
+        assert_eq!(parse_entities("thanks @bob."), vec![entity(EntityKind::Mention, 7, 11, "bob")]);
+        assert_eq!(parse_entities("@jean.dupont-2"), vec![entity(EntityKind::Mention, 0, 14, "jean.dupont-2")]);
+        assert_eq!(parse_entities("＠Zoé"), vec![entity(EntityKind::Mention, 0, 4, "Zoé")]);
+
+        assert!(parse_entities("@ alone @.").is_empty());
+    }
+
+    #[test]
+    fn url_edge_cases() {
+
+        // Trailing punctuation and unbalanced brackets are not part of the URL
+        assert_eq!(
+            parse_entities("see https://example.com/a?b=1."),
+            vec![entity(EntityKind::Url, 4, 29, "https://example.com/a?b=1")],
+        );
+        assert_eq!(
+            parse_entities("(https://en.wikipedia.org/wiki/Rust_(language))"),
+            vec![entity(EntityKind::Url, 1, 46, "https://en.wikipedia.org/wiki/Rust_(language)")],
+        );
+
+        // Scheme and host are lowercased, not the path
+        assert_eq!(
+            parse_entities("HTTP://Example.COM/Path"),
+            vec![entity(EntityKind::Url, 0, 23, "http://example.com/Path")],
+        );
+
+        // The # and @ of an URL are not entities
+        assert_eq!(
+            parse_entities("https://example.com/@bob#top"),
+            vec![entity(EntityKind::Url, 0, 28, "https://example.com/@bob#top")],
+        );
+
+        // Not an URL in the middle of a word
+        assert!(parse_entities("xhttps://example.com").is_empty());
+    }
+}
@@ -0,0 +1,155 @@
+use axum::{extract::{Path, Extension, State}, Json, http::StatusCode};
+use sqlx::{PgPool, Row};
+
+use crate::models::api_token::{ApiToken, CreateApiTokenRequest, CreatedApiToken};
+use crate::models::auth::AuthUser;
+use crate::auth::token_handler::{generate_api_token, hash_token};
+use crate::auth::scopes::is_valid_scope;
+
+/*
+ * List the active API tokens of the connected user
+ * @auth {Session} - only for users connected with a password, not with an API token
+ */
+pub async fn list_tokens(Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>) -> Result<Json<Vec<ApiToken>>, StatusCode> {
+
+    // If user is not connected we return 401
+    if !auth_user.is_connected {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    // API tokens can't manage the API tokens, return 403
+    if !auth_user.is_session() {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let query = sqlx::query_as::<_, ApiToken>("
+        SELECT id, name, scopes, created_at, expires_at, last_used_at
+        FROM api_tokens
+        WHERE user_id = $1 AND revoked_at IS NULL AND (expires_at IS NULL OR expires_at > NOW())
+        ORDER BY created_at DESC, id DESC;
+    ")
+    .bind(auth_user.user_id);
+
+    let tokens = query.fetch_all(&pool).await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?; // Return 500 if SQL request failed
+
+    Ok(Json(tokens))
+}
+
+/*
+ * Create an API token for the connected user
+ * - The token value is only returned here, only its hash is saved
+ * @auth {Session} - only for users connected with a password, not with an API token
+ * @param {CreateApiTokenRequest} - name, scopes and optional lifetime of the token
+ */
+pub async fn create_token(Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>, Json(payload): Json<CreateApiTokenRequest>) -> Result<Json<CreatedApiToken>, StatusCode> {
+
+    // If user is not connected we return 401
+    if !auth_user.is_connected {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    // API tokens can't create other tokens, return 403
+    if !auth_user.is_session() {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let name = payload.name.trim();
+
+    // Return 400 if the name, the scopes or the lifetime are not valid
+    if name.is_empty() || name.chars().count() > 100 {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    if payload.scopes.is_empty() || !payload.scopes.iter().all(|scope| is_valid_scope(scope)) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let expires_at = match payload.expires_in_days {
+        Some(days) if days > 0 => Some(chrono::Utc::now().naive_utc() + chrono::Duration::days(days.min(3650))),
+        Some(_) => return Err(StatusCode::BAD_REQUEST),
+        None => None,
+    };
+
+    let mut scopes = payload.scopes;
+    scopes.sort();
+    scopes.dedup();
+
+    let token = generate_api_token();
+
+    let query = sqlx::query_as::<_, ApiToken>("
+        INSERT INTO api_tokens (user_id, name, token_hash, scopes, expires_at)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING id, name, scopes, created_at, expires_at, last_used_at;
+    ")
+    .bind(auth_user.user_id)
+    .bind(name)
+    .bind(hash_token(&token))
+    .bind(&scopes)
+    .bind(expires_at);
+
+    let api_token = query.fetch_one(&pool).await
+        .map_err(|e| {
+            eprintln!("Error creating API token: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    Ok(Json(CreatedApiToken { api_token, token }))
+}
+
+/*
+ * Revoke an API token of the connected user
+ * @auth {Session} - users can only revoke there own tokens
+ * @param {id} - token id you want to revoke
+ */
+pub async fn revoke_token(Path(id): Path<i32>, Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>) -> StatusCode {
+
+    // If user is not connected we return 401
+    if !auth_user.is_connected {
+        return StatusCode::UNAUTHORIZED;
+    }
+
+    // API tokens can't manage the API tokens, return 403
+    if !auth_user.is_session() {
+        return StatusCode::FORBIDDEN;
+    }
+
+    let query = sqlx::query("UPDATE api_tokens SET revoked_at = NOW() WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL")
+        .bind(id)
+        .bind(auth_user.user_id);
+
+    match query.execute(&pool).await {
+
+        Ok(res) if res.rows_affected() > 0 => StatusCode::NO_CONTENT, // 204
+        Ok(_) => StatusCode::NOT_FOUND, // 404 if no active token found
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR, // 500
+    }
+}
+
+/*
+ * Find the user and the scopes of an API token, and mark it as used
+ * - Returns None if the token is unknown, revoked or expired, or if its user is suspended
+ * - This is not an hanlder, but an helper function used by the auth middleware
+ */
+pub async fn touch_api_token(pool: &PgPool, token: &str) -> Option<(i32, Vec<String>)> {
+
+    let query = sqlx::query("
+        UPDATE api_tokens t SET last_used_at = NOW()
+        FROM users u
+        WHERE t.token_hash = $1 AND t.revoked_at IS NULL AND (t.expires_at IS NULL OR t.expires_at > NOW())
+            AND u.id = t.user_id AND u.suspended_at IS NULL
+        RETURNING t.user_id, t.scopes;
+    ")
+    .bind(hash_token(token));
+
+    match query.fetch_optional(pool).await {
+
+        Ok(row) => row.map(|row| (row.get("user_id"), row.get("scopes"))),
+
+        // If there was an error, we consider the token as not valid
+        Err(e) => {
+            eprintln!("Error while checking API token: {e}");
+            None
+        }
+    }
+}
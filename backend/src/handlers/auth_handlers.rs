@@ -1,96 +1,305 @@
+use std::net::SocketAddr;
 use sqlx::{PgPool, Row};
-use axum::{Json, extract::State};
-use axum::http::StatusCode;
-use argon2::{Argon2, PasswordVerifier, password_hash::PasswordHash};
+use axum::{Json, extract::{State, ConnectInfo}};
+use axum::http::{StatusCode, HeaderMap, header::USER_AGENT};
+use jsonwebtoken::jwk::JwkSet;
 
-use crate::models::auth::{AuthUser, LoginRequest, TokenResponse};
-use crate::auth::token_handler::create_jwt;
+use crate::models::auth::{AuthUser, LoginRequest, LoginResponse, TokenResponse, RefreshRequest, TwoFactorChallenge};
+use crate::auth::token_handler::{create_jwt, create_challenge_jwt, generate_opaque_token, hash_token, refresh_token_duration};
+use crate::auth::jwt_keys::jwt_keys;
+use crate::auth::login_guard::{LoginPolicy, account_key, ip_key, verify_password, ensure_not_locked, record_failure, clear_failures};
+use crate::handlers::session_handlers::create_session;
+use crate::handlers::two_factor_handlers::get_has_two_factor;
 
 /*
  * Try to log user with username and password
+ * - Each successful login creates a new session (device)
+ * - If the user enabled 2FA, a challenge token is returned instead, see two_factor_handlers::login_2fa
+ * - Unknown users and wrong passwords get the same response, failed attempts can lock the account and the IP
  * @auth {None} - no authorization needed
  */
-pub async fn login(State(pool): State<PgPool>, Json(payload): Json<LoginRequest>) -> Result<Json<TokenResponse>, StatusCode> {
+pub async fn login(State(pool): State<PgPool>, ConnectInfo(addr): ConnectInfo<SocketAddr>, headers: HeaderMap, Json(payload): Json<LoginRequest>) -> Result<Json<LoginResponse>, StatusCode> {
 
     let username = payload.username;
     let password = payload.password;
 
-    let query = sqlx::query("SELECT id, password FROM users WHERE username = $1").bind(username);
+    let policy = LoginPolicy::from_env();
+    let account = account_key(&username);
+    let ip = ip_key(&addr.ip().to_string());
 
-    let result = query.fetch_one(&pool).await;
+    // Return 429 if there was too many failed attempts
+    ensure_not_locked(&pool, &[account.clone(), ip.clone()]).await?;
 
-    match result {
+    let query = sqlx::query("SELECT id, password FROM users WHERE username = $1").bind(&username);
 
-        Ok(row) => {
+    let row = query.fetch_optional(&pool).await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-            // Get DB datas
-            let db_id: i32 = row.get("id");
-            let db_password: String = row.get("password");
+    // Get DB datas (if the user exists)
+    let db_id: Option<i32> = row.as_ref().map(|row| row.get("id"));
+    let db_password: Option<String> = row.as_ref().map(|row| row.get("password"));
 
-            // Verify if the hash is correct
-            let parsed_hash = PasswordHash::new(&db_password)
-                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    // Check if hashed pasword is good (a dummy hash is checked for unknown users)
+    let is_valid = verify_password(&password, db_password.as_deref());
 
-            // Check if hashed pasword is good
-            if Argon2::default().verify_password(password.as_bytes(), &parsed_hash).is_ok() {
+    let db_id = match db_id {
 
-                let token = create_jwt(db_id);
+        Some(id) if is_valid => id,
 
-                return Ok(Json(TokenResponse { token }));
-            }
+        // If we reach here, the password verification failed or user not found
+        _ => {
+            record_failure(&pool, &policy, &account, policy.max_failures).await?;
+            record_failure(&pool, &policy, &ip, policy.ip_max_failures).await?;
+
+            return Err(StatusCode::UNAUTHORIZED);
         }
+    };
 
-        Err(e) => {
+    clear_failures(&pool, &account).await?;
 
-            if let sqlx::Error::RowNotFound = e {
+    let response = finish_login(&pool, db_id, &addr, &headers).await?;
 
-                // If no rows are found, return a specific error (like 204 No Content)
-                return Err(StatusCode::NO_CONTENT);
-            }
+    Ok(Json(response))
+}
 
-            // Handle other types of database errors
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
-        }
+/*
+ * Exchange a refresh token for a new access token and a new refresh token
+ * - The used refresh token cannot be used again (rotation)
+ * - If an already used token is sent again, the whole family is revoked
+ * @auth {None} - the refresh token is the authorization
+ * @param {RefreshRequest} - the refresh token
+ */
+pub async fn refresh(State(pool): State<PgPool>, Json(payload): Json<RefreshRequest>) -> Result<Json<TokenResponse>, StatusCode> {
+
+    let token_hash = hash_token(&payload.refresh_token);
+
+    // Start transaction, the row is locked so two concurrent refresh cannot both succeed
+    let mut tx = pool.begin().await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let query = sqlx::query("
+        SELECT
+            id,
+            user_id,
+            session_id,
+            family_id,
+            used_at IS NOT NULL OR revoked_at IS NOT NULL AS is_spent,
+            expires_at < NOW() AS is_expired
+        FROM refresh_tokens
+        WHERE token_hash = $1
+        FOR UPDATE;
+    ")
+    .bind(&token_hash);
+
+    let row = query.fetch_optional(&mut *tx).await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::UNAUTHORIZED)?; // Unknown token
+
+    let id: i32 = row.get("id");
+    let user_id: i32 = row.get("user_id");
+    let session_id: i32 = row.get("session_id");
+    let family_id: sqlx::types::Uuid = row.get("family_id");
+    let is_spent: bool = row.get("is_spent");
+    let is_expired: bool = row.get("is_expired");
+
+    // Token reuse : someone else may own a copy of it, revoke every token of the family
+    if is_spent {
+
+        revoke_family(&mut tx, family_id).await?;
+
+        tx.commit().await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    if is_expired {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    // Mark the current token as used
+    sqlx::query("UPDATE refresh_tokens SET used_at = NOW() WHERE id = $1")
+        .bind(id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    // Create the next token of the family
+    let refresh_token = generate_opaque_token();
+    let expires_at = chrono::Utc::now().naive_utc() + refresh_token_duration();
+
+    sqlx::query("INSERT INTO refresh_tokens (user_id, session_id, family_id, token_hash, expires_at) VALUES ($1, $2, $3, $4, $5)")
+        .bind(user_id)
+        .bind(session_id)
+        .bind(family_id)
+        .bind(hash_token(&refresh_token))
+        .bind(expires_at)
+        .execute(&mut *tx)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    // The session is still in use
+    sqlx::query("UPDATE sessions SET last_seen_at = NOW() WHERE id = $1")
+        .bind(session_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    // Commit -> Apply all queries
+    tx.commit().await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let token = create_jwt(user_id, session_id);
+
+    Ok(Json(TokenResponse { token, refresh_token }))
+}
+
+/*
+ * Logout : revoke the session of the refresh token and all the tokens of its family
+ * - Access tokens of this session are rejected by the middleware once the session is revoked
+ * @auth {None} - the refresh token is the authorization
+ * @param {RefreshRequest} - the refresh token
+ */
+pub async fn logout(State(pool): State<PgPool>, Json(payload): Json<RefreshRequest>) -> StatusCode {
+
+    let token_hash = hash_token(&payload.refresh_token);
+
+    let query = sqlx::query("
+        WITH revoked AS (
+            UPDATE refresh_tokens SET revoked_at = NOW()
+            WHERE revoked_at IS NULL
+            AND family_id = (SELECT family_id FROM refresh_tokens WHERE token_hash = $1)
+            RETURNING session_id
+        )
+        UPDATE sessions SET revoked_at = NOW()
+        WHERE revoked_at IS NULL
+        AND id IN (SELECT session_id FROM revoked);
+    ")
+    .bind(&token_hash);
+
+    match query.execute(&pool).await {
+
+        Ok(res) if res.rows_affected() > 0 => StatusCode::NO_CONTENT, // 204
+        Ok(_) => StatusCode::UNAUTHORIZED, // 401 if unknown or already revoked token
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR, // 500
     }
+}
+
+/*
+ * Public keys used to verify the JWTs, so other services can check them without sharing a secret
+ * - During a key rotation the old keys are still listed until their tokens expire
+ * @auth {None} - no authorization needed
+ */
+pub async fn jwks() -> Json<JwkSet> {
 
-    // If we reach here, the password verification failed or user not found
-    Err(StatusCode::UNAUTHORIZED)
+    Json(jwt_keys().jwks())
 }
 
 /*
- * This function is used to ensure that user is admin in the DB
+ * Last step of a login once the user is identified (password, OIDC provider, ...)
+ * - If the user enabled 2FA a challenge token is returned, and no session is created yet
  * - This is not an hanlder, but an helper function
  */
-pub async fn get_is_admin(pool: &PgPool, auth_user: &AuthUser) -> bool {
+pub async fn finish_login(pool: &PgPool, user_id: i32, addr: &SocketAddr, headers: &HeaderMap) -> Result<LoginResponse, StatusCode> {
 
-    // If user is not connected we can simply return false
-    if !auth_user.is_connected {
-        return false;
+    if get_has_two_factor(pool, user_id).await? {
+
+        let challenge = TwoFactorChallenge {
+            two_factor_required: true,
+            challenge_token: create_challenge_jwt(user_id),
+        };
+
+        return Ok(LoginResponse::TwoFactorRequired(challenge));
     }
 
-    // Get user connected id
-    let id = auth_user.user_id;
+    let tokens = start_session(pool, user_id, addr, headers).await?;
 
-    // Get is_admin row in datbase for this user
-    let query = sqlx::query("SELECT is_admin FROM users WHERE id = $1")
-        .bind(id);
+    Ok(LoginResponse::Tokens(tokens))
+}
 
-    // Execute query
-    let result = query.fetch_one(pool).await;
+/*
+ * Create a session for the device making the request, and its tokens
+ * - This is not an hanlder, but an helper function
+ */
+pub async fn start_session(pool: &PgPool, user_id: i32, addr: &SocketAddr, headers: &HeaderMap) -> Result<TokenResponse, StatusCode> {
 
-    match result {
+    // Save the device used to login
+    let user_agent = headers.get(USER_AGENT).and_then(|value| value.to_str().ok());
+    let session_id = create_session(pool, user_id, user_agent, &addr.ip().to_string()).await?;
 
-        // If we successfully fetch_one, we can return the is_admin row
-        Ok(row) => {
+    issue_tokens(pool, user_id, session_id).await
+}
 
-            let is_admin: bool = row.get("is_admin");
-            return is_admin;
-        }
+/*
+ * Create an access token and the first refresh token of a new family
+ * - This is not an hanlder, but an helper function
+ */
+pub async fn issue_tokens(pool: &PgPool, user_id: i32, session_id: i32) -> Result<TokenResponse, StatusCode> {
+
+    let refresh_token = generate_opaque_token();
+    let expires_at = chrono::Utc::now().naive_utc() + refresh_token_duration();
+
+    // family_id is generated by the DB
+    let query = sqlx::query("INSERT INTO refresh_tokens (user_id, session_id, token_hash, expires_at) VALUES ($1, $2, $3, $4)")
+        .bind(user_id)
+        .bind(session_id)
+        .bind(hash_token(&refresh_token))
+        .bind(expires_at);
+
+    query.execute(pool).await
+        .map_err(|e| {
+            eprintln!("Error creating refresh token: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let token = create_jwt(user_id, session_id);
+
+    Ok(TokenResponse { token, refresh_token })
+}
+
+/*
+ * Revoke all the tokens of a refresh token family
+ * - This is not an hanlder, but an helper function
+ */
+async fn revoke_family(tx: &mut sqlx::Transaction<'_, sqlx::Postgres>, family_id: sqlx::types::Uuid) -> Result<(), StatusCode> {
+
+    sqlx::query("UPDATE refresh_tokens SET revoked_at = NOW() WHERE family_id = $1 AND revoked_at IS NULL")
+        .bind(family_id)
+        .execute(&mut **tx)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(())
+}
+
+/*
+ * This function is used to ensure that user verified his email
+ * - Always true if REQUIRE_VERIFIED_EMAIL is not enabled
+ * - This is not an hanlder, but an helper function
+ */
+pub async fn get_is_verified(pool: &PgPool, auth_user: &AuthUser) -> bool {
+
+    // The restriction is disabled by default
+    if std::env::var("REQUIRE_VERIFIED_EMAIL").as_deref() != Ok("true") {
+        return true;
+    }
+
+    // If user is not connected we can simply return false
+    if !auth_user.is_connected {
+        return false;
+    }
+
+    let query = sqlx::query("SELECT email_verified_at IS NOT NULL AS is_verified FROM users WHERE id = $1")
+        .bind(auth_user.user_id);
+
+    match query.fetch_one(pool).await {
+
+        Ok(row) => row.get("is_verified"),
 
         // If there was an error in the fetch, print an error and return false
         Err(e) => {
-            eprintln!("Error while check is_admin: {e}");
-            return false;
+            eprintln!("Error while check is_verified: {e}");
+            false
         }
     }
 }
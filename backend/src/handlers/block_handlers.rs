@@ -0,0 +1,485 @@
+use std::collections::HashSet;
+use axum::{extract::{Path, State, Extension, Query}, Json, http::StatusCode};
+use sqlx::{PgPool, Row};
+use serde_json::json;
+
+use crate::models::block::HiddenUser;
+use crate::models::auth::AuthUser;
+use crate::auth::scopes::{USERS_READ, USERS_WRITE};
+use crate::handlers::{DEFAULT_LIMIT, DEFAULT_OFFSET, PaginationQuery};
+use crate::handlers::follow_handlers::get_user_exists;
+use crate::handlers::stream_handlers::publish_event;
+use crate::stream::{RELATIONS_EVENT, LIKES_EVENT};
+
+/*
+ * Block an user: both users don't see each other anymore, and they stop following each other
+ * - The likes, reposts and bookmarks of each one on the posts of the other one are removed
+ * - The blocked user can't like, reply, quote, repost, follow or message the connected user
+ * @auth {Connected} - only for connected users
+ * @param {id} - id of the user you want to block
+ */
+pub async fn block_user(Path(id): Path<i32>, Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>) -> StatusCode {
+
+    // Return 401 if user not connected
+    if !auth_user.is_connected {
+        return StatusCode::UNAUTHORIZED;
+    }
+
+    // If the API token doesn't have the users:write scope, return 403
+    if !auth_user.has_scope(USERS_WRITE) {
+        return StatusCode::FORBIDDEN;
+    }
+
+    // Users can't block themselves, return 400
+    if auth_user.user_id == id {
+        return StatusCode::BAD_REQUEST;
+    }
+
+    // Start transaction
+    let mut tx = match pool.begin().await {
+        Ok(t) => t,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR,
+    };
+
+    // Insert block, only if the blocked user exists
+    let insert_result = sqlx::query("
+        INSERT INTO blocks (blocker_id, blocked_id)
+        SELECT $1, id FROM users WHERE id = $2
+        ON CONFLICT DO NOTHING;
+    ")
+    .bind(auth_user.user_id)
+    .bind(id)
+    .execute(&mut *tx)
+    .await;
+
+    match insert_result {
+        Ok(res) if res.rows_affected() > 0 => {},
+        Ok(_) => {
+            // Already blocked (409) or no user found (404)
+            return match get_user_exists(&pool, id).await {
+                Ok(true) => StatusCode::CONFLICT,
+                Ok(false) => StatusCode::NOT_FOUND,
+                Err(status) => status,
+            };
+        }
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR,
+    }
+
+    // Remove the follows in both directions, and update the follow counts of both users
+    let update_result = sqlx::query("
+        WITH removed AS (
+            DELETE FROM follows
+            WHERE (follower_id = $1 AND followed_id = $2) OR (follower_id = $2 AND followed_id = $1)
+            RETURNING follower_id, followed_id
+        )
+        UPDATE users u SET
+            followers_count = followers_count - (SELECT COUNT(*) FROM removed WHERE followed_id = u.id),
+            following_count = following_count - (SELECT COUNT(*) FROM removed WHERE follower_id = u.id)
+        WHERE u.id IN ($1, $2);
+    ")
+    .bind(auth_user.user_id)
+    .bind(id)
+    .execute(&mut *tx)
+    .await;
+
+    if update_result.is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    }
+
+    // Remove the likes in both directions, and update the likes counts of the posts
+    let likes_result = sqlx::query("
+        WITH removed AS (
+            DELETE FROM user_likes l USING posts p
+            WHERE p.id = l.post_id AND ((l.user_id = $1 AND p.user_id = $2) OR (l.user_id = $2 AND p.user_id = $1))
+            RETURNING l.post_id
+        )
+        UPDATE posts p SET likes_count = likes_count - r.count
+        FROM (SELECT post_id, COUNT(*) AS count FROM removed GROUP BY post_id) r
+        WHERE p.id = r.post_id
+        RETURNING p.id, p.likes_count;
+    ")
+    .bind(auth_user.user_id)
+    .bind(id)
+    .fetch_all(&mut *tx)
+    .await;
+
+    let liked_posts = match likes_result {
+        Ok(rows) => rows,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR,
+    };
+
+    // Push the new counts to the connected clients
+    for post in liked_posts {
+        let data = json!({ "post_id": post.get::<i32, _>("id"), "likes_count": post.get::<i32, _>("likes_count") });
+        if let Err(status) = publish_event(&mut tx, LIKES_EVENT, None, data).await {
+            return status;
+        }
+    }
+
+    // Remove the reposts in both directions, and update the reposts counts of the posts
+    let reposts_result = sqlx::query("
+        WITH removed AS (
+            DELETE FROM reposts r USING posts p
+            WHERE p.id = r.post_id AND ((r.user_id = $1 AND p.user_id = $2) OR (r.user_id = $2 AND p.user_id = $1))
+            RETURNING r.post_id
+        )
+        UPDATE posts p SET reposts_count = reposts_count - r.count
+        FROM (SELECT post_id, COUNT(*) AS count FROM removed GROUP BY post_id) r
+        WHERE p.id = r.post_id;
+    ")
+    .bind(auth_user.user_id)
+    .bind(id)
+    .execute(&mut *tx)
+    .await;
+
+    if reposts_result.is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    }
+
+    // Remove the bookmarks in both directions
+    let bookmarks_result = sqlx::query("
+        DELETE FROM bookmarks b USING posts p
+        WHERE p.id = b.post_id AND ((b.user_id = $1 AND p.user_id = $2) OR (b.user_id = $2 AND p.user_id = $1));
+    ")
+    .bind(auth_user.user_id)
+    .bind(id)
+    .execute(&mut *tx)
+    .await;
+
+    if bookmarks_result.is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    }
+
+    // The streams of both users must hide the other one (the blocked user is not told who blocked him)
+    for (user_id, data) in [(auth_user.user_id, json!({ "user_id": id })), (id, json!({}))] {
+        if let Err(status) = publish_event(&mut tx, RELATIONS_EVENT, Some(user_id), data).await {
+            return status;
+        }
+    }
+
+    // Commit -> Apply all queries
+    if tx.commit().await.is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    }
+
+    StatusCode::CREATED
+}
+
+/*
+ * Unblock an user, the follows removed by the block are not restored
+ * @auth {Connected} - only for connected users
+ * @param {id} - id of the user you want to unblock
+ */
+pub async fn unblock_user(Path(id): Path<i32>, Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>) -> StatusCode {
+
+    // Return 401 if user not connected
+    if !auth_user.is_connected {
+        return StatusCode::UNAUTHORIZED;
+    }
+
+    // If the API token doesn't have the users:write scope, return 403
+    if !auth_user.has_scope(USERS_WRITE) {
+        return StatusCode::FORBIDDEN;
+    }
+
+    // Start transaction
+    let mut tx = match pool.begin().await {
+        Ok(t) => t,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR,
+    };
+
+    let delete_result = sqlx::query("DELETE FROM blocks WHERE blocker_id = $1 AND blocked_id = $2")
+        .bind(auth_user.user_id)
+        .bind(id)
+        .execute(&mut *tx)
+        .await;
+
+    match delete_result {
+        Ok(res) if res.rows_affected() > 0 => {},
+        Ok(_) => return StatusCode::NOT_FOUND, // 404 if the user was not blocked
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR,
+    }
+
+    for (user_id, data) in [(auth_user.user_id, json!({ "user_id": id })), (id, json!({}))] {
+        if let Err(status) = publish_event(&mut tx, RELATIONS_EVENT, Some(user_id), data).await {
+            return status;
+        }
+    }
+
+    // Commit -> Apply both queries
+    if tx.commit().await.is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    }
+
+    StatusCode::OK
+}
+
+/*
+ * Mute an user: his posts, reposts and notifications are hidden from the feeds of the connected user
+ * - The muted user is not told, and his posts can still be opened
+ * @auth {Connected} - only for connected users
+ * @param {id} - id of the user you want to mute
+ */
+pub async fn mute_user(Path(id): Path<i32>, Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>) -> StatusCode {
+
+    // Return 401 if user not connected
+    if !auth_user.is_connected {
+        return StatusCode::UNAUTHORIZED;
+    }
+
+    // If the API token doesn't have the users:write scope, return 403
+    if !auth_user.has_scope(USERS_WRITE) {
+        return StatusCode::FORBIDDEN;
+    }
+
+    // Users can't mute themselves, return 400
+    if auth_user.user_id == id {
+        return StatusCode::BAD_REQUEST;
+    }
+
+    // Start transaction
+    let mut tx = match pool.begin().await {
+        Ok(t) => t,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR,
+    };
+
+    // Insert mute, only if the muted user exists
+    let insert_result = sqlx::query("
+        INSERT INTO mutes (muter_id, muted_id)
+        SELECT $1, id FROM users WHERE id = $2
+        ON CONFLICT DO NOTHING;
+    ")
+    .bind(auth_user.user_id)
+    .bind(id)
+    .execute(&mut *tx)
+    .await;
+
+    match insert_result {
+        Ok(res) if res.rows_affected() > 0 => {},
+        Ok(_) => {
+            // Already muted (409) or no user found (404)
+            return match get_user_exists(&pool, id).await {
+                Ok(true) => StatusCode::CONFLICT,
+                Ok(false) => StatusCode::NOT_FOUND,
+                Err(status) => status,
+            };
+        }
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR,
+    }
+
+    // Only the stream of the connected user changes
+    if let Err(status) = publish_event(&mut tx, RELATIONS_EVENT, Some(auth_user.user_id), json!({ "user_id": id })).await {
+        return status;
+    }
+
+    // Commit -> Apply both queries
+    if tx.commit().await.is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    }
+
+    StatusCode::CREATED
+}
+
+/*
+ * Unmute an user
+ * @auth {Connected} - only for connected users
+ * @param {id} - id of the user you want to unmute
+ */
+pub async fn unmute_user(Path(id): Path<i32>, Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>) -> StatusCode {
+
+    // Return 401 if user not connected
+    if !auth_user.is_connected {
+        return StatusCode::UNAUTHORIZED;
+    }
+
+    // If the API token doesn't have the users:write scope, return 403
+    if !auth_user.has_scope(USERS_WRITE) {
+        return StatusCode::FORBIDDEN;
+    }
+
+    // Start transaction
+    let mut tx = match pool.begin().await {
+        Ok(t) => t,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR,
+    };
+
+    let delete_result = sqlx::query("DELETE FROM mutes WHERE muter_id = $1 AND muted_id = $2")
+        .bind(auth_user.user_id)
+        .bind(id)
+        .execute(&mut *tx)
+        .await;
+
+    match delete_result {
+        Ok(res) if res.rows_affected() > 0 => {},
+        Ok(_) => return StatusCode::NOT_FOUND, // 404 if the user was not muted
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR,
+    }
+
+    if let Err(status) = publish_event(&mut tx, RELATIONS_EVENT, Some(auth_user.user_id), json!({ "user_id": id })).await {
+        return status;
+    }
+
+    // Commit -> Apply both queries
+    if tx.commit().await.is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    }
+
+    StatusCode::OK
+}
+
+/*
+ * List the users blocked by the connected user, most recent first
+ * @auth {Connected} - only for connected users
+ */
+pub async fn list_blocks(Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>, Query(pagination): Query<PaginationQuery>) -> Result<Json<Vec<HiddenUser>>, StatusCode> {
+
+    // Return 401 if user not connected
+    if !auth_user.is_connected {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    // If the API token doesn't have the users:read scope, return 403
+    if !auth_user.has_scope(USERS_READ) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let limit = pagination.limit.unwrap_or(DEFAULT_LIMIT);
+    let offset = pagination.offset.unwrap_or(DEFAULT_OFFSET);
+
+    let query = sqlx::query_as::<_, HiddenUser>("
+        SELECT u.id, u.username, u.title, u.created_at, b.created_at AS hidden_at
+        FROM blocks b
+        JOIN users u ON u.id = b.blocked_id
+        WHERE b.blocker_id = $1
+        ORDER BY b.created_at DESC, u.id DESC
+        LIMIT $2
+        OFFSET $3;
+    ")
+    .bind(auth_user.user_id)
+    .bind(limit)
+    .bind(offset);
+
+    let users = query.fetch_all(&pool).await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?; // Return 500 if SQL request failed
+
+    Ok(Json(users))
+}
+
+/*
+ * List the users muted by the connected user, most recent first
+ * @auth {Connected} - only for connected users
+ */
+pub async fn list_mutes(Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>, Query(pagination): Query<PaginationQuery>) -> Result<Json<Vec<HiddenUser>>, StatusCode> {
+
+    // Return 401 if user not connected
+    if !auth_user.is_connected {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    // If the API token doesn't have the users:read scope, return 403
+    if !auth_user.has_scope(USERS_READ) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let limit = pagination.limit.unwrap_or(DEFAULT_LIMIT);
+    let offset = pagination.offset.unwrap_or(DEFAULT_OFFSET);
+
+    let query = sqlx::query_as::<_, HiddenUser>("
+        SELECT u.id, u.username, u.title, u.created_at, m.created_at AS hidden_at
+        FROM mutes m
+        JOIN users u ON u.id = m.muted_id
+        WHERE m.muter_id = $1
+        ORDER BY m.created_at DESC, u.id DESC
+        LIMIT $2
+        OFFSET $3;
+    ")
+    .bind(auth_user.user_id)
+    .bind(limit)
+    .bind(offset);
+
+    let users = query.fetch_all(&pool).await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?; // Return 500 if SQL request failed
+
+    Ok(Json(users))
+}
+
+/*
+ * SQL condition: one of the users blocked the other one
+ * - user and other are SQL expressions (ex: "$1" and "p.user_id")
+ * - This is not an hanlder, but an helper function
+ */
+pub(crate) fn is_blocked(user: &str, other: &str) -> String {
+    format!("EXISTS (SELECT 1 FROM blocks bk WHERE (bk.blocker_id = {user} AND bk.blocked_id = {other}) OR (bk.blocker_id = {other} AND bk.blocked_id = {user}))")
+}
+
+/*
+ * SQL condition: the user muted the other one
+ * - This is not an hanlder, but an helper function
+ */
+pub(crate) fn is_muted(user: &str, other: &str) -> String {
+    format!("EXISTS (SELECT 1 FROM mutes mt WHERE mt.muter_id = {user} AND mt.muted_id = {other})")
+}
+
+/*
+ * SQL condition: the other user must be hidden from the feeds of the user (blocked in any direction, or muted)
+ * - This is not an hanlder, but an helper function
+ */
+pub(crate) fn is_hidden(user: &str, other: &str) -> String {
+    format!("({} OR {})", is_blocked(user, other), is_muted(user, other))
+}
+
+/*
+ * This function is used to know if one of two users blocked the other one
+ * - This is not an hanlder, but an helper function
+ */
+pub(crate) async fn get_is_blocked<'e, E: sqlx::PgExecutor<'e>>(executor: E, user_id: i32, other_id: i32) -> Result<bool, StatusCode> {
+
+    let sql = format!("SELECT {} AS is_blocked", is_blocked("$1", "$2"));
+
+    let row = sqlx::query(&sql)
+        .bind(user_id)
+        .bind(other_id)
+        .fetch_one(executor)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(row.get("is_blocked"))
+}
+
+/*
+ * This function is used to know if the other user is hidden from the user (blocked in any direction, or muted)
+ * - This is not an hanlder, but an helper function
+ */
+pub(crate) async fn get_is_hidden<'e, E: sqlx::PgExecutor<'e>>(executor: E, user_id: i32, other_id: i32) -> Result<bool, StatusCode> {
+
+    let sql = format!("SELECT {} AS is_hidden", is_hidden("$1", "$2"));
+
+    let row = sqlx::query(&sql)
+        .bind(user_id)
+        .bind(other_id)
+        .fetch_one(executor)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(row.get("is_hidden"))
+}
+
+/*
+ * Users whose posts are hidden from the stream of an user: blocked, blocking him or muted
+ * - This is not an hanlder, but an helper function
+ */
+pub(crate) async fn get_hidden_user_ids(pool: &PgPool, user_id: i32) -> Result<HashSet<i32>, sqlx::Error> {
+
+    let rows = sqlx::query("
+        SELECT blocked_id AS id FROM blocks WHERE blocker_id = $1
+        UNION
+        SELECT blocker_id FROM blocks WHERE blocked_id = $1
+        UNION
+        SELECT muted_id FROM mutes WHERE muter_id = $1;
+    ")
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows.iter().map(|row| row.get("id")).collect())
+}
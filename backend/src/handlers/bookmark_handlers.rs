@@ -0,0 +1,139 @@
+use axum::{extract::{Path, State, Extension, Query}, Json, http::StatusCode};
+use sqlx::PgPool;
+use crate::models::post::PostWithUserData;
+use crate::models::bookmark::Bookmarks;
+use crate::models::auth::AuthUser;
+use crate::auth::scopes::{POSTS_READ, POSTS_WRITE};
+use crate::handlers::{DEFAULT_LIMIT, KeysetQuery, encode_cursor, decode_cursor};
+use crate::handlers::block_handlers::is_blocked;
+use crate::handlers::post_handlers::{POST_COLUMNS, POST_JOINS, get_post_exists};
+
+/*
+ * Save a post in the bookmarks
+ * @auth {Conneceted} - only for conneceted users
+ * @param {id} - post's id you want to save
+ */
+pub async fn bookmark_post(Path(id): Path<i32>, Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>) -> StatusCode {
+
+    // Return 401 if user not connected
+    if !auth_user.is_connected {
+        return StatusCode::UNAUTHORIZED;
+    }
+
+    // If the API token doesn't have the posts:write scope, return 403
+    if !auth_user.has_scope(POSTS_WRITE) {
+        return StatusCode::FORBIDDEN;
+    }
+
+    // Insert bookmark, only if the post exists
+    let insert_result = sqlx::query("
+        INSERT INTO bookmarks (user_id, post_id)
+        SELECT $1, id FROM posts WHERE id = $2
+        ON CONFLICT DO NOTHING;
+    ")
+    .bind(auth_user.user_id)
+    .bind(id)
+    .execute(&pool)
+    .await;
+
+    match insert_result {
+        Ok(res) if res.rows_affected() > 0 => StatusCode::CREATED,
+        Ok(_) => {
+            // Already saved (409) or no post found (404)
+            match get_post_exists(&pool, id).await {
+                Ok(true) => StatusCode::CONFLICT,
+                Ok(false) => StatusCode::NOT_FOUND,
+                Err(status) => status,
+            }
+        }
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+/*
+ * Remove a post from the bookmarks
+ * @auth {Conneceted} - only for conneceted users
+ * @param {id} - post's id you want to remove
+ */
+pub async fn unbookmark_post(Path(id): Path<i32>, Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>) -> StatusCode {
+
+    // Return 401 if user not connected
+    if !auth_user.is_connected {
+        return StatusCode::UNAUTHORIZED;
+    }
+
+    // If the API token doesn't have the posts:write scope, return 403
+    if !auth_user.has_scope(POSTS_WRITE) {
+        return StatusCode::FORBIDDEN;
+    }
+
+    let delete_result = sqlx::query("DELETE FROM bookmarks WHERE user_id = $1 AND post_id = $2")
+        .bind(auth_user.user_id)
+        .bind(id)
+        .execute(&pool)
+        .await;
+
+    match delete_result {
+        Ok(res) if res.rows_affected() > 0 => StatusCode::OK,
+        Ok(_) => StatusCode::NOT_FOUND, // 404 if the post was not saved
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+/*
+ * List the posts saved by the connected user, most recently saved first
+ * - Posts of the users blocked by the connected user (or blocking him) are hidden
+ * @auth {Conneceted} - only for conneceted users
+ * @param {KeysetQuery} - cursor (next_cursor of the previous page) & limit
+ */
+pub async fn list_bookmarks(Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>, Query(pagination): Query<KeysetQuery>) -> Result<Json<Bookmarks>, StatusCode> {
+
+    // Return 401 if user not connected
+    if !auth_user.is_connected {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    // If the API token doesn't have the posts:read scope, return 403
+    if !auth_user.has_scope(POSTS_READ) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let limit = pagination.limit.unwrap_or(DEFAULT_LIMIT);
+
+    // Return 400 if the cursor is invalid
+    let cursor = pagination.cursor.as_deref().map(decode_cursor).transpose()?;
+    let (before_bookmarked_at, before_id) = cursor.unzip();
+
+    let blocked_author = is_blocked("$1", "p.user_id");
+
+    let sql = format!("
+        SELECT {POST_COLUMNS}, b.created_at AS bookmarked_at
+        FROM bookmarks b
+        JOIN posts p ON p.id = b.post_id
+        {POST_JOINS}
+        WHERE b.user_id = $1
+            AND NOT {blocked_author}
+            AND ($2::TIMESTAMP IS NULL OR (b.created_at, b.post_id) < ($2, $3))
+        ORDER BY b.created_at DESC, b.post_id DESC
+        LIMIT $4;
+    ");
+
+    let query = sqlx::query_as::<_, PostWithUserData>(&sql)
+        .bind(auth_user.user_id)
+        .bind(before_bookmarked_at)
+        .bind(before_id)
+        .bind(limit);
+
+    let posts = query.fetch_all(&pool).await.map_err(|e| {
+        eprintln!("Error fetching bookmarks: {:?}", e);
+        StatusCode::INTERNAL_SERVER_ERROR // Return 500 if SQL error
+    })?;
+
+    // A full page may have a next one
+    let next_cursor = match posts.last() {
+        Some(last) if posts.len() as i64 == limit => Some(encode_cursor(last.bookmarked_at, last.id)),
+        _ => None,
+    };
+
+    Ok(Json(Bookmarks { posts, next_cursor }))
+}
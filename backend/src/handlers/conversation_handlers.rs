@@ -0,0 +1,520 @@
+use std::collections::HashSet;
+use axum::{extract::{Path, State, Form, Extension, Query}, Json, http::StatusCode};
+use serde_json::json;
+use sqlx::{PgPool, Row};
+use crate::models::conversation::{Conversation, ConversationPage, Message, MessagePage, CreateConversationRequest, FormMessage, FormRead};
+use crate::models::auth::AuthUser;
+use crate::auth::scopes::{MESSAGES_READ, MESSAGES_WRITE};
+use crate::handlers::{DEFAULT_LIMIT, KeysetQuery, encode_cursor, decode_cursor, auth_handlers::get_is_verified};
+use crate::handlers::block_handlers::is_blocked;
+use crate::handlers::stream_handlers::publish_event;
+use crate::stream::MESSAGE_EVENT;
+
+// Largest group, with its creator
+const MAX_GROUP_PARTICIPANTS: usize = 10;
+
+// Longest title of a group (size of the column)
+const MAX_TITLE_LENGTH: usize = 100;
+
+// Columns of Conversation, $1 must be the connected user and cp his participant row
+const CONVERSATION_COLUMNS: &str = "
+    c.id,
+    c.is_group,
+    c.title,
+    c.created_at,
+    c.last_message_at,
+    (
+        SELECT json_agg(json_build_object(
+            'id', u.id,
+            'username', u.username,
+            'title', u.title,
+            'last_read_message_id', p.last_read_message_id
+        ) ORDER BY p.joined_at, u.id)
+        FROM conversation_participants p
+        JOIN users u ON u.id = p.user_id
+        WHERE p.conversation_id = c.id
+    ) AS participants,
+    (
+        SELECT json_build_object(
+            'id', m.id,
+            'conversation_id', m.conversation_id,
+            'sender_id', m.sender_id,
+            'sender_username', su.username,
+            'content', m.content,
+            'created_at', m.created_at
+        )
+        FROM messages m
+        LEFT JOIN users su ON su.id = m.sender_id
+        WHERE m.conversation_id = c.id
+        ORDER BY m.id DESC
+        LIMIT 1
+    ) AS last_message,
+    (
+        SELECT COUNT(*) FROM messages m
+        WHERE m.conversation_id = c.id
+            AND m.id > COALESCE(cp.last_read_message_id, 0)
+            AND m.sender_id IS DISTINCT FROM $1
+    ) AS unread_count
+";
+
+// Columns of Message, after FROM messages m
+const MESSAGE_COLUMNS: &str = "
+    m.id,
+    m.conversation_id,
+    m.sender_id,
+    su.username AS sender_username,
+    m.content,
+    m.created_at
+";
+
+/*
+ * List the conversations of the connected user, the most recently active first
+ * @auth {Conneceted} - only for conneceted users
+ * @param {KeysetQuery} - cursor (next_cursor of the previous page) & limit
+ */
+pub async fn list_conversations(Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>, Query(pagination): Query<KeysetQuery>) -> Result<Json<ConversationPage>, StatusCode> {
+
+    // Return 401 if user not connected
+    if !auth_user.is_connected {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    // If the API token doesn't have the messages:read scope, return 403
+    if !auth_user.has_scope(MESSAGES_READ) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let limit = pagination.limit.unwrap_or(DEFAULT_LIMIT);
+
+    // Return 400 if the cursor is invalid
+    let cursor = pagination.cursor.as_deref().map(decode_cursor).transpose()?;
+    let (before_last_message_at, before_id) = cursor.unzip();
+
+    let sql = format!("
+        SELECT {CONVERSATION_COLUMNS}
+        FROM conversation_participants cp
+        JOIN conversations c ON c.id = cp.conversation_id
+        WHERE cp.user_id = $1
+            AND ($2::TIMESTAMP IS NULL OR (c.last_message_at, c.id) < ($2, $3))
+        ORDER BY c.last_message_at DESC, c.id DESC
+        LIMIT $4;
+    ");
+
+    let query = sqlx::query_as::<_, Conversation>(&sql)
+        .bind(auth_user.user_id)
+        .bind(before_last_message_at)
+        .bind(before_id)
+        .bind(limit);
+
+    let conversations = query.fetch_all(&pool).await.map_err(|e| {
+        eprintln!("Error fetching conversations: {:?}", e);
+        StatusCode::INTERNAL_SERVER_ERROR // Return 500 if SQL error
+    })?;
+
+    // A full page may have a next one
+    let next_cursor = match conversations.last() {
+        Some(last) if conversations.len() as i64 == limit => Some(encode_cursor(Some(last.last_message_at), last.id)),
+        _ => None,
+    };
+
+    Ok(Json(ConversationPage { conversations, next_cursor }))
+}
+
+/*
+ * Start a conversation with one user (returns the existing one if there is one) or a group
+ * - Each user must accept messages from the connected user (see users.dm_policy)
+ * @auth {Conneceted} - only for conneceted users
+ * @param {CreateConversationRequest} - the other participants, and the title of a group
+ */
+pub async fn create_conversation(Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>, Json(payload): Json<CreateConversationRequest>) -> Result<Json<Conversation>, StatusCode> {
+
+    // Return 401 if user not connected
+    if !auth_user.is_connected {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    // If the API token doesn't have the messages:write scope, return 403
+    if !auth_user.has_scope(MESSAGES_WRITE) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    // If the user must verify his email before writing, return 403
+    if !get_is_verified(&pool, &auth_user).await {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    // The other participants, without duplicates
+    let mut seen = HashSet::new();
+    let user_ids: Vec<i32> = payload.user_ids.into_iter()
+        .filter(|id| *id != auth_user.user_id && seen.insert(*id))
+        .collect();
+
+    // Return 400 without other participant or with too many
+    if user_ids.is_empty() || user_ids.len() + 1 > MAX_GROUP_PARTICIPANTS {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let is_group = user_ids.len() > 1;
+    let title = payload.title.map(|title| title.trim().to_string()).filter(|title| is_group && !title.is_empty());
+
+    if title.as_ref().is_some_and(|title| title.chars().count() > MAX_TITLE_LENGTH) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    // Return 404 if an user doesn't exist, 403 if he doesn't accept messages from the connected user
+    for user_id in &user_ids {
+        if !get_can_message(&pool, auth_user.user_id, *user_id).await?.ok_or(StatusCode::NOT_FOUND)? {
+            return Err(StatusCode::FORBIDDEN);
+        }
+    }
+
+    // Only one conversation between two users
+    let direct_key = match user_ids.as_slice() {
+        [user_id] if !is_group => Some(format!("{}:{}", auth_user.user_id.min(*user_id), auth_user.user_id.max(*user_id))),
+        _ => None,
+    };
+
+    // Start transaction
+    let mut tx = pool.begin().await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let query = sqlx::query("
+        INSERT INTO conversations (is_group, title, direct_key, created_by)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (direct_key) DO NOTHING
+        RETURNING id;
+    ")
+    .bind(is_group)
+    .bind(&title)
+    .bind(&direct_key)
+    .bind(auth_user.user_id);
+
+    let conversation_id: i32 = match query.fetch_optional(&mut *tx).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)? {
+
+        Some(row) => {
+
+            let id: i32 = row.get("id");
+
+            let participants: Vec<i32> = user_ids.iter().copied().chain([auth_user.user_id]).collect();
+
+            sqlx::query("INSERT INTO conversation_participants (conversation_id, user_id) SELECT $1, UNNEST($2::INTEGER[])")
+                .bind(id)
+                .bind(&participants)
+                .execute(&mut *tx)
+                .await
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+            id
+        }
+
+        // The one-to-one conversation already exists
+        None => {
+            sqlx::query("SELECT id FROM conversations WHERE direct_key = $1")
+                .bind(&direct_key)
+                .fetch_one(&mut *tx)
+                .await
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+                .get("id")
+        }
+    };
+
+    // Commit -> Apply all queries
+    tx.commit().await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let conversation = get_conversation(&pool, conversation_id, auth_user.user_id).await?
+        .ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Json(conversation))
+}
+
+/*
+ * Get a conversation
+ * @auth {Conneceted} - only for its participants
+ * @param {id} - conversation's id
+ */
+pub async fn get_by_id(Path(id): Path<i32>, Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>) -> Result<Json<Conversation>, StatusCode> {
+
+    // Return 401, 403 or 404 if the user can't read it
+    require_participant(&pool, &auth_user, id, MESSAGES_READ).await?;
+
+    let conversation = get_conversation(&pool, id, auth_user.user_id).await?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    Ok(Json(conversation))
+}
+
+/*
+ * Get the history of a conversation, most recent first
+ * @auth {Conneceted} - only for its participants
+ * @param {id} - conversation's id
+ * @param {KeysetQuery} - cursor (next_cursor of the previous page) & limit
+ */
+pub async fn list_messages(Path(id): Path<i32>, Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>, Query(pagination): Query<KeysetQuery>) -> Result<Json<MessagePage>, StatusCode> {
+
+    // Return 401, 403 or 404 if the user can't read it
+    require_participant(&pool, &auth_user, id, MESSAGES_READ).await?;
+
+    let limit = pagination.limit.unwrap_or(DEFAULT_LIMIT);
+
+    // Return 400 if the cursor is invalid, messages are sorted by id only
+    let before_id = pagination.cursor.as_deref().map(decode_cursor).transpose()?.map(|(_, id)| id);
+
+    let sql = format!("
+        SELECT {MESSAGE_COLUMNS}
+        FROM messages m
+        LEFT JOIN users su ON su.id = m.sender_id
+        WHERE m.conversation_id = $1
+            AND ($2::INTEGER IS NULL OR m.id < $2)
+        ORDER BY m.id DESC
+        LIMIT $3;
+    ");
+
+    let query = sqlx::query_as::<_, Message>(&sql)
+        .bind(id)
+        .bind(before_id)
+        .bind(limit);
+
+    let messages = query.fetch_all(&pool).await.map_err(|e| {
+        eprintln!("Error fetching messages: {:?}", e);
+        StatusCode::INTERNAL_SERVER_ERROR // Return 500 if SQL error
+    })?;
+
+    // A full page may have a next one
+    let next_cursor = match messages.last() {
+        Some(last) if messages.len() as i64 == limit => Some(encode_cursor(Some(last.created_at), last.id)),
+        _ => None,
+    };
+
+    Ok(Json(MessagePage { messages, next_cursor }))
+}
+
+/*
+ * Send a message in a conversation
+ * - In a one-to-one conversation, the other user must still accept messages from the connected user
+ * - In a group, no participant must have blocked the connected user (or been blocked by him)
+ * @auth {Conneceted} - only for its participants
+ * @param {id} - conversation's id
+ * @param {FormMessage} - form input data
+ */
+pub async fn send_message(Path(id): Path<i32>, Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>, Form(payload): Form<FormMessage>) -> Result<Json<Message>, StatusCode> {
+
+    // Return 401, 403 or 404 if the user can't write in it
+    require_participant(&pool, &auth_user, id, MESSAGES_WRITE).await?;
+
+    // Return 400 if the message is empty
+    if payload.content.trim().is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    // Start transaction
+    let mut tx = pool.begin().await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    // Lock the other participants: a block (which updates both users) waits for the message, or the message sees the block
+    let others = sqlx::query("
+        SELECT u.id, c.is_group
+        FROM conversations c
+        JOIN conversation_participants p ON p.conversation_id = c.id AND p.user_id <> $2
+        JOIN users u ON u.id = p.user_id
+        WHERE c.id = $1
+        FOR SHARE OF u;
+    ")
+    .bind(id)
+    .bind(auth_user.user_id)
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    match others.as_slice() {
+
+        // Return 403 if the other user of a one-to-one conversation doesn't accept messages from the connected user anymore
+        [other] if !other.get::<bool, _>("is_group") => {
+            if get_can_message(&mut *tx, auth_user.user_id, other.get("id")).await? != Some(true) {
+                return Err(StatusCode::FORBIDDEN);
+            }
+        }
+
+        // Return 403 if the connected user blocked a participant of the group, or was blocked by him
+        _ => {
+            let sql = format!("
+                SELECT EXISTS (
+                    SELECT 1 FROM conversation_participants p
+                    WHERE p.conversation_id = $1 AND p.user_id <> $2 AND {}
+                ) AS is_blocked;
+            ", is_blocked("$2", "p.user_id"));
+
+            let is_blocked: bool = sqlx::query(&sql)
+                .bind(id)
+                .bind(auth_user.user_id)
+                .fetch_one(&mut *tx)
+                .await
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+                .get("is_blocked");
+
+            if is_blocked {
+                return Err(StatusCode::FORBIDDEN);
+            }
+        }
+    }
+
+    let message_id: i32 = sqlx::query("INSERT INTO messages (conversation_id, sender_id, content) VALUES ($1, $2, $3) RETURNING id")
+        .bind(id)
+        .bind(auth_user.user_id)
+        .bind(&payload.content)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .get("id");
+
+    sqlx::query("UPDATE conversations SET last_message_at = NOW() WHERE id = $1")
+        .bind(id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    // The sender has read his own message
+    sqlx::query("UPDATE conversation_participants SET last_read_message_id = $3 WHERE conversation_id = $1 AND user_id = $2")
+        .bind(id)
+        .bind(auth_user.user_id)
+        .bind(message_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    // Push it to the other participants if they're connected
+    let recipients = sqlx::query("SELECT user_id FROM conversation_participants WHERE conversation_id = $1 AND user_id <> $2")
+        .bind(id)
+        .bind(auth_user.user_id)
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    for recipient in recipients {
+        let data = json!({ "conversation_id": id, "message_id": message_id });
+        publish_event(&mut tx, MESSAGE_EVENT, Some(recipient.get("user_id")), data).await?;
+    }
+
+    let sql = format!("SELECT {MESSAGE_COLUMNS} FROM messages m LEFT JOIN users su ON su.id = m.sender_id WHERE m.id = $1");
+
+    let message = sqlx::query_as::<_, Message>(&sql)
+        .bind(message_id)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    // Commit -> Apply all queries
+    tx.commit().await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Json(message))
+}
+
+/*
+ * Move the read marker of the connected user (it never goes back)
+ * @auth {Conneceted} - only for its participants
+ * @param {id} - conversation's id
+ * @param {FormRead} - the last message read
+ */
+pub async fn mark_read(Path(id): Path<i32>, Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>, Form(payload): Form<FormRead>) -> StatusCode {
+
+    // Return 401, 403 or 404 if the user can't read it
+    if let Err(status) = require_participant(&pool, &auth_user, id, MESSAGES_READ).await {
+        return status;
+    }
+
+    let update_result = sqlx::query("
+        UPDATE conversation_participants
+        SET last_read_message_id = GREATEST(COALESCE(last_read_message_id, 0), $3)
+        WHERE conversation_id = $1 AND user_id = $2
+            AND EXISTS (SELECT 1 FROM messages WHERE id = $3 AND conversation_id = $1);
+    ")
+    .bind(id)
+    .bind(auth_user.user_id)
+    .bind(payload.message_id)
+    .execute(&pool)
+    .await;
+
+    match update_result {
+        Ok(res) if res.rows_affected() > 0 => StatusCode::OK,
+        Ok(_) => StatusCode::BAD_REQUEST, // 400 if the message is not in the conversation
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+/*
+ * Guard of the routes of a conversation
+ * - Returns 401 if the user is not connected, 403 if the API token doesn't have the scope
+ *   and 404 if he's not a participant (he can't know if the conversation exists)
+ */
+pub async fn require_participant(pool: &PgPool, auth_user: &AuthUser, conversation_id: i32, scope: &str) -> Result<(), StatusCode> {
+
+    if !auth_user.is_connected {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    if !auth_user.has_scope(scope) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let row = sqlx::query("SELECT 1 FROM conversation_participants WHERE conversation_id = $1 AND user_id = $2")
+        .bind(conversation_id)
+        .bind(auth_user.user_id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    row.map(|_| ()).ok_or(StatusCode::NOT_FOUND)
+}
+
+/*
+ * Check if an user accepts messages from another one (see users.dm_policy), None if he doesn't exist
+ * - Never if one of them blocked the other one
+ * - This is not an hanlder, but an helper function
+ */
+async fn get_can_message<'e, E: sqlx::PgExecutor<'e>>(executor: E, sender_id: i32, recipient_id: i32) -> Result<Option<bool>, StatusCode> {
+
+    let sql = format!("
+        SELECT CASE
+            WHEN {} THEN FALSE
+            WHEN u.dm_policy = 'everyone' THEN TRUE
+            WHEN u.dm_policy = 'following' THEN EXISTS (SELECT 1 FROM follows f WHERE f.follower_id = u.id AND f.followed_id = $1)
+            ELSE FALSE
+        END AS can_message
+        FROM users u
+        WHERE u.id = $2;
+    ", is_blocked("$1", "u.id"));
+
+    let query = sqlx::query(&sql)
+        .bind(sender_id)
+        .bind(recipient_id);
+
+    let row = query.fetch_optional(executor).await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(row.map(|row| row.get("can_message")))
+}
+
+/*
+ * Get a conversation as seen by one of its participants
+ * - This is not an hanlder, but an helper function
+ */
+async fn get_conversation(pool: &PgPool, id: i32, user_id: i32) -> Result<Option<Conversation>, StatusCode> {
+
+    let sql = format!("
+        SELECT {CONVERSATION_COLUMNS}
+        FROM conversation_participants cp
+        JOIN conversations c ON c.id = cp.conversation_id
+        WHERE cp.user_id = $1 AND c.id = $2;
+    ");
+
+    sqlx::query_as::<_, Conversation>(&sql)
+        .bind(user_id)
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| {
+            eprintln!("Error fetching conversation: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
@@ -0,0 +1,128 @@
+use std::sync::Arc;
+use axum::{Json, extract::{State, Extension}, http::StatusCode};
+use sqlx::{PgPool, PgConnection, Row};
+
+use crate::models::auth::{AuthUser, VerifyEmailRequest};
+use crate::auth::scopes::USERS_WRITE;
+use crate::auth::token_handler::{generate_opaque_token, hash_token, email_verification_duration};
+use crate::mail::{Mail, Mailer, app_url, send_in_background};
+
+/*
+ * Verify an email with a token received by mail
+ * @auth {None} - the verification token is the authorization
+ * @param {VerifyEmailRequest} - the token
+ */
+pub async fn verify_email(State(pool): State<PgPool>, Json(payload): Json<VerifyEmailRequest>) -> StatusCode {
+
+    // Consume the token and verify the email, only if the user didn't change it since the mail
+    let query = sqlx::query("
+        WITH token AS (
+            UPDATE email_verification_tokens SET used_at = NOW()
+            WHERE token_hash = $1 AND used_at IS NULL AND expires_at > NOW()
+            RETURNING user_id, email
+        )
+        UPDATE users SET email_verified_at = NOW()
+        FROM token
+        WHERE users.id = token.user_id AND users.email = token.email;
+    ")
+    .bind(hash_token(&payload.token));
+
+    match query.execute(&pool).await {
+
+        Ok(res) if res.rows_affected() > 0 => StatusCode::NO_CONTENT, // 204
+        Ok(_) => StatusCode::UNAUTHORIZED, // 401 if unknown, used or expired token
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR, // 500
+    }
+}
+
+/*
+ * Send again the verification mail to the connected user
+ * @auth {Connected} - only for connected users
+ */
+pub async fn resend_verification(Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>, State(mailer): State<Arc<dyn Mailer>>) -> StatusCode {
+
+    // If user is not connected we return 401
+    if !auth_user.is_connected {
+        return StatusCode::UNAUTHORIZED;
+    }
+
+    // If the API token doesn't have the users:write scope, return 403
+    if !auth_user.has_scope(USERS_WRITE) {
+        return StatusCode::FORBIDDEN;
+    }
+
+    let query = sqlx::query("SELECT username, email, email_verified_at IS NOT NULL AS is_verified FROM users WHERE id = $1")
+        .bind(auth_user.user_id);
+
+    let row = match query.fetch_one(&pool).await {
+        Ok(row) => row,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR,
+    };
+
+    let is_verified: bool = row.get("is_verified");
+
+    // Nothing to do, return 409
+    if is_verified {
+        return StatusCode::CONFLICT;
+    }
+
+    match send_verification_mail(&pool, mailer, auth_user.user_id, row.get("username"), row.get("email")).await {
+        Ok(()) => StatusCode::NO_CONTENT,
+        Err(status) => status,
+    }
+}
+
+/*
+ * Create a verification token for the user email and send it by mail
+ * - This is not an hanlder, but an helper function
+ */
+pub async fn send_verification_mail(pool: &PgPool, mailer: Arc<dyn Mailer>, user_id: i32, username: String, email: String) -> Result<(), StatusCode> {
+
+    let mut conn = pool.acquire().await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let mail = create_verification_mail(&mut conn, user_id, username, email).await?;
+
+    send_in_background(mailer, mail);
+
+    Ok(())
+}
+
+/*
+ * Create a verification token for the user email, and the mail to send once the transaction is committed
+ * - This is not an hanlder, but an helper function
+ */
+pub async fn create_verification_mail(conn: &mut PgConnection, user_id: i32, username: String, email: String) -> Result<Mail, StatusCode> {
+
+    // Only the last sent link is usable
+    sqlx::query("UPDATE email_verification_tokens SET used_at = NOW() WHERE user_id = $1 AND used_at IS NULL")
+        .bind(user_id)
+        .execute(&mut *conn)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let token = generate_opaque_token();
+    let expires_at = chrono::Utc::now().naive_utc() + email_verification_duration();
+
+    sqlx::query("INSERT INTO email_verification_tokens (user_id, email, token_hash, expires_at) VALUES ($1, $2, $3, $4)")
+        .bind(user_id)
+        .bind(&email)
+        .bind(hash_token(&token))
+        .bind(expires_at)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| {
+            eprintln!("Error creating email verification token: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    Ok(Mail {
+        to: email,
+        subject: String::from("FEUR - Verify your email"),
+        body: format!(
+            "Hello {username},\n\nTo verify your email address, open this link:\n{}/verify-email?token={token}\n\nThis link expires in {} hours.",
+            app_url(),
+            email_verification_duration().num_hours()
+        ),
+    })
+}
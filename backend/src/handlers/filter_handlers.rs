@@ -0,0 +1,301 @@
+use axum::{extract::{Path, State, Extension}, Json, http::StatusCode};
+use sqlx::PgPool;
+
+use crate::models::filter::{ContentFilter, CreateFilterRequest};
+use crate::models::auth::AuthUser;
+use crate::auth::scopes::{USERS_READ, USERS_WRITE};
+use crate::entities::{parse_entities, EntityKind};
+
+// Where a filter applies
+pub const HOME_CONTEXT: &str = "home";                   // explore, home timeline and hashtags
+pub const NOTIFICATIONS_CONTEXT: &str = "notifications";
+pub const SEARCH_CONTEXT: &str = "search";
+
+pub const FILTER_CONTEXTS: [&str; 3] = [HOME_CONTEXT, NOTIFICATIONS_CONTEXT, SEARCH_CONTEXT];
+
+// What is done with the matching posts
+pub const FILTER_ACTIONS: [&str; 2] = ["hide", "warn"];
+
+// Filters of an user, they are all checked for each post
+const MAX_FILTERS: i64 = 100;
+
+// Longest phrase (size of the column)
+const MAX_PHRASE_LENGTH: usize = 100;
+
+// Condition hiding the posts p matched by a "hide" filter, needs the filters CTE (see active_filters)
+pub(crate) const NOT_FILTERED: &str = "
+    NOT EXISTS (
+        SELECT 1 FROM filters f
+        WHERE f.action = 'hide'
+            AND (p.search_vector @@ f.query OR EXISTS (SELECT 1 FROM post_hashtags ph WHERE ph.post_id = p.id AND ph.hashtag_id = f.hashtag_id))
+    )
+";
+
+// Column with the phrases of the "warn" filters matching the post p (NULL if none), needs the filters CTE
+pub(crate) const FILTERED_COLUMN: &str = "
+    NULLIF(ARRAY(
+        SELECT f.phrase::TEXT FROM filters f
+        WHERE f.action = 'warn'
+            AND (p.search_vector @@ f.query OR EXISTS (SELECT 1 FROM post_hashtags ph WHERE ph.post_id = p.id AND ph.hashtag_id = f.hashtag_id))
+        ORDER BY f.phrase
+    ), '{}') AS filtered
+";
+
+/*
+ * List the content filters of the connected user, the expired ones included
+ * @auth {Connected} - only for connected users
+ */
+pub async fn list_filters(Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>) -> Result<Json<Vec<ContentFilter>>, StatusCode> {
+
+    // Return 401 if user not connected
+    if !auth_user.is_connected {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    // If the API token doesn't have the users:read scope, return 403
+    if !auth_user.has_scope(USERS_READ) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let query = sqlx::query_as::<_, ContentFilter>("
+        SELECT id, phrase, is_hashtag, contexts, action, expires_at, created_at
+        FROM content_filters
+        WHERE user_id = $1
+        ORDER BY created_at DESC, id DESC;
+    ")
+    .bind(auth_user.user_id);
+
+    let filters = query.fetch_all(&pool).await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?; // Return 500 if SQL request failed
+
+    Ok(Json(filters))
+}
+
+/*
+ * Add a muted word, phrase or hashtag (#tag) to the connected user, or replace the filter of the same phrase
+ * - Words match their other forms (ex: "cat" hides "cats"), like the search
+ * @auth {Connected} - only for connected users
+ * @param {CreateFilterRequest} - the phrase, its contexts, its action and its duration
+ */
+pub async fn create_filter(Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>, Json(payload): Json<CreateFilterRequest>) -> Result<Json<ContentFilter>, StatusCode> {
+
+    // Return 401 if user not connected
+    if !auth_user.is_connected {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    // If the API token doesn't have the users:write scope, return 403
+    if !auth_user.has_scope(USERS_WRITE) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    // Return 400 if the phrase, a context or the action is not valid
+    let (phrase, is_hashtag) = parse_phrase(&payload.phrase)?;
+    let contexts = parse_contexts(&payload.contexts)?;
+    let action = parse_action(payload.action.as_deref())?;
+
+    // Return 400 if the duration is not positive
+    if payload.expires_in_minutes.is_some_and(|minutes| minutes <= 0) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    // Return 400 if the user has too many filters (the replaced one is not counted)
+    let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM content_filters WHERE user_id = $1 AND NOT (is_hashtag = $2 AND phrase = $3)")
+        .bind(auth_user.user_id)
+        .bind(is_hashtag)
+        .bind(&phrase)
+        .fetch_one(&pool)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    if count >= MAX_FILTERS {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    // A phrase made of stop words only (ex: "the") would match nothing, it's not saved
+    let query = sqlx::query_as::<_, ContentFilter>("
+        INSERT INTO content_filters (user_id, phrase, is_hashtag, contexts, action, expires_at)
+        SELECT $1, $2, $3, $4, $5, NOW() + make_interval(mins => $6)
+        WHERE $3 OR numnode(phraseto_tsquery('english', $2) || phraseto_tsquery('french', $2)) > 0
+        ON CONFLICT (user_id, is_hashtag, phrase) DO UPDATE SET
+            contexts = EXCLUDED.contexts,
+            action = EXCLUDED.action,
+            expires_at = EXCLUDED.expires_at
+        RETURNING id, phrase, is_hashtag, contexts, action, expires_at, created_at;
+    ")
+    .bind(auth_user.user_id)
+    .bind(&phrase)
+    .bind(is_hashtag)
+    .bind(&contexts)
+    .bind(action)
+    .bind(payload.expires_in_minutes);
+
+    let filter = query.fetch_optional(&pool).await
+        .map_err(|e| {
+            eprintln!("Error saving filter: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::BAD_REQUEST)?;
+
+    Ok(Json(filter))
+}
+
+/*
+ * Phrase of a filter as it's saved, and if it's a hashtag
+ * - A tag is saved like the hashtags of the posts (lowercase, without #), a phrase in lowercase (the search ignores the case)
+ * - Return 400 if it's empty, too long, or starts with # without being a tag
+ */
+fn parse_phrase(phrase: &str) -> Result<(String, bool), StatusCode> {
+
+    let phrase = phrase.trim();
+
+    let (phrase, is_hashtag) = if phrase.starts_with(['#', '＃']) {
+        match parse_entities(phrase).as_slice() {
+            [tag] if tag.kind == EntityKind::Hashtag && tag.start == 0 && tag.end == phrase.chars().count() => (tag.value.clone(), true),
+            _ => return Err(StatusCode::BAD_REQUEST),
+        }
+    } else {
+        (phrase.to_lowercase(), false)
+    };
+
+    if phrase.is_empty() || phrase.chars().count() > MAX_PHRASE_LENGTH {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    Ok((phrase, is_hashtag))
+}
+
+/*
+ * Contexts of a filter, without duplicates
+ * - Return 400 without context, or with an unknown one
+ */
+fn parse_contexts(contexts: &[String]) -> Result<Vec<&'static str>, StatusCode> {
+
+    let mut parsed: Vec<&'static str> = Vec::new();
+
+    for context in contexts {
+        match FILTER_CONTEXTS.iter().find(|known| *known == context) {
+            Some(known) if !parsed.contains(known) => parsed.push(known),
+            Some(_) => {},
+            None => return Err(StatusCode::BAD_REQUEST),
+        }
+    }
+
+    if parsed.is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    Ok(parsed)
+}
+
+/*
+ * Action of a filter, "hide" by default
+ * - Return 400 if it's unknown
+ */
+fn parse_action(action: Option<&str>) -> Result<&'static str, StatusCode> {
+
+    let action = action.unwrap_or(FILTER_ACTIONS[0]);
+
+    FILTER_ACTIONS.iter().find(|known| **known == action).copied().ok_or(StatusCode::BAD_REQUEST)
+}
+
+/*
+ * Delete a content filter
+ * @auth {Connected} - only for the user who created it
+ * @param {id} - filter's id
+ */
+pub async fn delete_filter(Path(id): Path<i32>, Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>) -> StatusCode {
+
+    // Return 401 if user not connected
+    if !auth_user.is_connected {
+        return StatusCode::UNAUTHORIZED;
+    }
+
+    // If the API token doesn't have the users:write scope, return 403
+    if !auth_user.has_scope(USERS_WRITE) {
+        return StatusCode::FORBIDDEN;
+    }
+
+    let delete_result = sqlx::query("DELETE FROM content_filters WHERE id = $1 AND user_id = $2")
+        .bind(id)
+        .bind(auth_user.user_id)
+        .execute(&pool)
+        .await;
+
+    match delete_result {
+        Ok(res) if res.rows_affected() > 0 => StatusCode::OK,
+        Ok(_) => StatusCode::NOT_FOUND, // 404 if no filter found (or created by another user)
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+/*
+ * CTE "filters": the filters of the user $1 active in a context, read once per query
+ * - hashtag_id: the tag of a hashtag filter (NULL if it was never used), query: the words of the others
+ * - This is not an hanlder, but an helper function
+ */
+pub(crate) fn active_filters(context: &str) -> String {
+
+    format!("
+        filters AS (
+            SELECT cf.phrase, cf.action, cf.query, h.id AS hashtag_id
+            FROM content_filters cf
+            LEFT JOIN hashtags h ON cf.is_hashtag AND h.name = cf.phrase
+            WHERE cf.user_id = $1
+                AND '{context}' = ANY(cf.contexts)
+                AND (cf.expires_at IS NULL OR cf.expires_at > NOW())
+        )
+    ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn phrases_are_lowercase() {
+
+        assert_eq!(parse_phrase("  Cat "), Ok((String::from("cat"), false)));
+        assert_eq!(parse_phrase("Spoiler Alert"), Ok((String::from("spoiler alert"), false)));
+        assert_eq!(parse_phrase("Été"), Ok((String::from("été"), false)));
+
+        // Empty or too long
+        assert!(parse_phrase("   ").is_err());
+        assert!(parse_phrase(&"a".repeat(MAX_PHRASE_LENGTH + 1)).is_err());
+        assert!(parse_phrase(&"a".repeat(MAX_PHRASE_LENGTH)).is_ok());
+    }
+
+    #[test]
+    fn hashtags_are_saved_like_the_posts_ones() {
+
+        assert_eq!(parse_phrase("#Rust"), Ok((String::from("rust"), true)));
+        assert_eq!(parse_phrase(" ＃タグ "), Ok((String::from("タグ"), true)));
+
+        // Not a single tag
+        assert!(parse_phrase("#").is_err());
+        assert!(parse_phrase("#123").is_err());
+        assert!(parse_phrase("#rust lang").is_err());
+        assert!(parse_phrase("#rust!").is_err());
+    }
+
+    #[test]
+    fn contexts_are_known_and_unique() {
+
+        let contexts = |values: &[&str]| values.iter().map(|value| value.to_string()).collect::<Vec<String>>();
+
+        assert_eq!(parse_contexts(&contexts(&["home", "search", "home"])), Ok(vec![HOME_CONTEXT, SEARCH_CONTEXT]));
+        assert_eq!(parse_contexts(&contexts(&["notifications"])), Ok(vec![NOTIFICATIONS_CONTEXT]));
+
+        assert!(parse_contexts(&contexts(&[])).is_err());
+        assert!(parse_contexts(&contexts(&["home", "profile"])).is_err());
+        assert!(parse_contexts(&contexts(&["Home"])).is_err());
+    }
+
+    #[test]
+    fn actions_are_known() {
+
+        assert_eq!(parse_action(None), Ok("hide"));
+        assert_eq!(parse_action(Some("warn")), Ok("warn"));
+        assert!(parse_action(Some("delete")).is_err());
+    }
+}
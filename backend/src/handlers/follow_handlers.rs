@@ -0,0 +1,274 @@
+use axum::{extract::{Path, State, Extension, Query}, Json, http::StatusCode};
+use sqlx::{PgPool, Row};
+
+use crate::models::follow::{FollowUser, FollowList};
+use crate::models::auth::AuthUser;
+use crate::auth::scopes::{USERS_READ, USERS_WRITE};
+use crate::models::notification::NotificationKind;
+use crate::handlers::{DEFAULT_LIMIT, DEFAULT_OFFSET, PaginationQuery};
+use crate::handlers::notification_handlers::{add_notification, remove_notification};
+use crate::handlers::block_handlers::{is_blocked, get_is_blocked};
+
+/*
+ * Follow an user
+ * @auth {Connected} - only for connected users
+ * @param {id} - id of the user you want to follow
+ */
+pub async fn follow_user(Path(id): Path<i32>, Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>) -> StatusCode {
+
+    // Return 401 if user not connected
+    if !auth_user.is_connected {
+        return StatusCode::UNAUTHORIZED;
+    }
+
+    // If the API token doesn't have the users:write scope, return 403
+    if !auth_user.has_scope(USERS_WRITE) {
+        return StatusCode::FORBIDDEN;
+    }
+
+    // Users can't follow themselves, return 400
+    if auth_user.user_id == id {
+        return StatusCode::BAD_REQUEST;
+    }
+
+    // Return 403 if one of them blocked the other one
+    match get_is_blocked(&pool, auth_user.user_id, id).await {
+        Ok(false) => {},
+        Ok(true) => return StatusCode::FORBIDDEN,
+        Err(status) => return status,
+    }
+
+    // Start transaction
+    let mut tx = match pool.begin().await {
+        Ok(t) => t,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR,
+    };
+
+    // Insert follow, only if the followed user exists
+    let insert_result = sqlx::query("
+        INSERT INTO follows (follower_id, followed_id)
+        SELECT $1, id FROM users WHERE id = $2
+        ON CONFLICT DO NOTHING;
+    ")
+    .bind(auth_user.user_id)
+    .bind(id)
+    .execute(&mut *tx)
+    .await;
+
+    match insert_result {
+        Ok(res) if res.rows_affected() > 0 => {},
+        Ok(_) => {
+            // Already followed (409) or no user found (404)
+            return match get_user_exists(&pool, id).await {
+                Ok(true) => StatusCode::CONFLICT,
+                Ok(false) => StatusCode::NOT_FOUND,
+                Err(status) => status,
+            };
+        }
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR,
+    }
+
+    // Update follow counts of both users
+    let update_result = sqlx::query("
+        UPDATE users SET
+            followers_count = followers_count + CASE WHEN id = $2 THEN 1 ELSE 0 END,
+            following_count = following_count + CASE WHEN id = $1 THEN 1 ELSE 0 END
+        WHERE id IN ($1, $2);
+    ")
+    .bind(auth_user.user_id)
+    .bind(id)
+    .execute(&mut *tx)
+    .await;
+
+    if update_result.is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    }
+
+    // Notify the followed user
+    if let Err(status) = add_notification(&mut tx, id, auth_user.user_id, NotificationKind::Follow, None).await {
+        return status;
+    }
+
+    // Commit -> Apply both queries
+    if tx.commit().await.is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    }
+
+    StatusCode::CREATED
+}
+
+/*
+ * Unfollow an user
+ * @auth {Connected} - only for connected users
+ * @param {id} - id of the user you want to unfollow
+ */
+pub async fn unfollow_user(Path(id): Path<i32>, Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>) -> StatusCode {
+
+    // Return 401 if user not connected
+    if !auth_user.is_connected {
+        return StatusCode::UNAUTHORIZED;
+    }
+
+    // If the API token doesn't have the users:write scope, return 403
+    if !auth_user.has_scope(USERS_WRITE) {
+        return StatusCode::FORBIDDEN;
+    }
+
+    // Start transaction
+    let mut tx = match pool.begin().await {
+        Ok(t) => t,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR,
+    };
+
+    // Delete follow
+    let delete_result = sqlx::query("DELETE FROM follows WHERE follower_id = $1 AND followed_id = $2")
+        .bind(auth_user.user_id)
+        .bind(id)
+        .execute(&mut *tx)
+        .await;
+
+    match delete_result {
+        Ok(res) if res.rows_affected() > 0 => {},
+        Ok(_) => return StatusCode::NOT_FOUND, // 404 if the user was not followed
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR,
+    }
+
+    // Update follow counts of both users
+    let update_result = sqlx::query("
+        UPDATE users SET
+            followers_count = followers_count - CASE WHEN id = $2 THEN 1 ELSE 0 END,
+            following_count = following_count - CASE WHEN id = $1 THEN 1 ELSE 0 END
+        WHERE id IN ($1, $2);
+    ")
+    .bind(auth_user.user_id)
+    .bind(id)
+    .execute(&mut *tx)
+    .await;
+
+    if update_result.is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    }
+
+    // Remove the notification of the followed user
+    if let Err(status) = remove_notification(&mut tx, id, auth_user.user_id, NotificationKind::Follow, None).await {
+        return status;
+    }
+
+    // Commit -> Apply both queries
+    if tx.commit().await.is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    }
+
+    StatusCode::OK
+}
+
+/*
+ * List the users following an user, most recent first
+ * @auth {None} - no authorization needed
+ * @param {id} - id of the followed user
+ */
+pub async fn list_followers(Path(id): Path<i32>, Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>, Query(pagination): Query<PaginationQuery>) -> Result<Json<FollowList>, StatusCode> {
+
+    // If the API token doesn't have the users:read scope, return 403
+    if auth_user.is_connected && !auth_user.has_scope(USERS_READ) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let limit = pagination.limit.unwrap_or(DEFAULT_LIMIT);
+    let offset = pagination.offset.unwrap_or(DEFAULT_OFFSET);
+
+    let (count, _) = get_follow_counts(&pool, id).await?;
+
+    // Users blocked by the connected user (or blocking him) are left out, -1 if not connected
+    let sql = format!("
+        SELECT u.id, u.username, u.title, u.created_at, f.created_at AS followed_at
+        FROM follows f
+        JOIN users u ON u.id = f.follower_id
+        WHERE f.followed_id = $1 AND NOT {}
+        ORDER BY f.created_at DESC, u.id DESC
+        LIMIT $2
+        OFFSET $3;
+    ", is_blocked("$4", "u.id"));
+
+    let query = sqlx::query_as::<_, FollowUser>(&sql)
+        .bind(id)
+        .bind(limit)
+        .bind(offset)
+        .bind(if auth_user.is_connected { auth_user.user_id } else { -1 });
+
+    let users = query.fetch_all(&pool).await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?; // Return 500 if SQL request failed
+
+    Ok(Json(FollowList { count, users }))
+}
+
+/*
+ * List the users followed by an user, most recent first
+ * @auth {None} - no authorization needed
+ * @param {id} - id of the follower
+ */
+pub async fn list_following(Path(id): Path<i32>, Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>, Query(pagination): Query<PaginationQuery>) -> Result<Json<FollowList>, StatusCode> {
+
+    // If the API token doesn't have the users:read scope, return 403
+    if auth_user.is_connected && !auth_user.has_scope(USERS_READ) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let limit = pagination.limit.unwrap_or(DEFAULT_LIMIT);
+    let offset = pagination.offset.unwrap_or(DEFAULT_OFFSET);
+
+    let (_, count) = get_follow_counts(&pool, id).await?;
+
+    // Users blocked by the connected user (or blocking him) are left out, -1 if not connected
+    let sql = format!("
+        SELECT u.id, u.username, u.title, u.created_at, f.created_at AS followed_at
+        FROM follows f
+        JOIN users u ON u.id = f.followed_id
+        WHERE f.follower_id = $1 AND NOT {}
+        ORDER BY f.created_at DESC, u.id DESC
+        LIMIT $2
+        OFFSET $3;
+    ", is_blocked("$4", "u.id"));
+
+    let query = sqlx::query_as::<_, FollowUser>(&sql)
+        .bind(id)
+        .bind(limit)
+        .bind(offset)
+        .bind(if auth_user.is_connected { auth_user.user_id } else { -1 });
+
+    let users = query.fetch_all(&pool).await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?; // Return 500 if SQL request failed
+
+    Ok(Json(FollowList { count, users }))
+}
+
+/*
+ * Get the followers and following counts of an user, 404 if he doesn't exist
+ * - This is not an hanlder, but an helper function
+ */
+async fn get_follow_counts(pool: &PgPool, user_id: i32) -> Result<(i32, i32), StatusCode> {
+
+    let query = sqlx::query("SELECT followers_count, following_count FROM users WHERE id = $1")
+        .bind(user_id);
+
+    let row = query.fetch_optional(pool).await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    Ok((row.get("followers_count"), row.get("following_count")))
+}
+
+/*
+ * This function is used to know if an user exists
+ * - This is not an hanlder, but an helper function
+ */
+pub(crate) async fn get_user_exists(pool: &PgPool, user_id: i32) -> Result<bool, StatusCode> {
+
+    let query = sqlx::query("SELECT 1 FROM users WHERE id = $1")
+        .bind(user_id);
+
+    let row = query.fetch_optional(pool).await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(row.is_some())
+}
@@ -0,0 +1,212 @@
+use std::sync::Arc;
+use axum::{extract::{Path, State, Extension, Multipart}, Json, http::{StatusCode, header}, response::IntoResponse};
+use rand::{rngs::OsRng, RngCore};
+use sqlx::PgPool;
+
+use crate::models::media::{Media, UpdateMediaRequest};
+use crate::models::auth::AuthUser;
+use crate::auth::scopes::POSTS_WRITE;
+use crate::media::{process_image, max_upload_bytes, MediaError};
+use crate::storage::{Storage, is_valid_key};
+use crate::handlers::auth_handlers::get_is_verified;
+
+// Longest description of an image
+const MAX_ALT_TEXT_LENGTH: usize = 1000;
+
+/*
+ * Upload an image, it can then be attached to a post with FormPost.media_ids
+ * - multipart/form-data with a "file" field (JPEG, PNG or WebP) and an optional "alt_text" field
+ * - The metadata of the image are removed and a thumbnail is created
+ * @auth {Conneceted} - only for conneceted users
+ */
+pub async fn upload_media(Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>, State(storage): State<Arc<dyn Storage>>, mut multipart: Multipart) -> Result<Json<Media>, StatusCode> {
+
+    // If the user is not connected, return 401
+    if !auth_user.is_connected {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    // If the API token doesn't have the posts:write scope, return 403
+    if !auth_user.has_scope(POSTS_WRITE) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    // If the user must verify his email before posting, return 403
+    if !get_is_verified(&pool, &auth_user).await {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let mut file = None;
+    let mut alt_text = None;
+
+    // Return 413 if the body is too large, 400 if it's not a valid multipart body
+    while let Some(field) = multipart.next_field().await.map_err(|e| e.status())? {
+
+        match field.name() {
+            Some("file") => file = Some(field.bytes().await.map_err(|e| e.status())?),
+            Some("alt_text") => alt_text = Some(field.text().await.map_err(|e| e.status())?),
+            _ => {}
+        }
+    }
+
+    // Return 400 without file or with a too long description
+    let file = file.ok_or(StatusCode::BAD_REQUEST)?;
+
+    let alt_text = alt_text.map(|text| text.trim().to_string()).filter(|text| !text.is_empty());
+
+    if alt_text.as_ref().is_some_and(|text| text.chars().count() > MAX_ALT_TEXT_LENGTH) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    // Return 413 if the file is too large
+    if file.len() > max_upload_bytes() {
+        return Err(StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    let size_bytes = file.len() as i32;
+
+    // Return 415 if it's not a supported image, 400 if it can't be read
+    let image = tokio::task::spawn_blocking(move || process_image(&file)).await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .map_err(|e| match e {
+            MediaError::UnsupportedType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            MediaError::InvalidImage => StatusCode::BAD_REQUEST,
+        })?;
+
+    // Random name, grouped by month
+    let mut name = [0u8; 16];
+    OsRng.fill_bytes(&mut name);
+
+    let base_key = format!("media/{}/{}", chrono::Utc::now().format("%Y/%m"), hex::encode(name));
+    let storage_key = format!("{base_key}.{}", image.extension);
+    let thumbnail_key = format!("{base_key}_thumb.jpg");
+
+    let uploads = [
+        storage.put(&storage_key, image.data, image.content_type).await,
+        storage.put(&thumbnail_key, image.thumbnail, "image/jpeg").await,
+    ];
+
+    if let Some(Err(e)) = uploads.into_iter().find(|result| result.is_err()) {
+        eprintln!("Error storing media: {e}");
+        delete_files(storage.as_ref(), &[storage_key, thumbnail_key]).await;
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    let query = sqlx::query_as::<_, Media>("
+        INSERT INTO media (user_id, storage_key, thumbnail_key, url, thumbnail_url, content_type, width, height, size_bytes, alt_text)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        RETURNING id, url, thumbnail_url, content_type, width, height, alt_text;
+    ")
+    .bind(auth_user.user_id)
+    .bind(&storage_key)
+    .bind(&thumbnail_key)
+    .bind(storage.url(&storage_key))
+    .bind(storage.url(&thumbnail_key))
+    .bind(image.content_type)
+    .bind(image.width as i32)
+    .bind(image.height as i32)
+    .bind(size_bytes)
+    .bind(alt_text);
+
+    match query.fetch_one(&pool).await {
+
+        Ok(media) => Ok(Json(media)),
+
+        // Don't keep files nobody can use
+        Err(_) => {
+            delete_files(storage.as_ref(), &[storage_key, thumbnail_key]).await;
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/*
+ * Change the description of an uploaded image
+ * @auth {Conneceted} - only for the user who uploaded it
+ * @param {id} - media's id
+ * @param {UpdateMediaRequest} - the new description (null to remove it)
+ */
+pub async fn update_media(Path(id): Path<i32>, Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>, Json(payload): Json<UpdateMediaRequest>) -> Result<Json<Media>, StatusCode> {
+
+    // If the user is not connected, return 401
+    if !auth_user.is_connected {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    // If the API token doesn't have the posts:write scope, return 403
+    if !auth_user.has_scope(POSTS_WRITE) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let alt_text = payload.alt_text.map(|text| text.trim().to_string()).filter(|text| !text.is_empty());
+
+    // Return 400 if the description is too long
+    if alt_text.as_ref().is_some_and(|text| text.chars().count() > MAX_ALT_TEXT_LENGTH) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let query = sqlx::query_as::<_, Media>("
+        UPDATE media SET alt_text = $3
+        WHERE id = $1 AND user_id = $2
+        RETURNING id, url, thumbnail_url, content_type, width, height, alt_text;
+    ")
+    .bind(id)
+    .bind(auth_user.user_id)
+    .bind(alt_text);
+
+    let media = query.fetch_optional(&pool).await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?; // Return 404 if no media found (or uploaded by another user)
+
+    Ok(Json(media))
+}
+
+/*
+ * Send a stored file, used by the local storage (MEDIA_PUBLIC_URL)
+ * @auth {None} - no authorization needed
+ * @param {key} - key of the file in the storage
+ */
+pub async fn serve_media(Path(key): Path<String>, State(storage): State<Arc<dyn Storage>>) -> Result<impl IntoResponse, StatusCode> {
+
+    // Return 404 for keys the backend can't have created (ex: "../.env")
+    if !is_valid_key(&key) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let data = storage.get(&key).await
+        .map_err(|e| {
+            eprintln!("Error reading media {key}: {e}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let content_type = match key.rsplit_once('.').map(|(_, extension)| extension) {
+        Some("jpg") => "image/jpeg",
+        Some("png") => "image/png",
+        Some("webp") => "image/webp",
+        _ => "application/octet-stream",
+    };
+
+    // Files are never changed, a new upload gets a new key
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type),
+            (header::CACHE_CONTROL, "public, max-age=31536000, immutable"),
+            (header::X_CONTENT_TYPE_OPTIONS, "nosniff"),
+        ],
+        data,
+    ))
+}
+
+/*
+ * Delete files of the storage, errors are only logged (an orphan file is not a problem for the user)
+ * - This is not an hanlder, but an helper function
+ */
+pub async fn delete_files(storage: &dyn Storage, keys: &[String]) {
+
+    for key in keys {
+        if let Err(e) = storage.delete(key).await {
+            eprintln!("Error deleting media {key}: {e}");
+        }
+    }
+}
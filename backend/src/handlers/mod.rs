@@ -1,10 +1,29 @@
+use std::collections::HashSet;
 use axum::http::StatusCode;
+use chrono::NaiveDateTime;
 use serde::Deserialize;
 
 // Expose rust files
 pub mod user_handlers;
 pub mod post_handlers;
 pub mod auth_handlers;
+pub mod session_handlers;
+pub mod password_handlers;
+pub mod email_handlers;
+pub mod two_factor_handlers;
+pub mod api_token_handlers;
+pub mod oidc_handlers;
+pub mod follow_handlers;
+pub mod tag_handlers;
+pub mod search_handlers;
+pub mod media_handlers;
+pub mod poll_handlers;
+pub mod bookmark_handlers;
+pub mod notification_handlers;
+pub mod stream_handlers;
+pub mod conversation_handlers;
+pub mod block_handlers;
+pub mod filter_handlers;
 
 // It's defined here cause it's the same one of user and post handlers
 
@@ -20,6 +39,59 @@ pub struct PaginationQuery {
     // pub since_created_at: Option<String>
 }
 
+// Keyset pagination: the cursor is the next_cursor of the previous page, so new posts don't shift the pages
+#[derive(Deserialize)]
+pub struct KeysetQuery {
+    pub cursor: Option<String>,
+    pub limit: Option<i64>,
+}
+
+/*
+ * Cursor of the page after a post ("<created_at in microseconds>_<id>")
+ */
+pub fn encode_cursor(created_at: Option<NaiveDateTime>, id: i32) -> String {
+
+    let micros = created_at.map(|date| date.and_utc().timestamp_micros()).unwrap_or(0);
+
+    format!("{micros}_{id}")
+}
+
+/*
+ * Read a cursor made by encode_cursor, 400 if it's invalid
+ */
+pub fn decode_cursor(cursor: &str) -> Result<(NaiveDateTime, i32), StatusCode> {
+
+    let (micros, id) = cursor.split_once('_').ok_or(StatusCode::BAD_REQUEST)?;
+
+    let micros: i64 = micros.parse().map_err(|_| StatusCode::BAD_REQUEST)?;
+    let id: i32 = id.parse().map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    let created_at = chrono::DateTime::from_timestamp_micros(micros)
+        .ok_or(StatusCode::BAD_REQUEST)?
+        .naive_utc();
+
+    Ok((created_at, id))
+}
+
+/*
+ * Read comma separated ids (ex: "12,13"), 400 if one is invalid or given twice
+ */
+pub fn parse_ids(ids: &str) -> Result<Vec<i32>, StatusCode> {
+
+    let ids = ids.split(',')
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .map(|id| id.parse::<i32>())
+        .collect::<Result<Vec<i32>, _>>()
+        .map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    if ids.iter().collect::<HashSet<_>>().len() != ids.len() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    Ok(ids)
+}
+
 // This is only used to make the backend return 200 when trying to acess it
 // - This will help me check if the backend is UP when running my script
 pub async fn ping() -> StatusCode {
@@ -0,0 +1,312 @@
+use axum::{extract::{Path, State, Extension, Query}, Json, http::StatusCode};
+use sqlx::{PgPool, PgConnection, Row};
+use serde_json::json;
+use crate::models::notification::{Notification, NotificationKind, NotificationPage, UnreadCount};
+use crate::models::auth::AuthUser;
+use crate::auth::scopes::{USERS_READ, USERS_WRITE};
+use crate::handlers::{DEFAULT_LIMIT, KeysetQuery, encode_cursor, decode_cursor};
+use crate::handlers::stream_handlers::publish_event;
+use crate::handlers::block_handlers::{is_hidden, get_is_hidden};
+use crate::handlers::filter_handlers::{NOTIFICATIONS_CONTEXT, NOT_FILTERED, FILTERED_COLUMN, active_filters};
+use crate::stream::NOTIFICATION_EVENT;
+
+// Actors returned with each notification, the others are only counted
+const NOTIFICATION_ACTORS: i64 = 3;
+
+/*
+ * List the notifications of the connected user, most recent first
+ * - Actors blocked (in any direction) or muted by the connected user are left out
+ * - Notifications of a post matching his content filters (notifications context) are hidden, or marked with the filters
+ * - The pages are ordered by updated_at, which changes when an unread notification is grouped again: it then moves
+ *   before the cursor and is not in the next pages, the client gets it with the "notification" event (or the first page)
+ * @auth {Conneceted} - only for conneceted users
+ * @param {KeysetQuery} - cursor (next_cursor of the previous page) & limit
+ */
+pub async fn list_notifications(Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>, Query(pagination): Query<KeysetQuery>) -> Result<Json<NotificationPage>, StatusCode> {
+
+    // Return 401 if user not connected
+    if !auth_user.is_connected {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    // If the API token doesn't have the users:read scope, return 403
+    if !auth_user.has_scope(USERS_READ) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let limit = pagination.limit.unwrap_or(DEFAULT_LIMIT);
+
+    // Return 400 if the cursor is invalid
+    let cursor = pagination.cursor.as_deref().map(decode_cursor).transpose()?;
+    let (before_updated_at, before_id) = cursor.unzip();
+
+    let hidden_actor = is_hidden("$1", "a.actor_id");
+    let filters = active_filters(NOTIFICATIONS_CONTEXT);
+
+    // Notifications without actors (deleted or hidden users) are skipped
+    let sql = format!("
+        WITH {filters}
+        SELECT
+            n.id,
+            n.kind,
+            n.post_id,
+            p.content AS post_content,
+            COALESCE((
+                SELECT json_agg(json_build_object('id', u.id, 'username', u.username, 'title', u.title) ORDER BY va.created_at DESC)
+                FROM (
+                    SELECT a.actor_id, a.created_at FROM notification_actors a
+                    WHERE a.notification_id = n.id AND NOT {hidden_actor}
+                    ORDER BY a.created_at DESC
+                    LIMIT $5
+                ) va
+                JOIN users u ON u.id = va.actor_id
+            ), '[]') AS actors,
+            (SELECT COUNT(*) FROM notification_actors a WHERE a.notification_id = n.id AND NOT {hidden_actor}) AS actors_count,
+            n.updated_at,
+            n.read_at IS NOT NULL AS is_read,
+            {FILTERED_COLUMN}
+        FROM notifications n
+        LEFT JOIN posts p ON p.id = n.post_id
+        WHERE n.user_id = $1
+            AND EXISTS (SELECT 1 FROM notification_actors a WHERE a.notification_id = n.id AND NOT {hidden_actor})
+            AND {NOT_FILTERED}
+            AND ($2::TIMESTAMP IS NULL OR (n.updated_at, n.id) < ($2, $3))
+        ORDER BY n.updated_at DESC, n.id DESC
+        LIMIT $4;
+    ");
+
+    let query = sqlx::query_as::<_, Notification>(&sql)
+    .bind(auth_user.user_id)
+    .bind(before_updated_at)
+    .bind(before_id)
+    .bind(limit)
+    .bind(NOTIFICATION_ACTORS);
+
+    let notifications = query.fetch_all(&pool).await.map_err(|e| {
+        eprintln!("Error fetching notifications: {:?}", e);
+        StatusCode::INTERNAL_SERVER_ERROR // Return 500 if SQL error
+    })?;
+
+    // A full page may have a next one
+    let next_cursor = match notifications.last() {
+        Some(last) if notifications.len() as i64 == limit => Some(encode_cursor(Some(last.updated_at), last.id)),
+        _ => None,
+    };
+
+    Ok(Json(NotificationPage { notifications, next_cursor }))
+}
+
+/*
+ * Number of unread notifications of the connected user
+ * @auth {Conneceted} - only for conneceted users
+ */
+pub async fn unread_count(Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>) -> Result<Json<UnreadCount>, StatusCode> {
+
+    // Return 401 if user not connected
+    if !auth_user.is_connected {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    // If the API token doesn't have the users:read scope, return 403
+    if !auth_user.has_scope(USERS_READ) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    // Same notifications as list_notifications
+    let sql = format!("
+        WITH {}
+        SELECT COUNT(*) FROM notifications n
+        LEFT JOIN posts p ON p.id = n.post_id
+        WHERE n.user_id = $1 AND n.read_at IS NULL
+            AND EXISTS (SELECT 1 FROM notification_actors a WHERE a.notification_id = n.id AND NOT {})
+            AND {NOT_FILTERED};
+    ", active_filters(NOTIFICATIONS_CONTEXT), is_hidden("$1", "a.actor_id"));
+
+    let query = sqlx::query_as::<_, (i64,)>(&sql)
+    .bind(auth_user.user_id);
+
+    let (count,) = query.fetch_one(&pool).await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Json(UnreadCount { count }))
+}
+
+/*
+ * Mark a notification as read
+ * @auth {Conneceted} - only for the user who received it
+ * @param {id} - notification's id
+ */
+pub async fn mark_read(Path(id): Path<i32>, Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>) -> StatusCode {
+
+    // Return 401 if user not connected
+    if !auth_user.is_connected {
+        return StatusCode::UNAUTHORIZED;
+    }
+
+    // If the API token doesn't have the users:write scope, return 403
+    if !auth_user.has_scope(USERS_WRITE) {
+        return StatusCode::FORBIDDEN;
+    }
+
+    // Already read notifications are not changed
+    let update_result = sqlx::query("UPDATE notifications SET read_at = COALESCE(read_at, NOW()) WHERE id = $1 AND user_id = $2")
+        .bind(id)
+        .bind(auth_user.user_id)
+        .execute(&pool)
+        .await;
+
+    match update_result {
+        Ok(res) if res.rows_affected() > 0 => StatusCode::OK,
+        Ok(_) => StatusCode::NOT_FOUND, // 404 if no notification found (or received by another user)
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+/*
+ * Mark all the notifications of the connected user as read
+ * @auth {Conneceted} - only for conneceted users
+ */
+pub async fn mark_all_read(Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>) -> StatusCode {
+
+    // Return 401 if user not connected
+    if !auth_user.is_connected {
+        return StatusCode::UNAUTHORIZED;
+    }
+
+    // If the API token doesn't have the users:write scope, return 403
+    if !auth_user.has_scope(USERS_WRITE) {
+        return StatusCode::FORBIDDEN;
+    }
+
+    let update_result = sqlx::query("UPDATE notifications SET read_at = NOW() WHERE user_id = $1 AND read_at IS NULL")
+        .bind(auth_user.user_id)
+        .execute(&pool)
+        .await;
+
+    match update_result {
+        Ok(_) => StatusCode::OK,
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+/*
+ * Notify an user of an action, added to the unread notification of the same group if there is one
+ * - Nothing is done if the user did the action himself, if one of them blocked the other one, or if the user muted the actor
+ * - This is not an hanlder, but an helper function
+ */
+pub async fn add_notification(conn: &mut PgConnection, user_id: i32, actor_id: i32, kind: NotificationKind, post_id: Option<i32>) -> Result<(), StatusCode> {
+
+    if user_id == actor_id || get_is_hidden(&mut *conn, user_id, actor_id).await? {
+        return Ok(());
+    }
+
+    let query = sqlx::query("
+        WITH n AS (
+            INSERT INTO notifications (user_id, kind, post_id, group_key)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (user_id, group_key) WHERE read_at IS NULL DO UPDATE SET updated_at = NOW()
+            RETURNING id
+        )
+        INSERT INTO notification_actors (notification_id, actor_id)
+        SELECT id, $5 FROM n
+        ON CONFLICT (notification_id, actor_id) DO UPDATE SET created_at = NOW()
+        RETURNING notification_id;
+    ")
+    .bind(user_id)
+    .bind(kind.as_str())
+    .bind(post_id)
+    .bind(kind.group_key(post_id))
+    .bind(actor_id);
+
+    let notification_id: i32 = query.fetch_one(&mut *conn).await
+        .map_err(|e| {
+            eprintln!("Error adding notification: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .get("notification_id");
+
+    // Push it to the user if he's connected
+    let data = json!({ "notification_id": notification_id, "kind": kind.as_str(), "post_id": post_id });
+    publish_event(conn, NOTIFICATION_EVENT, Some(user_id), data).await
+}
+
+/*
+ * Remove an actor from the unread notification of an undone action (ex: unlike), and the notification if he was the only one
+ * - This is not an hanlder, but an helper function
+ */
+pub async fn remove_notification(conn: &mut PgConnection, user_id: i32, actor_id: i32, kind: NotificationKind, post_id: Option<i32>) -> Result<(), StatusCode> {
+
+    let query = sqlx::query("
+        WITH n AS (
+            SELECT id FROM notifications WHERE user_id = $1 AND group_key = $2 AND read_at IS NULL
+        ),
+        removed AS (
+            DELETE FROM notification_actors a USING n
+            WHERE a.notification_id = n.id AND a.actor_id = $3
+            RETURNING a.notification_id
+        )
+        DELETE FROM notifications
+        WHERE id IN (SELECT notification_id FROM removed)
+            AND NOT EXISTS (
+                SELECT 1 FROM notification_actors a
+                WHERE a.notification_id = notifications.id AND a.actor_id <> $3
+            );
+    ")
+    .bind(user_id)
+    .bind(kind.group_key(post_id))
+    .bind(actor_id);
+
+    query.execute(&mut *conn).await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(())
+}
+
+/*
+ * Notify the users mentioned in a post, once per post (an edit only notifies the new mentions)
+ * - Users blocking the author, blocked by him or muting him are not notified
+ * - This is not an hanlder, but an helper function
+ */
+pub async fn notify_mentions(conn: &mut PgConnection, post_id: i32) -> Result<(), StatusCode> {
+
+    let kind = NotificationKind::Mention;
+
+    let sql = format!("
+        WITH n AS (
+            INSERT INTO notifications (user_id, kind, post_id, group_key)
+            SELECT DISTINCT pm.user_id, $2, $1, $3
+            FROM post_mentions pm
+            JOIN posts p ON p.id = pm.post_id
+            WHERE pm.post_id = $1
+                AND pm.user_id <> p.user_id
+                AND pm.user_id IS DISTINCT FROM (SELECT pp.user_id FROM posts pp WHERE pp.id = p.parent_id) -- already notified of the reply
+                AND NOT EXISTS (SELECT 1 FROM notifications n WHERE n.user_id = pm.user_id AND n.group_key = $3)
+                AND NOT {}
+            ON CONFLICT DO NOTHING
+            RETURNING id, user_id
+        ),
+        actors AS (
+            INSERT INTO notification_actors (notification_id, actor_id)
+            SELECT n.id, p.user_id FROM n, posts p WHERE p.id = $1
+        )
+        SELECT id, user_id FROM n;
+    ", is_hidden("pm.user_id", "p.user_id"));
+
+    let query = sqlx::query(&sql)
+    .bind(post_id)
+    .bind(kind.as_str())
+    .bind(kind.group_key(Some(post_id)));
+
+    let rows = query.fetch_all(&mut *conn).await.map_err(|e| {
+        eprintln!("Error notifying mentions: {:?}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    // Push them to the users if they're connected
+    for row in rows {
+        let data = json!({ "notification_id": row.get::<i32, _>("id"), "kind": kind.as_str(), "post_id": post_id });
+        publish_event(conn, NOTIFICATION_EVENT, Some(row.get("user_id")), data).await?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,277 @@
+use std::env;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use axum::{Json, extract::{State, ConnectInfo}, http::{StatusCode, HeaderMap, HeaderName, header::SET_COOKIE}};
+use headers::{Cookie, HeaderMapExt};
+use rand::{rngs::OsRng, RngCore};
+use sqlx::{PgPool, Row};
+
+use crate::models::auth::{LoginResponse, OidcAuthorizeResponse, OidcCallbackRequest};
+use crate::auth::token_handler::hash_token;
+use crate::auth::permissions::DEFAULT_ROLE;
+use crate::oidc::{OidcProvider, IdTokenClaims, generate_random_value};
+use crate::handlers::auth_handlers::finish_login;
+use crate::mail::app_url;
+
+// How long the user has to log in on the provider, can be overridden with OIDC_STATE_MINUTES
+const DEFAULT_OIDC_STATE_MINUTES: i64 = 10;
+
+// Cookie tying the state to the browser which started the login (no login CSRF)
+const OIDC_STATE_COOKIE: &str = "oidc_state";
+
+// Size of the email columns (users & user_identities)
+const MAX_EMAIL_LENGTH: usize = 100;
+
+/*
+ * Start a login with the OpenID Connect provider
+ * - The client must send the user to the returned URL, the provider then redirects him to OIDC_REDIRECT_URI
+ * - The state is also set in a cookie, the client must send it back to /auth/oidc/callback (credentials: include)
+ * @auth {None} - no authorization needed
+ */
+pub async fn oidc_authorize(State(oidc): State<Option<Arc<OidcProvider>>>, State(pool): State<PgPool>) -> Result<([(HeaderName, String); 1], Json<OidcAuthorizeResponse>), StatusCode> {
+
+    // Return 404 if OIDC login is not configured
+    let provider = oidc.ok_or(StatusCode::NOT_FOUND)?;
+
+    let state = generate_random_value();
+    let nonce = generate_random_value();
+    let code_verifier = generate_random_value();
+
+    let minutes = env::var("OIDC_STATE_MINUTES").ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_OIDC_STATE_MINUTES);
+
+    let expires_at = chrono::Utc::now().naive_utc() + chrono::Duration::minutes(minutes);
+
+    // Save the login, and forget the ones that were never finished
+    let query = sqlx::query("
+        WITH expired AS (DELETE FROM oidc_login_states WHERE expires_at < NOW())
+        INSERT INTO oidc_login_states (state_hash, code_verifier, nonce, expires_at) VALUES ($1, $2, $3, $4);
+    ")
+    .bind(hash_token(&state))
+    .bind(&code_verifier)
+    .bind(&nonce)
+    .bind(expires_at);
+
+    query.execute(&pool).await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    // Return 502 if the provider can't be reached
+    let authorization_url = provider.authorization_url(&state, &nonce, &code_verifier).await
+        .map_err(|e| {
+            eprintln!("Error with the OIDC provider: {e}");
+            StatusCode::BAD_GATEWAY
+        })?;
+
+    let cookie = state_cookie(&state, minutes * 60);
+
+    Ok(([(SET_COOKIE, cookie)], Json(OidcAuthorizeResponse { authorization_url })))
+}
+
+/*
+ * Finish a login with the OpenID Connect provider
+ * - The identity is linked to an user: the one already linked, the one with the same verified email, or a new one
+ * - A new user can't be created without email (403), the provider must send the "email" claim
+ * - If the user enabled 2FA, a challenge token is returned like for /auth/login
+ * @auth {None} - the code and the state given by the provider are the authorization, the state cookie must match the state
+ * @param {OidcCallbackRequest} - the code and the state received on OIDC_REDIRECT_URI
+ */
+pub async fn oidc_callback(State(oidc): State<Option<Arc<OidcProvider>>>, State(pool): State<PgPool>, ConnectInfo(addr): ConnectInfo<SocketAddr>, headers: HeaderMap, Json(payload): Json<OidcCallbackRequest>) -> Result<([(HeaderName, String); 1], Json<LoginResponse>), StatusCode> {
+
+    // Return 404 if OIDC login is not configured
+    let provider = oidc.ok_or(StatusCode::NOT_FOUND)?;
+
+    // Return 401 if the login was not started by this browser
+    let cookie_state = headers.typed_get::<Cookie>()
+        .and_then(|cookie| cookie.get(OIDC_STATE_COOKIE).map(String::from));
+
+    if cookie_state.as_deref() != Some(payload.state.as_str()) {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    // A state can only be used once
+    let query = sqlx::query("
+        DELETE FROM oidc_login_states WHERE state_hash = $1
+        RETURNING code_verifier, nonce, expires_at > NOW() AS is_valid;
+    ")
+    .bind(hash_token(&payload.state));
+
+    let row = query.fetch_optional(&pool).await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::UNAUTHORIZED)?; // 401 if unknown or already used state
+
+    let is_valid: bool = row.get("is_valid");
+
+    // 401 if expired state
+    if !is_valid {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    let code_verifier: String = row.get("code_verifier");
+    let nonce: String = row.get("nonce");
+
+    // Return 401 if the code or the ID token is not valid
+    let claims = provider.exchange_code(&payload.code, &code_verifier, &nonce).await
+        .map_err(|e| {
+            eprintln!("OIDC login failed: {e}");
+            StatusCode::UNAUTHORIZED
+        })?;
+
+    let user_id = find_or_create_user(&pool, provider.issuer(), &claims).await?;
+
+    let response = finish_login(&pool, user_id, &addr, &headers).await?;
+
+    // The state is used, remove the cookie
+    Ok(([(SET_COOKIE, state_cookie("", 0))], Json(response)))
+}
+
+/*
+ * Set-Cookie value of the state cookie, only sent back to the OIDC routes
+ * - This is not an hanlder, but an helper function
+ */
+fn state_cookie(state: &str, max_age: i64) -> String {
+
+    let secure = if app_url().starts_with("https://") { "; Secure" } else { "" };
+
+    format!("{OIDC_STATE_COOKIE}={state}; Max-Age={max_age}; Path=/auth/oidc; HttpOnly; SameSite=Lax{secure}")
+}
+
+/*
+ * Get the user linked to an identity of the provider, link or create one if needed
+ * - This is not an hanlder, but an helper function
+ */
+async fn find_or_create_user(pool: &PgPool, issuer: &str, claims: &IdTokenClaims) -> Result<i32, StatusCode> {
+
+    // Return 403 if the email given by the provider can't be stored
+    if claims.email.as_deref().is_some_and(|email| email.chars().count() > MAX_EMAIL_LENGTH) {
+        eprintln!("OIDC login refused: email too long for the subject {}", claims.sub);
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    // Identity already linked
+    let query = sqlx::query("
+        UPDATE user_identities SET last_login_at = NOW(), email = COALESCE($3, email)
+        WHERE issuer = $1 AND subject = $2
+        RETURNING user_id;
+    ")
+    .bind(issuer)
+    .bind(&claims.sub)
+    .bind(&claims.email);
+
+    let row = query.fetch_optional(pool).await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    if let Some(row) = row {
+        return Ok(row.get("user_id"));
+    }
+
+    // Start transaction
+    let mut tx = pool.begin().await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    // Link to the account with the same email, only if both sides verified it (and there is only one)
+    let mut existing_id = None;
+
+    if let (Some(email), true) = (&claims.email, claims.email_verified) {
+
+        let rows = sqlx::query("SELECT id FROM users WHERE LOWER(email) = LOWER($1) AND email_verified_at IS NOT NULL LIMIT 2")
+            .bind(email)
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        if rows.len() == 1 {
+            existing_id = Some(rows[0].get::<i32, _>("id"));
+        }
+    }
+
+    let user_id = match existing_id {
+
+        Some(id) => id,
+
+        // Create a new user, without password (he can set one with a password reset)
+        None => {
+
+            // Return 403 if the provider gave no email (the user could never reset a password or get mails)
+            let email = claims.email.as_deref()
+                .filter(|email| !email.is_empty())
+                .ok_or_else(|| {
+                    eprintln!("OIDC login refused: no email for the subject {}", claims.sub);
+                    StatusCode::FORBIDDEN
+                })?;
+
+            let base_username = get_username_candidate(claims);
+            let mut username = base_username.clone();
+            let mut created_id: Option<i32> = None;
+
+            // Add a random suffix while the username is taken
+            for _ in 0..5 {
+
+                let query = sqlx::query("
+                    INSERT INTO users (username, email, password, role_id, email_verified_at)
+                    VALUES ($1, $2, '', (SELECT id FROM roles WHERE name = $3), CASE WHEN $4 THEN NOW() END)
+                    ON CONFLICT (username) DO NOTHING
+                    RETURNING id;
+                ")
+                .bind(&username)
+                .bind(email)
+                .bind(DEFAULT_ROLE)
+                .bind(claims.email_verified);
+
+                let row = query.fetch_optional(&mut *tx).await
+                    .map_err(|e| {
+                        eprintln!("Error creating OIDC user: {:?}", e);
+                        StatusCode::INTERNAL_SERVER_ERROR
+                    })?;
+
+                if let Some(row) = row {
+                    created_id = Some(row.get("id"));
+                    break;
+                }
+
+                let mut suffix = [0u8; 2];
+                OsRng.fill_bytes(&mut suffix);
+                username = format!("{base_username}_{}", hex::encode(suffix));
+            }
+
+            created_id.ok_or(StatusCode::CONFLICT)?
+        }
+    };
+
+    sqlx::query("INSERT INTO user_identities (user_id, issuer, subject, email) VALUES ($1, $2, $3, $4)")
+        .bind(user_id)
+        .bind(issuer)
+        .bind(&claims.sub)
+        .bind(&claims.email)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| {
+            eprintln!("Error linking OIDC identity: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    // Commit -> Apply all queries
+    tx.commit().await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(user_id)
+}
+
+/*
+ * Username for a new user, from the profile given by the provider
+ */
+fn get_username_candidate(claims: &IdTokenClaims) -> String {
+
+    let source = claims.preferred_username.as_deref()
+        .or(claims.email.as_deref().and_then(|email| email.split('@').next()))
+        .or(claims.name.as_deref())
+        .unwrap_or("user");
+
+    // Keep the username short enough for the random suffix (users.username is VARCHAR(50))
+    let username: String = source.chars()
+        .filter(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.'))
+        .take(40)
+        .collect();
+
+    if username.is_empty() { String::from("user") } else { username }
+}
@@ -0,0 +1,156 @@
+use std::sync::Arc;
+use axum::{Json, extract::State, http::StatusCode};
+use argon2::{Argon2, PasswordHasher, password_hash::SaltString};
+use rand::rngs::OsRng;
+use sqlx::{PgPool, Row};
+
+use crate::models::auth::{ForgotPasswordRequest, ResetPasswordRequest};
+use crate::auth::token_handler::{generate_opaque_token, hash_token, password_reset_duration};
+use crate::mail::{Mail, Mailer, app_url};
+
+/*
+ * Send a password reset link to the users with this email
+ * - Always returns 204 right away, the links are created and sent in background:
+ *   the response (and its time) doesn't tell if an email is registered
+ * @auth {None} - no authorization needed
+ * @param {ForgotPasswordRequest} - the account email
+ */
+pub async fn forgot_password(State(pool): State<PgPool>, State(mailer): State<Arc<dyn Mailer>>, Json(payload): Json<ForgotPasswordRequest>) -> StatusCode {
+
+    tokio::spawn(async move {
+        if let Err(e) = send_reset_mails(&pool, mailer, &payload.email).await {
+            eprintln!("Error creating password reset links: {:?}", e);
+        }
+    });
+
+    StatusCode::NO_CONTENT
+}
+
+/*
+ * Create a reset token for each user with this email, and send it by mail
+ * - This is not an hanlder, but an helper function
+ */
+async fn send_reset_mails(pool: &PgPool, mailer: Arc<dyn Mailer>, email: &str) -> Result<(), sqlx::Error> {
+
+    let users = sqlx::query("SELECT id, username, email FROM users WHERE email = $1")
+        .bind(email)
+        .fetch_all(pool)
+        .await?;
+
+    for row in users {
+
+        let user_id: i32 = row.get("id");
+        let username: String = row.get("username");
+        let email: String = row.get("email");
+
+        // Only the last requested link is usable
+        sqlx::query("UPDATE password_reset_tokens SET used_at = NOW() WHERE user_id = $1 AND used_at IS NULL")
+            .bind(user_id)
+            .execute(pool)
+            .await?;
+
+        let token = generate_opaque_token();
+        let expires_at = chrono::Utc::now().naive_utc() + password_reset_duration();
+
+        sqlx::query("INSERT INTO password_reset_tokens (user_id, token_hash, expires_at) VALUES ($1, $2, $3)")
+            .bind(user_id)
+            .bind(hash_token(&token))
+            .bind(expires_at)
+            .execute(pool)
+            .await?;
+
+        let mail = Mail {
+            to: email,
+            subject: String::from("FEUR - Reset your password"),
+            body: format!(
+                "Hello {username},\n\nTo choose a new password, open this link:\n{}/reset-password?token={token}\n\nThis link expires in {} minutes. If you did not ask for it, you can ignore this mail.",
+                app_url(),
+                password_reset_duration().num_minutes()
+            ),
+        };
+
+        // Already in background, a failed mail is only logged
+        if let Err(e) = mailer.send(mail).await {
+            eprintln!("Error sending password reset mail: {e}");
+        }
+    }
+
+    Ok(())
+}
+
+/*
+ * Set a new password with a token received by mail
+ * - The token can only be used once, all the user sessions are revoked
+ * @auth {None} - the reset token is the authorization
+ * @param {ResetPasswordRequest} - the token and the new password
+ */
+pub async fn reset_password(State(pool): State<PgPool>, Json(payload): Json<ResetPasswordRequest>) -> StatusCode {
+
+    // Generate and Hash the new password
+    let salt = SaltString::generate(&mut OsRng);
+    let password_hash = match Argon2::default().hash_password(payload.password.as_bytes(), &salt) {
+        Ok(hash) => hash.to_string(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR,
+    };
+
+    // Start transaction
+    let mut tx = match pool.begin().await {
+        Ok(t) => t,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR,
+    };
+
+    // Consume the token, if it is valid
+    let token_result = sqlx::query("
+        UPDATE password_reset_tokens SET used_at = NOW()
+        WHERE token_hash = $1 AND used_at IS NULL AND expires_at > NOW()
+        RETURNING user_id;
+    ")
+    .bind(hash_token(&payload.token))
+    .fetch_optional(&mut *tx)
+    .await;
+
+    let user_id: i32 = match token_result {
+        Ok(Some(row)) => row.get("user_id"),
+        Ok(None) => return StatusCode::UNAUTHORIZED, // 401 if unknown, used or expired token
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR,
+    };
+
+    // Update password
+    let update_result = sqlx::query("UPDATE users SET password = $1 WHERE id = $2")
+        .bind(&password_hash)
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await;
+
+    if update_result.is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    }
+
+    // Logout everywhere and revoke the API tokens, someone else may know the old password
+    let sessions_result = sqlx::query("
+        WITH revoked_sessions AS (
+            UPDATE sessions SET revoked_at = NOW()
+            WHERE user_id = $1 AND revoked_at IS NULL
+        ),
+        revoked_tokens AS (
+            UPDATE api_tokens SET revoked_at = NOW()
+            WHERE user_id = $1 AND revoked_at IS NULL
+        )
+        UPDATE refresh_tokens SET revoked_at = NOW()
+        WHERE user_id = $1 AND revoked_at IS NULL;
+    ")
+    .bind(user_id)
+    .execute(&mut *tx)
+    .await;
+
+    if sessions_result.is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    }
+
+    // Commit -> Apply all queries
+    if tx.commit().await.is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    }
+
+    StatusCode::NO_CONTENT
+}
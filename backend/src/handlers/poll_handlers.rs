@@ -0,0 +1,180 @@
+use std::collections::HashSet;
+use axum::{extract::{Path, State, Form, Extension}, Json, http::StatusCode};
+use sqlx::{PgPool, PgConnection, Row};
+use crate::models::post::PostWithUserData;
+use crate::models::poll::FormVote;
+use crate::models::auth::AuthUser;
+use crate::auth::scopes::POSTS_WRITE;
+use crate::handlers::parse_ids;
+use crate::handlers::post_handlers::get_post;
+use crate::handlers::block_handlers::get_is_blocked;
+
+// Number of options of a poll
+const MIN_POLL_OPTIONS: usize = 2;
+const MAX_POLL_OPTIONS: usize = 4;
+
+// Longest label of an option (size of the column)
+const MAX_OPTION_LENGTH: usize = 100;
+
+// Time before a poll closes, 1 day by default, from 5 minutes to 7 days
+const DEFAULT_POLL_DURATION_MINUTES: i32 = 24 * 60;
+const MIN_POLL_DURATION_MINUTES: i32 = 5;
+const MAX_POLL_DURATION_MINUTES: i32 = 7 * 24 * 60;
+
+/*
+ * Vote for one option of a poll (or several if it's a multiple choice poll), returns the post with the results
+ * - A user can vote only once, votes can't be changed
+ * @auth {Conneceted} - only for conneceted users, while the poll is open (and if the author didn't block him, or the opposite)
+ * @param {id} - post's id of the poll
+ * @param {FormVote} - form input data
+ */
+pub async fn vote_poll(Path(id): Path<i32>, Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>, Form(payload): Form<FormVote>) -> Result<Json<PostWithUserData>, StatusCode> {
+
+    // Return 401 if user not connected
+    if !auth_user.is_connected {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    // If the API token doesn't have the posts:write scope, return 403
+    if !auth_user.has_scope(POSTS_WRITE) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let option_ids = parse_ids(&payload.option_ids)?;
+
+    // Start transaction
+    let mut tx = pool.begin().await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let query = sqlx::query("
+        SELECT pl.multiple_choice, pl.closes_at <= NOW() AS is_closed, p.user_id AS author_id
+        FROM polls pl
+        JOIN posts p ON p.id = pl.post_id
+        WHERE pl.post_id = $1;
+    ")
+    .bind(id);
+
+    let poll = query.fetch_optional(&mut *tx).await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?; // Return 404 if no poll found
+
+    // Return 403 if the poll is closed
+    if poll.get::<bool, _>("is_closed") {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    // Return 403 if the author blocked the user (or was blocked by him)
+    if get_is_blocked(&mut *tx, auth_user.user_id, poll.get("author_id")).await? {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    // Return 400 without option, or with several options if it's not a multiple choice poll
+    if option_ids.is_empty() || (option_ids.len() > 1 && !poll.get::<bool, _>("multiple_choice")) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    // Insert voter, the primary key ensures one vote per user
+    let insert_result = sqlx::query("INSERT INTO poll_voters (post_id, user_id) VALUES ($1, $2) ON CONFLICT DO NOTHING")
+        .bind(id)
+        .bind(auth_user.user_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    // Return 409 if already voted
+    if insert_result.rows_affected() == 0 {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    // Insert votes, only for the options of this poll
+    let insert_result = sqlx::query("
+        INSERT INTO poll_votes (option_id, user_id)
+        SELECT id, $2 FROM poll_options WHERE post_id = $1 AND id = ANY($3);
+    ")
+    .bind(id)
+    .bind(auth_user.user_id)
+    .bind(&option_ids)
+    .execute(&mut *tx)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    // Return 400 if an option is not one of the poll (the transaction is rolled back)
+    if insert_result.rows_affected() != option_ids.len() as u64 {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    // Update votes counts
+    sqlx::query("UPDATE poll_options SET votes_count = votes_count + 1 WHERE id = ANY($1)")
+        .bind(&option_ids)
+        .execute(&mut *tx)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    sqlx::query("UPDATE polls SET voters_count = voters_count + 1 WHERE post_id = $1")
+        .bind(id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let post = get_post(&mut *tx, id, auth_user.user_id).await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    // Commit -> Apply all queries
+    tx.commit().await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    // Return the post, with the results
+    Ok(Json(post))
+}
+
+/*
+ * Create the poll of a new post
+ * - options: one option per line, MIN_POLL_OPTIONS to MAX_POLL_OPTIONS different options
+ * - Return 400 if the options or the duration are invalid
+ * - This is not an hanlder, but an helper function
+ */
+pub async fn save_poll(conn: &mut PgConnection, post_id: i32, options: &str, duration_minutes: Option<i32>, multiple_choice: bool) -> Result<(), StatusCode> {
+
+    let options: Vec<&str> = options.lines()
+        .map(str::trim)
+        .filter(|option| !option.is_empty())
+        .collect();
+
+    if options.len() < MIN_POLL_OPTIONS || options.len() > MAX_POLL_OPTIONS {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    // Return 400 if an option is too long or given twice
+    if options.iter().any(|option| option.chars().count() > MAX_OPTION_LENGTH)
+        || options.iter().collect::<HashSet<_>>().len() != options.len()
+    {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let duration_minutes = duration_minutes.unwrap_or(DEFAULT_POLL_DURATION_MINUTES);
+
+    if !(MIN_POLL_DURATION_MINUTES..=MAX_POLL_DURATION_MINUTES).contains(&duration_minutes) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    sqlx::query("INSERT INTO polls (post_id, multiple_choice, closes_at) VALUES ($1, $2, NOW() + make_interval(mins => $3))")
+        .bind(post_id)
+        .bind(multiple_choice)
+        .bind(duration_minutes)
+        .execute(&mut *conn)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    sqlx::query("
+        INSERT INTO poll_options (post_id, position, label)
+        SELECT $1, position - 1, label FROM UNNEST($2::TEXT[]) WITH ORDINALITY AS o(label, position);
+    ")
+    .bind(post_id)
+    .bind(&options)
+    .execute(&mut *conn)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(())
+}
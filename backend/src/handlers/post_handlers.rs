@@ -1,47 +1,185 @@
+use std::env;
+use std::sync::Arc;
+use std::collections::HashMap;
 use axum::{extract::{Path, State, Form, Extension, Query}, Json, http::StatusCode};
-use sqlx::PgPool;
-use crate::models::post::{PostWithUserData, FormPost};
+use sqlx::{PgPool, PgConnection, Row};
+use serde_json::json;
+use crate::models::post::{PostWithUserData, FormPost, PostThread, ThreadNode, ThreadPost, PostRevision};
 use crate::models::auth::AuthUser;
-use crate::handlers::{DEFAULT_LIMIT, DEFAULT_OFFSET, PaginationQuery, auth_handlers::get_is_admin};
+use crate::auth::scopes::{POSTS_READ, POSTS_WRITE};
+use crate::auth::permissions::{POST_DELETE_ANY, require_permission};
+use crate::entities::{parse_entities, EntityKind};
+use crate::models::tag::TagPosts;
+use crate::media::max_media_per_post;
+use crate::storage::Storage;
+use crate::handlers::media_handlers::delete_files;
+use crate::handlers::poll_handlers::save_poll;
+use crate::handlers::notification_handlers::{add_notification, remove_notification, notify_mentions};
+use crate::models::notification::NotificationKind;
+use crate::handlers::stream_handlers::publish_event;
+use crate::handlers::block_handlers::{is_blocked, is_hidden, get_is_blocked};
+use crate::handlers::filter_handlers::{HOME_CONTEXT, NOT_FILTERED, FILTERED_COLUMN, active_filters};
+use crate::stream::{POST_EVENT, LIKES_EVENT};
+use crate::handlers::{DEFAULT_LIMIT, DEFAULT_OFFSET, PaginationQuery, KeysetQuery, encode_cursor, decode_cursor, parse_ids, auth_handlers::get_is_verified};
+
+// Levels of replies returned by get_thread under the post
+const THREAD_DEPTH: i32 = 3;
+
+// Posts and deleted posts of the threads, to walk the replies through the deleted ones
+const THREAD_NODES: &str = "(
+    SELECT id, parent_id, created_at, FALSE AS is_deleted FROM posts
+    UNION ALL
+    SELECT id, parent_id, created_at, TRUE AS is_deleted FROM deleted_posts
+)";
+
+// Time the author has to edit a post, can be overridden with POST_EDIT_MINUTES
+const DEFAULT_POST_EDIT_MINUTES: i64 = 15;
+
+// Columns of PostWithUserData, $1 must be the connected user (-1 if not connected)
+pub(crate) const POST_COLUMNS: &str = "
+    p.id,
+    p.content,
+    p.created_at,
+    p.likes_count,
+    p.parent_id,
+    p.replies_count,
+    p.reposts_count,
+    p.quotes_count,
+    p.edited_at,
+    u.id AS user_id,
+    u.username AS user_username,
+    u.title AS user_title,
+    u.created_at AS user_created_at,
+    EXISTS (SELECT 1 FROM user_likes ul WHERE ul.post_id = p.id AND ul.user_id = $1) AS auth_is_liked,
+    EXISTS (SELECT 1 FROM reposts r WHERE r.post_id = p.id AND r.user_id = $1) AS auth_is_reposted,
+    EXISTS (SELECT 1 FROM bookmarks b WHERE b.post_id = p.id AND b.user_id = $1) AS auth_is_bookmarked,
+    p.quoted_post_id,
+    CASE WHEN p.quoted_post_id IS NOT NULL THEN qp.id IS NOT NULL END AS quoted_is_available,
+    qp.content AS quoted_content,
+    qp.created_at AS quoted_created_at,
+    qu.id AS quoted_user_id,
+    qu.username AS quoted_user_username,
+    COALESCE((
+        SELECT json_agg(e ORDER BY e.start)
+        FROM (
+            SELECT 'hashtag' AS kind, ph.start_index AS start, ph.end_index AS end, h.name AS value, NULL::INTEGER AS user_id
+            FROM post_hashtags ph JOIN hashtags h ON h.id = ph.hashtag_id WHERE ph.post_id = p.id
+            UNION ALL
+            SELECT 'mention', pm.start_index, pm.end_index, mu.username, mu.id
+            FROM post_mentions pm JOIN users mu ON mu.id = pm.user_id WHERE pm.post_id = p.id
+            UNION ALL
+            SELECT 'url', pu.start_index, pu.end_index, pu.url, NULL
+            FROM post_urls pu WHERE pu.post_id = p.id
+        ) e
+    ), '[]') AS entities,
+    COALESCE((
+        SELECT json_agg(json_build_object(
+            'id', m.id,
+            'url', m.url,
+            'thumbnail_url', m.thumbnail_url,
+            'content_type', m.content_type,
+            'width', m.width,
+            'height', m.height,
+            'alt_text', m.alt_text
+        ) ORDER BY m.position)
+        FROM media m WHERE m.post_id = p.id
+    ), '[]') AS media,
+    (
+        SELECT json_build_object(
+            'multiple_choice', pl.multiple_choice,
+            'closes_at', pl.closes_at,
+            'is_closed', pl.closes_at <= NOW(),
+            'voters_count', pl.voters_count,
+            'auth_has_voted', pv.has_voted,
+            'options', (
+                SELECT json_agg(json_build_object(
+                    'id', po.id,
+                    'label', po.label,
+                    'votes_count', CASE WHEN pv.has_voted OR pl.closes_at <= NOW() THEN po.votes_count END,
+                    'auth_is_voted', EXISTS (SELECT 1 FROM poll_votes v WHERE v.option_id = po.id AND v.user_id = $1)
+                ) ORDER BY po.position)
+                FROM poll_options po WHERE po.post_id = pl.post_id
+            )
+        )
+        FROM polls pl
+        CROSS JOIN LATERAL (
+            SELECT EXISTS (SELECT 1 FROM poll_voters v WHERE v.post_id = pl.post_id AND v.user_id = $1) AS has_voted
+        ) pv
+        WHERE pl.post_id = p.id
+    ) AS poll
+";
+
+// Joins needed by POST_COLUMNS, after FROM posts p
+// A quoted post of an user blocking or blocked by $1 is not available (see is_blocked)
+pub(crate) const POST_JOINS: &str = "
+    JOIN users u ON p.user_id = u.id
+    LEFT JOIN posts qp ON qp.id = p.quoted_post_id
+        AND NOT EXISTS (SELECT 1 FROM blocks bk WHERE (bk.blocker_id = $1 AND bk.blocked_id = qp.user_id) OR (bk.blocker_id = qp.user_id AND bk.blocked_id = $1))
+    LEFT JOIN users qu ON qu.id = qp.user_id
+";
+
+// Posts and reposts of the feeds, each entry is a post (reposted_by NULL) or a repost, sorted by activity_at
+const FEED_ENTRIES: &str = "
+    entries AS (
+        SELECT id AS post_id, NULL::INTEGER AS reposted_by, created_at AS activity_at FROM posts
+        UNION ALL
+        SELECT post_id, user_id, created_at FROM reposts
+    )
+";
+
+// Columns of a feed entry, after POST_COLUMNS
+const FEED_COLUMNS: &str = "
+    ru.id AS reposted_by_user_id,
+    ru.username AS reposted_by_username,
+    CASE WHEN ru.id IS NOT NULL THEN e.activity_at END AS reposted_at
+";
 
 
 /*
- * List all posts from the database
+ * List all posts from the database (explore feed)
+ * - Reposts are entries of the feed too, with the user who reposted
+ * - Posts and reposts of the users blocked (in any direction) or muted by the connected user are hidden
+ * - Posts matching his content filters (home context) are hidden, or marked with the filters
  * @auth {None} - no authorization needed
  */
 pub async fn list(Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>, Query(pagination): Query<PaginationQuery>) -> Result<Json<Vec<PostWithUserData>>, StatusCode> {
 
+    // If the API token doesn't have the posts:read scope, return 403
+    if auth_user.is_connected && !auth_user.has_scope(POSTS_READ) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
     // The goal is if the user is connected, return his likes, otherwise set all likes to false wit
     // user -1.
     let user_id = if auth_user.is_connected { auth_user.user_id } else { -1 };
 
     // Get values for pagination or else get default values
-    let limit = pagination.limit.unwrap_or(DEFAULT_LIMIT); 
+    let limit = pagination.limit.unwrap_or(DEFAULT_LIMIT);
     let offset = pagination.offset.unwrap_or(DEFAULT_OFFSET);
 
+    let hidden_author = is_hidden("$1", "p.user_id");
+    let hidden_reposter = is_hidden("$1", "e.reposted_by");
+    let filters = active_filters(HOME_CONTEXT);
+
     // Create query
-    let query = sqlx::query_as::<_, PostWithUserData>("
-        SELECT
-            p.id,
-            p.content,
-            p.created_at,
-            p.likes_count,
-            u.id AS user_id,
-            u.username AS user_username,
-            u.title AS user_title,
-            u.created_at AS user_created_at,
-            -- Check if a match was found in the LEFT JOIN
-            CASE WHEN ul.user_id IS NOT NULL THEN TRUE ELSE FALSE END AS auth_is_liked
-        FROM posts p
-        JOIN users u ON p.user_id = u.id
-        LEFT JOIN user_likes ul ON ul.post_id = p.id AND ul.user_id = $1
-        ORDER BY p.created_at DESC, p.id DESC
+    let sql = format!("
+        WITH {FEED_ENTRIES}, {filters}
+        SELECT {POST_COLUMNS}, {FEED_COLUMNS}, {FILTERED_COLUMN}
+        FROM entries e
+        JOIN posts p ON p.id = e.post_id
+        {POST_JOINS}
+        LEFT JOIN users ru ON ru.id = e.reposted_by
+        WHERE NOT {hidden_author} AND NOT {hidden_reposter}
+            AND {NOT_FILTERED}
+        ORDER BY e.activity_at DESC, p.id DESC
         LIMIT $2
         OFFSET $3;
-    ")
-    .bind(user_id)
-    .bind(limit)
-    .bind(offset);
+    ");
+
+    let query = sqlx::query_as::<_, PostWithUserData>(&sql)
+        .bind(user_id)
+        .bind(limit)
+        .bind(offset);
 
     let posts = query.fetch_all(&pool).await.map_err(|e| {
         eprintln!("Error fetching posts: {:?}", e);
@@ -51,60 +189,237 @@ pub async fn list(Extension(auth_user): Extension<AuthUser>, State(pool): State<
     Ok(Json(posts))
 }
 
+/*
+ * Home timeline: posts of the users followed by the connected user, and his own posts
+ * - The global list (list) is the explore feed
+ * - Reposts made by these users are entries of the feed too
+ * - Posts and reposts of the users blocked (in any direction) or muted by the connected user are hidden
+ * - Posts matching his content filters (home context) are hidden, or marked with the filters
+ * @auth {Connected} - only for connected users
+ */
+pub async fn feed(Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>, Query(pagination): Query<PaginationQuery>) -> Result<Json<Vec<PostWithUserData>>, StatusCode> {
+
+    // If the user is not connected, return 401
+    if !auth_user.is_connected {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    // If the API token doesn't have the posts:read scope, return 403
+    if !auth_user.has_scope(POSTS_READ) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    // Get values for pagination or else get default values
+    let limit = pagination.limit.unwrap_or(DEFAULT_LIMIT);
+    let offset = pagination.offset.unwrap_or(DEFAULT_OFFSET);
+
+    let hidden_author = is_hidden("$1", "p.user_id");
+    let hidden_reposter = is_hidden("$1", "e.reposted_by");
+    let filters = active_filters(HOME_CONTEXT);
+
+    // The author of a post, or the user who reposted it, must be followed (or be the user)
+    let sql = format!("
+        WITH {FEED_ENTRIES}, {filters}
+        SELECT {POST_COLUMNS}, {FEED_COLUMNS}, {FILTERED_COLUMN}
+        FROM entries e
+        JOIN posts p ON p.id = e.post_id
+        {POST_JOINS}
+        LEFT JOIN users ru ON ru.id = e.reposted_by
+        WHERE (COALESCE(e.reposted_by, p.user_id) = $1
+                OR COALESCE(e.reposted_by, p.user_id) IN (SELECT followed_id FROM follows WHERE follower_id = $1))
+            AND NOT {hidden_author} AND NOT {hidden_reposter}
+            AND {NOT_FILTERED}
+        ORDER BY e.activity_at DESC, p.id DESC
+        LIMIT $2
+        OFFSET $3;
+    ");
+
+    let query = sqlx::query_as::<_, PostWithUserData>(&sql)
+        .bind(auth_user.user_id)
+        .bind(limit)
+        .bind(offset);
+
+    let posts = query.fetch_all(&pool).await.map_err(|e| {
+        eprintln!("Error fetching feed: {:?}", e);
+        StatusCode::INTERNAL_SERVER_ERROR // Return 500 if SQL error
+    })?;
+
+    Ok(Json(posts))
+}
+
+/*
+ * List the posts with a hashtag, most recent first (keyset pagination)
+ * - Posts of the users blocked (in any direction) or muted by the connected user are hidden
+ * - Posts matching his content filters (home context) are hidden, or marked with the filters
+ * @auth {None} - no authorization needed
+ * @param {tag} - the hashtag, with or without #
+ */
+pub async fn list_by_tag(Path(tag): Path<String>, Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>, Query(pagination): Query<KeysetQuery>) -> Result<Json<TagPosts>, StatusCode> {
+
+    // If the API token doesn't have the posts:read scope, return 403
+    if auth_user.is_connected && !auth_user.has_scope(POSTS_READ) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    // Same as list, user -1 if not connected
+    let user_id = if auth_user.is_connected { auth_user.user_id } else { -1 };
+
+    let limit = pagination.limit.unwrap_or(DEFAULT_LIMIT);
+
+    // Return 400 if the cursor is invalid
+    let cursor = pagination.cursor.as_deref().map(decode_cursor).transpose()?;
+    let (before_created_at, before_id) = cursor.unzip();
+
+    // Tags are saved lowercase
+    let tag = tag.trim_start_matches('#').to_lowercase();
+
+    let hashtag_id: i32 = sqlx::query("SELECT id FROM hashtags WHERE name = $1")
+        .bind(&tag)
+        .fetch_optional(&pool)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)? // Return 404 if the tag was never used
+        .get("id");
+
+    let hidden_author = is_hidden("$1", "p.user_id");
+    let filters = active_filters(HOME_CONTEXT);
+
+    let sql = format!("
+        WITH {filters}
+        SELECT {POST_COLUMNS}, {FILTERED_COLUMN}
+        FROM posts p
+        {POST_JOINS}
+        WHERE EXISTS (SELECT 1 FROM post_hashtags ph WHERE ph.post_id = p.id AND ph.hashtag_id = $2)
+            AND NOT {hidden_author}
+            AND {NOT_FILTERED}
+            AND ($3::TIMESTAMP IS NULL OR (p.created_at, p.id) < ($3, $4))
+        ORDER BY p.created_at DESC, p.id DESC
+        LIMIT $5;
+    ");
+
+    let query = sqlx::query_as::<_, PostWithUserData>(&sql)
+        .bind(user_id)
+        .bind(hashtag_id)
+        .bind(before_created_at)
+        .bind(before_id)
+        .bind(limit);
+
+    let posts = query.fetch_all(&pool).await.map_err(|e| {
+        eprintln!("Error fetching tag posts: {:?}", e);
+        StatusCode::INTERNAL_SERVER_ERROR // Return 500 if SQL error
+    })?;
+
+    // A full page may have a next one
+    let next_cursor = match posts.last() {
+        Some(last) if posts.len() as i64 == limit => Some(encode_cursor(last.created_at, last.id)),
+        _ => None,
+    };
+
+    Ok(Json(TagPosts { tag, posts, next_cursor }))
+}
+
 /*
  * Get data from a specific post
+ * - 404 if its author blocked the connected user, or was blocked by him
  * @auth {None} - no authorization needed
  * @param {id} - post's id
  */
-pub async fn get_by_id(Path(id): Path<i32>, State(pool): State<PgPool>) -> Result<Json<PostWithUserData>, StatusCode> {
-
-    let query = sqlx::query_as::<_, PostWithUserData>("
-        SELECT 
-            p.id,
-            p.content,
-            p.created_at,
-            p.likes_count,
-            u.id as user_id,
-            u.username as user_username,
-            u.title as user_title,
-            u.created_at as user_created_at
-        FROM posts p
-        JOIN users u ON p.user_id = u.id
-        WHERE p.id = $1;
-        ").bind(id);
+pub async fn get_by_id(Path(id): Path<i32>, Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>) -> Result<Json<PostWithUserData>, StatusCode> {
+
+    // If the API token doesn't have the posts:read scope, return 403
+    if auth_user.is_connected && !auth_user.has_scope(POSTS_READ) {
+        return Err(StatusCode::FORBIDDEN);
+    }
 
-    let post = query.fetch_one(&pool).await.map_err(|e| {
+    // Same as list, user -1 if not connected
+    let user_id = if auth_user.is_connected { auth_user.user_id } else { -1 };
+
+    let post = get_post(&pool, id, user_id).await.map_err(|e| {
         eprintln!("Error fetching posts: {:?}", e);
         StatusCode::INTERNAL_SERVER_ERROR // Return 500 if SQL error
     })?;
 
-    Ok(Json(post))
+    // Return 404 if no post found
+    post.map(Json).ok_or(StatusCode::NOT_FOUND)
 }
 
 /*
  * Delete a post
- * @auth {Admin} - only for admin users
+ * @auth {Permission} - only for users with the post.delete.any permission
  * @param {id} - post's id
  */
-pub async fn delete_post(Path(id): Path<i32>, Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>) -> StatusCode {
+pub async fn delete_post(Path(id): Path<i32>, Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>, State(storage): State<Arc<dyn Storage>>) -> StatusCode {
 
-    let is_admin = get_is_admin(&pool, &auth_user).await;
+    // Return 401 if not connected, 403 without the post.delete.any permission (moderators and admins)
+    if let Err(status) = require_permission(&pool, &auth_user, POST_DELETE_ANY).await {
+        return status;
+    }
 
-    // If user is not admin return 401
-    if !is_admin {
-        return StatusCode::UNAUTHORIZED;
+    // If the API token doesn't have the posts:write scope, return 403
+    if !auth_user.has_scope(POSTS_WRITE) {
+        return StatusCode::FORBIDDEN;
+    }
+
+    // Start transaction
+    let mut tx = match pool.begin().await {
+        Ok(t) => t,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR,
+    };
+
+    // Files of the attached media, their rows are deleted with the post
+    let media_keys: Vec<(String, String)> = match sqlx::query_as("SELECT storage_key, thumbnail_key FROM media WHERE post_id = $1")
+        .bind(id)
+        .fetch_all(&mut *tx)
+        .await
+    {
+        Ok(keys) => keys,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR,
+    };
+
+    let query = sqlx::query("DELETE FROM posts WHERE id = $1 RETURNING parent_id, quoted_post_id;").bind(id);
+
+    let (parent_id, quoted_post_id): (Option<i32>, Option<i32>) = match query.fetch_optional(&mut *tx).await {
+        Ok(Some(row)) => (row.get("parent_id"), row.get("quoted_post_id")),
+        Ok(None) => return StatusCode::NOT_FOUND, // Return 404 if no post found
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR, // Return 500 if SQL error
+    };
+
+    // Update replies count of the post it replied to
+    if let Some(parent_id) = parent_id {
+
+        let update_result = sqlx::query("UPDATE posts SET replies_count = replies_count - 1 WHERE id = $1")
+            .bind(parent_id)
+            .execute(&mut *tx)
+            .await;
+
+        if update_result.is_err() {
+            return StatusCode::INTERNAL_SERVER_ERROR;
+        }
     }
 
-    let query = sqlx::query("DELETE FROM posts WHERE id = $1;").bind(id);
+    // Update quotes count of the post it quoted
+    if let Some(quoted_post_id) = quoted_post_id {
 
-    let result = query.execute(&pool).await;
+        let update_result = sqlx::query("UPDATE posts SET quotes_count = quotes_count - 1 WHERE id = $1")
+            .bind(quoted_post_id)
+            .execute(&mut *tx)
+            .await;
 
-    match result {
+        if update_result.is_err() {
+            return StatusCode::INTERNAL_SERVER_ERROR;
+        }
+    }
 
-        Ok(res) if res.rows_affected() > 0 => StatusCode::NO_CONTENT, // Return 204 if success
-        Ok(_) => StatusCode::NOT_FOUND, // Return 404 if no user found
-        Err(_) => StatusCode::INTERNAL_SERVER_ERROR, // Return 500 if SQL error
+    // Commit -> Apply all queries
+    if tx.commit().await.is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR;
     }
+
+    // Delete the files once the post is deleted
+    let keys: Vec<String> = media_keys.into_iter().flat_map(|(key, thumbnail_key)| [key, thumbnail_key]).collect();
+    delete_files(storage.as_ref(), &keys).await;
+
+    StatusCode::NO_CONTENT // Return 204 if success
 }
 
 /*
@@ -119,44 +434,624 @@ pub async fn create_post(Extension(auth_user): Extension<AuthUser>, State(pool):
         return Err(StatusCode::UNAUTHORIZED);
     }
 
+    // If the API token doesn't have the posts:write scope, return 403
+    if !auth_user.has_scope(POSTS_WRITE) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    // If the user must verify his email before posting, return 403
+    if !get_is_verified(&pool, &auth_user).await {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
     // Get user & form data
     let user_id = auth_user.user_id;
     let content = payload.content;
 
-    let query = sqlx::query_as::<_, PostWithUserData>("
-        WITH new_post AS (
-            INSERT INTO posts (user_id, content) 
-            VALUES ($1, $2) 
-            RETURNING id, user_id, content, created_at, likes_count
-        )
-        SELECT 
-            np.id, 
-            np.content, 
-            np.created_at, 
-            np.likes_count, 
-            u.id as user_id, 
-            u.username as user_username, 
-            u.title as user_title, 
-            u.created_at as user_created_at,
-            EXISTS (
-                SELECT 1 
-                FROM user_likes ul 
-                WHERE ul.user_id = $3 AND ul.post_id = np.id
-            ) AS auth_is_liked 
-        FROM new_post np 
-        JOIN users u ON np.user_id = u.id;
-    ")
-    .bind(user_id)
-    .bind(content)
-    .bind(user_id);
+    // Start transaction
+    let mut tx = pool.begin().await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let query = sqlx::query("INSERT INTO posts (user_id, content) VALUES ($1, $2) RETURNING id;")
+        .bind(user_id)
+        .bind(&content);
 
-    let post = query.fetch_one(&pool).await
+    let post_id: i32 = query.fetch_one(&mut *tx).await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .get("id");
+
+    save_entities(&mut tx, post_id, &content).await?;
+    attach_media(&mut tx, post_id, user_id, payload.media_ids.as_deref()).await?;
+
+    if let Some(poll_options) = &payload.poll_options {
+        save_poll(&mut tx, post_id, poll_options, payload.poll_duration_minutes, payload.poll_multiple_choice.unwrap_or(false)).await?;
+    }
+
+    let post = get_post(&mut *tx, post_id, user_id).await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    // Push the new post to the connected clients
+    let data = json!({ "post_id": post.id, "user_id": post.user_id, "parent_id": post.parent_id, "quoted_post_id": post.quoted_post_id });
+    publish_event(&mut tx, POST_EVENT, None, data).await?;
+
+    // Commit -> Apply all queries
+    tx.commit().await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     // Return the created post
     Ok(Json(post))
 }
 
+/*
+ * Reply to a post and returns the reply with the user linked data
+ * @auth {Conneceted} - only for conneceted users
+ * @param {id} - post's id you want to reply to
+ * @param {FormPost} - form input data
+ */
+pub async fn reply_post(Path(id): Path<i32>, Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>, Form(payload): Form<FormPost>) -> Result<Json<PostWithUserData>, StatusCode> {
+
+    // If the user is not connected, return 401
+    if !auth_user.is_connected {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    // If the API token doesn't have the posts:write scope, return 403
+    if !auth_user.has_scope(POSTS_WRITE) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    // If the user must verify his email before posting, return 403
+    if !get_is_verified(&pool, &auth_user).await {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    // Start transaction
+    let mut tx = pool.begin().await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    // Increment replies count of the parent, and get its conversation
+    let query = sqlx::query("
+        UPDATE posts SET replies_count = replies_count + 1
+        WHERE id = $1
+        RETURNING COALESCE(root_id, id) AS root_id, user_id;
+    ")
+    .bind(id);
+
+    let parent = query.fetch_optional(&mut *tx).await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?; // Return 404 if no post found
+
+    let root_id: i32 = parent.get("root_id");
+    let parent_author_id: i32 = parent.get("user_id");
+
+    // Return 403 if the author of the parent blocked the user (or was blocked by him)
+    if get_is_blocked(&mut *tx, auth_user.user_id, parent_author_id).await? {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let query = sqlx::query("INSERT INTO posts (user_id, content, parent_id, root_id) VALUES ($1, $2, $3, $4) RETURNING id;")
+        .bind(auth_user.user_id)
+        .bind(&payload.content)
+        .bind(id)
+        .bind(root_id);
+
+    let post_id: i32 = query.fetch_one(&mut *tx).await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .get("id");
+
+    save_entities(&mut tx, post_id, &payload.content).await?;
+    attach_media(&mut tx, post_id, auth_user.user_id, payload.media_ids.as_deref()).await?;
+
+    // Notify the author of the parent (grouped with the other replies to it)
+    add_notification(&mut tx, parent_author_id, auth_user.user_id, NotificationKind::Reply, Some(id)).await?;
+
+    let post = get_post(&mut *tx, post_id, auth_user.user_id).await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    // Push the new post to the connected clients
+    let data = json!({ "post_id": post.id, "user_id": post.user_id, "parent_id": post.parent_id, "quoted_post_id": post.quoted_post_id });
+    publish_event(&mut tx, POST_EVENT, None, data).await?;
+
+    // Commit -> Apply all queries
+    tx.commit().await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    // Return the created reply
+    Ok(Json(post))
+}
+
+/*
+ * Edit the content of a post, the previous version is kept in its revisions
+ * @auth {Conneceted} - only for the author, during POST_EDIT_MINUTES after the creation
+ * @param {id} - post's id you want to edit
+ * @param {FormPost} - form input data (the new content)
+ */
+pub async fn edit_post(Path(id): Path<i32>, Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>, Form(payload): Form<FormPost>) -> Result<Json<PostWithUserData>, StatusCode> {
+
+    // If the user is not connected, return 401
+    if !auth_user.is_connected {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    // If the API token doesn't have the posts:write scope, return 403
+    if !auth_user.has_scope(POSTS_WRITE) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    // If the user must verify his email before posting, return 403
+    if !get_is_verified(&pool, &auth_user).await {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let minutes = env::var("POST_EDIT_MINUTES").ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_POST_EDIT_MINUTES);
+
+    // Start transaction
+    let mut tx = pool.begin().await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    // Lock the post, so two edits can't save the same previous version
+    let query = sqlx::query("
+        SELECT
+            user_id,
+            content,
+            COALESCE(edited_at, created_at) AS version_at,
+            created_at + make_interval(mins => $2) > NOW() AS is_editable
+        FROM posts
+        WHERE id = $1
+        FOR UPDATE;
+    ")
+    .bind(id)
+    .bind(minutes as i32);
+
+    let row = query.fetch_optional(&mut *tx).await
+        .map_err(|e| {
+            eprintln!("Error editing post: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::NOT_FOUND)?; // Return 404 if no post found
+
+    let author_id: i32 = row.get("user_id");
+    let is_editable: bool = row.get("is_editable");
+
+    // Return 403 if it's not his post or if the edit window is over
+    if author_id != auth_user.user_id || !is_editable {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    // Keep the previous version
+    let query = sqlx::query("INSERT INTO post_revisions (post_id, content, created_at) VALUES ($1, $2, $3)")
+        .bind(id)
+        .bind(row.get::<String, _>("content"))
+        .bind(row.get::<chrono::NaiveDateTime, _>("version_at"));
+
+    query.execute(&mut *tx).await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    sqlx::query("UPDATE posts SET content = $2, edited_at = NOW() WHERE id = $1")
+        .bind(id)
+        .bind(&payload.content)
+        .execute(&mut *tx)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    // Entities of the new content replace the previous ones
+    save_entities(&mut tx, id, &payload.content).await?;
+
+    let post = get_post(&mut *tx, id, auth_user.user_id).await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    // Commit -> Apply all queries
+    tx.commit().await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    // Return the edited post
+    Ok(Json(post))
+}
+
+/*
+ * List the previous versions of a post, the most recent first
+ * @auth {None} - no authorization needed
+ * @param {id} - post's id
+ */
+pub async fn list_revisions(Path(id): Path<i32>, Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>) -> Result<Json<Vec<PostRevision>>, StatusCode> {
+
+    // If the API token doesn't have the posts:read scope, return 403
+    if auth_user.is_connected && !auth_user.has_scope(POSTS_READ) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    // Same as list, user -1 if not connected
+    let user_id = if auth_user.is_connected { auth_user.user_id } else { -1 };
+
+    // Return 404 if no post found (or its author is blocked)
+    let post = get_post(&pool, id, user_id).await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    if post.is_none() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let query = sqlx::query_as::<_, PostRevision>("
+        SELECT id, content, created_at, replaced_at
+        FROM post_revisions
+        WHERE post_id = $1
+        ORDER BY replaced_at DESC, id DESC;
+    ")
+    .bind(id);
+
+    let revisions = query.fetch_all(&pool).await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?; // Return 500 if SQL request failed
+
+    Ok(Json(revisions))
+}
+
+/*
+ * Quote a post: create a post embedding it, with a commentary
+ * @auth {Conneceted} - only for conneceted users
+ * @param {id} - post's id you want to quote
+ * @param {FormPost} - form input data (the commentary)
+ */
+pub async fn quote_post(Path(id): Path<i32>, Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>, Form(payload): Form<FormPost>) -> Result<Json<PostWithUserData>, StatusCode> {
+
+    // If the user is not connected, return 401
+    if !auth_user.is_connected {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    // If the API token doesn't have the posts:write scope, return 403
+    if !auth_user.has_scope(POSTS_WRITE) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    // If the user must verify his email before posting, return 403
+    if !get_is_verified(&pool, &auth_user).await {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    // Start transaction
+    let mut tx = pool.begin().await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    // Increment quotes count of the quoted post
+    let quoted_author_id: i32 = sqlx::query("UPDATE posts SET quotes_count = quotes_count + 1 WHERE id = $1 RETURNING user_id")
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)? // Return 404 if no post found
+        .get("user_id");
+
+    // Return 403 if the author of the quoted post blocked the user (or was blocked by him)
+    if get_is_blocked(&mut *tx, auth_user.user_id, quoted_author_id).await? {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let query = sqlx::query("INSERT INTO posts (user_id, content, quoted_post_id) VALUES ($1, $2, $3) RETURNING id;")
+        .bind(auth_user.user_id)
+        .bind(&payload.content)
+        .bind(id);
+
+    let post_id: i32 = query.fetch_one(&mut *tx).await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .get("id");
+
+    save_entities(&mut tx, post_id, &payload.content).await?;
+    attach_media(&mut tx, post_id, auth_user.user_id, payload.media_ids.as_deref()).await?;
+
+    // Notify the author of the quoted post (grouped with the other quotes of it)
+    add_notification(&mut tx, quoted_author_id, auth_user.user_id, NotificationKind::Quote, Some(id)).await?;
+
+    let post = get_post(&mut *tx, post_id, auth_user.user_id).await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    // Push the new post to the connected clients
+    let data = json!({ "post_id": post.id, "user_id": post.user_id, "parent_id": post.parent_id, "quoted_post_id": post.quoted_post_id });
+    publish_event(&mut tx, POST_EVENT, None, data).await?;
+
+    // Commit -> Apply all queries
+    tx.commit().await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    // Return the quote post
+    Ok(Json(post))
+}
+
+/*
+ * Get the conversation around a post
+ * - ancestors: the posts it replies to, from the first post of the conversation
+ * - replies: its direct replies (paginated, oldest first), with their replies up to THREAD_DEPTH levels
+ *   (replies_count tells if a reply has more replies than the ones returned)
+ * - Posts of the users blocked by the connected user (or blocking him) are left out, with their replies
+ * - Deleted posts that still have replies keep their place, as { id, parent_id, is_available: false }
+ * @auth {None} - no authorization needed
+ * @param {id} - post's id
+ */
+pub async fn get_thread(Path(id): Path<i32>, Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>, Query(pagination): Query<PaginationQuery>) -> Result<Json<PostThread>, StatusCode> {
+
+    // If the API token doesn't have the posts:read scope, return 403
+    if auth_user.is_connected && !auth_user.has_scope(POSTS_READ) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    // Same as list, user -1 if not connected
+    let user_id = if auth_user.is_connected { auth_user.user_id } else { -1 };
+
+    // Get values for pagination or else get default values
+    let limit = pagination.limit.unwrap_or(DEFAULT_LIMIT);
+    let offset = pagination.offset.unwrap_or(DEFAULT_OFFSET);
+
+    // The post itself
+    let post = get_post(&pool, id, user_id).await
+        .map_err(|e| {
+            eprintln!("Error fetching thread: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR // Return 500 if SQL error
+        })?
+        .ok_or(StatusCode::NOT_FOUND)?; // Return 404 if no post found
+
+    // Walk up the parents, the furthest one first (deleted ones included, to keep the chain)
+    let sql = format!("
+        WITH RECURSIVE ancestors AS (
+            SELECT parent_id AS id, 1 AS depth FROM posts WHERE id = $1 AND parent_id IS NOT NULL
+            UNION ALL
+            SELECT n.parent_id, a.depth + 1
+            FROM {THREAD_NODES} n
+            JOIN ancestors a ON n.id = a.id
+            WHERE n.parent_id IS NOT NULL
+        )
+        SELECT a.id, n.parent_id, COALESCE(n.is_deleted, TRUE) AS is_deleted
+        FROM ancestors a
+        LEFT JOIN {THREAD_NODES} n ON n.id = a.id
+        ORDER BY a.depth DESC;
+    ");
+
+    let query = sqlx::query_as::<_, (i32, Option<i32>, bool)>(&sql)
+        .bind(id);
+
+    let ancestor_nodes = query.fetch_all(&pool).await
+        .map_err(|e| {
+            eprintln!("Error fetching thread: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR // Return 500 if SQL error
+        })?;
+
+    let ancestors = get_thread_posts(&pool, user_id, ancestor_nodes).await
+        .map_err(|e| {
+            eprintln!("Error fetching thread: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR // Return 500 if SQL error
+        })?;
+
+    // Page of direct replies, then their replies level by level (deleted ones included, to keep their replies)
+    let sql = format!("
+        WITH RECURSIVE tree AS (
+            SELECT id, parent_id, is_deleted, created_at, 1 AS depth
+            FROM (
+                SELECT * FROM {THREAD_NODES} n
+                WHERE parent_id = $1
+                ORDER BY created_at ASC, id ASC
+                LIMIT $2
+                OFFSET $3
+            ) page
+            UNION ALL
+            SELECT n.id, n.parent_id, n.is_deleted, n.created_at, t.depth + 1
+            FROM {THREAD_NODES} n
+            JOIN tree t ON n.parent_id = t.id
+            WHERE t.depth < $4
+        )
+        SELECT id, parent_id, is_deleted
+        FROM tree
+        ORDER BY created_at ASC, id ASC;
+    ");
+
+    let query = sqlx::query_as::<_, (i32, Option<i32>, bool)>(&sql)
+        .bind(id)
+        .bind(limit)
+        .bind(offset)
+        .bind(THREAD_DEPTH);
+
+    let descendant_nodes = query.fetch_all(&pool).await
+        .map_err(|e| {
+            eprintln!("Error fetching thread: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR // Return 500 if SQL error
+        })?;
+
+    let descendants = get_thread_posts(&pool, user_id, descendant_nodes).await
+        .map_err(|e| {
+            eprintln!("Error fetching thread: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR // Return 500 if SQL error
+        })?;
+
+    let replies = build_thread_tree(id, descendants);
+
+    Ok(Json(PostThread { ancestors, post, replies }))
+}
+
+/*
+ * Get a post with the user linked data, None if it doesn't exist or if its author and the user blocked each other
+ * - This is not an hanlder, but an helper function
+ */
+pub(crate) async fn get_post<'e, E: sqlx::PgExecutor<'e>>(executor: E, id: i32, user_id: i32) -> Result<Option<PostWithUserData>, sqlx::Error> {
+
+    let blocked_author = is_blocked("$1", "p.user_id");
+
+    let sql = format!("
+        SELECT {POST_COLUMNS}
+        FROM posts p
+        {POST_JOINS}
+        WHERE p.id = $2 AND NOT {blocked_author};
+    ");
+
+    sqlx::query_as::<_, PostWithUserData>(&sql)
+        .bind(user_id)
+        .bind(id)
+        .fetch_optional(executor)
+        .await
+}
+
+/*
+ * Parse the entities of a post content and save them, replacing the previous ones
+ * - Mentions of users that don't exist are ignored, the others are notified
+ * - This is not an hanlder, but an helper function
+ */
+async fn save_entities(conn: &mut PgConnection, post_id: i32, content: &str) -> Result<(), StatusCode> {
+
+    for table in ["post_hashtags", "post_mentions", "post_urls"] {
+
+        sqlx::query(&format!("DELETE FROM {table} WHERE post_id = $1"))
+            .bind(post_id)
+            .execute(&mut *conn)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    }
+
+    for entity in parse_entities(content) {
+
+        let query = match entity.kind {
+
+            EntityKind::Hashtag => sqlx::query("
+                WITH tag AS (
+                    INSERT INTO hashtags (name) VALUES ($2)
+                    ON CONFLICT (name) DO UPDATE SET name = EXCLUDED.name
+                    RETURNING id
+                )
+                INSERT INTO post_hashtags (post_id, hashtag_id, start_index, end_index)
+                SELECT $1, id, $3, $4 FROM tag;
+            "),
+
+            // Same username first, otherwise case insensitive
+            EntityKind::Mention => sqlx::query("
+                INSERT INTO post_mentions (post_id, user_id, start_index, end_index)
+                SELECT $1, id, $3, $4 FROM users
+                WHERE LOWER(username) = LOWER($2)
+                ORDER BY username = $2 DESC
+                LIMIT 1;
+            "),
+
+            EntityKind::Url => sqlx::query("INSERT INTO post_urls (post_id, url, start_index, end_index) VALUES ($1, $2, $3, $4)"),
+        };
+
+        query
+            .bind(post_id)
+            .bind(&entity.value)
+            .bind(entity.start as i32)
+            .bind(entity.end as i32)
+            .execute(&mut *conn)
+            .await
+            .map_err(|e| {
+                eprintln!("Error saving post entities: {:?}", e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+    }
+
+    notify_mentions(conn, post_id).await
+}
+
+/*
+ * Attach uploaded media to a new post, in the order of the ids
+ * - media_ids: comma-separated ids returned by the upload (ex: "12,13"), at most MEDIA_MAX_PER_POST
+ * - Return 400 if an id is invalid, or is not a media of the user not attached yet
+ * - This is not an hanlder, but an helper function
+ */
+async fn attach_media(conn: &mut PgConnection, post_id: i32, user_id: i32, media_ids: Option<&str>) -> Result<(), StatusCode> {
+
+    let ids = parse_ids(media_ids.unwrap_or(""))?;
+
+    if ids.is_empty() {
+        return Ok(());
+    }
+
+    // Return 400 if too many media
+    if ids.len() > max_media_per_post() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let update_result = sqlx::query("
+        UPDATE media SET post_id = $1, position = array_position($3::INTEGER[], id) - 1
+        WHERE id = ANY($3) AND user_id = $2 AND post_id IS NULL
+    ")
+    .bind(post_id)
+    .bind(user_id)
+    .bind(&ids)
+    .execute(&mut *conn)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    if update_result.rows_affected() != ids.len() as u64 {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    Ok(())
+}
+
+/*
+ * Get the posts of the thread nodes (id, parent_id, is_deleted), keeping their order
+ * - Deleted posts are returned as unavailable, posts of the blocked users (or blocking him) are left out
+ * - This is not an hanlder, but an helper function
+ */
+async fn get_thread_posts(pool: &PgPool, user_id: i32, nodes: Vec<(i32, Option<i32>, bool)>) -> Result<Vec<ThreadPost>, sqlx::Error> {
+
+    let blocked_author = is_blocked("$1", "p.user_id");
+
+    let sql = format!("
+        SELECT {POST_COLUMNS}
+        FROM posts p
+        {POST_JOINS}
+        WHERE p.id = ANY($2) AND NOT {blocked_author};
+    ");
+
+    let ids: Vec<i32> = nodes.iter().filter(|(_, _, is_deleted)| !is_deleted).map(|(id, _, _)| *id).collect();
+
+    let mut posts: HashMap<i32, PostWithUserData> = sqlx::query_as::<_, PostWithUserData>(&sql)
+        .bind(user_id)
+        .bind(&ids)
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .map(|post| (post.id, post))
+        .collect();
+
+    Ok(nodes.into_iter()
+        .filter_map(|(id, parent_id, is_deleted)| match is_deleted {
+            true => Some(ThreadPost::Unavailable { id, parent_id, is_available: false }),
+            false => posts.remove(&id).map(|post| ThreadPost::Available(Box::new(post)))
+        })
+        .collect())
+}
+
+/*
+ * Nest the replies under their parent, keeping their order
+ * - Deleted replies are only kept if some of their replies are left
+ * - This is not an hanlder, but an helper function
+ */
+fn build_thread_tree(root_id: i32, posts: Vec<ThreadPost>) -> Vec<ThreadNode> {
+
+    let mut children: HashMap<i32, Vec<ThreadPost>> = HashMap::new();
+
+    for post in posts {
+        if let Some(parent_id) = post.parent_id() {
+            children.entry(parent_id).or_default().push(post);
+        }
+    }
+
+    fn attach(parent_id: i32, children: &mut HashMap<i32, Vec<ThreadPost>>) -> Vec<ThreadNode> {
+
+        children.remove(&parent_id).unwrap_or_default()
+            .into_iter()
+            .map(|post| {
+                let replies = attach(post.id(), children);
+                ThreadNode { post, replies }
+            })
+            .filter(|node| matches!(node.post, ThreadPost::Available(_)) || !node.replies.is_empty())
+            .collect()
+    }
+
+    attach(root_id, &mut children)
+}
+
 /*
  * Like a post
  * @auth {Conneceted} - only for conneceted users
@@ -169,6 +1064,11 @@ pub async fn like_post(Path(id): Path<i32>, Extension(auth_user): Extension<Auth
         return StatusCode::UNAUTHORIZED;
     }
 
+    // If the API token doesn't have the posts:write scope, return 403
+    if !auth_user.has_scope(POSTS_WRITE) {
+        return StatusCode::FORBIDDEN;
+    }
+
     // Start transaction
     let mut tx = match pool.begin().await {
         Ok(t) => t,
@@ -188,13 +1088,31 @@ pub async fn like_post(Path(id): Path<i32>, Extension(auth_user): Extension<Auth
     }
 
     // Update like count
-    let update_result = sqlx::query("UPDATE posts SET likes_count = likes_count + 1 WHERE id = $1")
+    let update_result = sqlx::query("UPDATE posts SET likes_count = likes_count + 1 WHERE id = $1 RETURNING user_id, likes_count")
         .bind(id)
-        .execute(&mut *tx)
+        .fetch_one(&mut *tx)
         .await;
 
-    if update_result.is_err() {
-        return StatusCode::INTERNAL_SERVER_ERROR;
+    let (author_id, likes_count): (i32, i32) = match update_result {
+        Ok(row) => (row.get("user_id"), row.get("likes_count")),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR,
+    };
+
+    // Return 403 if the author blocked the user (or was blocked by him), the transaction is rolled back
+    match get_is_blocked(&mut *tx, auth_user.user_id, author_id).await {
+        Ok(false) => {},
+        Ok(true) => return StatusCode::FORBIDDEN,
+        Err(status) => return status,
+    }
+
+    // Push the new count to the connected clients
+    if let Err(status) = publish_event(&mut tx, LIKES_EVENT, None, json!({ "post_id": id, "likes_count": likes_count })).await {
+        return status;
+    }
+
+    // Notify the author of the post
+    if let Err(status) = add_notification(&mut tx, author_id, auth_user.user_id, NotificationKind::Like, Some(id)).await {
+        return status;
     }
 
     // Commit -> Apply both queries
@@ -217,6 +1135,11 @@ pub async fn unlike_post(Path(id): Path<i32>, Extension(auth_user): Extension<Au
         return StatusCode::UNAUTHORIZED;
     }
 
+    // If the API token doesn't have the posts:write scope, return 403
+    if !auth_user.has_scope(POSTS_WRITE) {
+        return StatusCode::FORBIDDEN;
+    }
+
     // Start transaction
     let mut tx = match pool.begin().await {
         Ok(t) => t,
@@ -235,15 +1158,164 @@ pub async fn unlike_post(Path(id): Path<i32>, Extension(auth_user): Extension<Au
     }
 
     // Update like count
-    let update_result = sqlx::query("UPDATE posts SET likes_count = likes_count - 1 WHERE id = $1")
+    let update_result = sqlx::query("UPDATE posts SET likes_count = likes_count - 1 WHERE id = $1 RETURNING user_id, likes_count")
         .bind(id)
-        .execute(&mut *tx)
+        .fetch_one(&mut *tx)
+        .await;
+
+    let (author_id, likes_count): (i32, i32) = match update_result {
+        Ok(row) => (row.get("user_id"), row.get("likes_count")),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR,
+    };
+
+    // Push the new count to the connected clients
+    if let Err(status) = publish_event(&mut tx, LIKES_EVENT, None, json!({ "post_id": id, "likes_count": likes_count })).await {
+        return status;
+    }
+
+    // Remove the notification of the author of the post
+    if let Err(status) = remove_notification(&mut tx, author_id, auth_user.user_id, NotificationKind::Like, Some(id)).await {
+        return status;
+    }
+
+    // Commit -> Apply both queries
+    if tx.commit().await.is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    }
+
+    StatusCode::OK
+}
+
+/*
+ * Repost a post, it's then shown in the feeds as reposted by the user
+ * @auth {Conneceted} - only for conneceted users
+ * @param {id} - post's id you want to repost
+ */
+pub async fn repost_post(Path(id): Path<i32>, Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>) -> StatusCode {
+
+    // Return 401 if user not connected
+    if !auth_user.is_connected {
+        return StatusCode::UNAUTHORIZED;
+    }
+
+    // If the API token doesn't have the posts:write scope, return 403
+    if !auth_user.has_scope(POSTS_WRITE) {
+        return StatusCode::FORBIDDEN;
+    }
+
+    // Start transaction
+    let mut tx = match pool.begin().await {
+        Ok(t) => t,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR,
+    };
+
+    // Insert repost, only if the post exists
+    let insert_result = sqlx::query("
+        INSERT INTO reposts (user_id, post_id)
+        SELECT $1, id FROM posts WHERE id = $2
+        ON CONFLICT DO NOTHING;
+    ")
+    .bind(auth_user.user_id)
+    .bind(id)
+    .execute(&mut *tx)
+    .await;
+
+    match insert_result {
+        Ok(res) if res.rows_affected() > 0 => {},
+        Ok(_) => {
+            // Already reposted (409) or no post found (404)
+            return match get_post_exists(&pool, id).await {
+                Ok(true) => StatusCode::CONFLICT,
+                Ok(false) => StatusCode::NOT_FOUND,
+                Err(status) => status,
+            };
+        }
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR,
+    }
+
+    // Update repost count
+    let update_result = sqlx::query("UPDATE posts SET reposts_count = reposts_count + 1 WHERE id = $1 RETURNING user_id")
+        .bind(id)
+        .fetch_one(&mut *tx)
         .await;
 
-    if update_result.is_err() {
+    let author_id: i32 = match update_result {
+        Ok(row) => row.get("user_id"),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR,
+    };
+
+    // Return 403 if the author blocked the user (or was blocked by him), the transaction is rolled back
+    match get_is_blocked(&mut *tx, auth_user.user_id, author_id).await {
+        Ok(false) => {},
+        Ok(true) => return StatusCode::FORBIDDEN,
+        Err(status) => return status,
+    }
+
+    // Notify the author of the post
+    if let Err(status) = add_notification(&mut tx, author_id, auth_user.user_id, NotificationKind::Repost, Some(id)).await {
+        return status;
+    }
+
+    // Commit -> Apply both queries
+    if tx.commit().await.is_err() {
         return StatusCode::INTERNAL_SERVER_ERROR;
     }
 
+    StatusCode::CREATED
+}
+
+/*
+ * Undo a repost
+ * @auth {Conneceted} - only for conneceted users
+ * @param {id} - post's id you want to unrepost
+ */
+pub async fn unrepost_post(Path(id): Path<i32>, Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>) -> StatusCode {
+
+    // Return 401 if user not connected
+    if !auth_user.is_connected {
+        return StatusCode::UNAUTHORIZED;
+    }
+
+    // If the API token doesn't have the posts:write scope, return 403
+    if !auth_user.has_scope(POSTS_WRITE) {
+        return StatusCode::FORBIDDEN;
+    }
+
+    // Start transaction
+    let mut tx = match pool.begin().await {
+        Ok(t) => t,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR,
+    };
+
+    // Delete repost
+    let delete_result = sqlx::query("DELETE FROM reposts WHERE user_id = $1 AND post_id = $2")
+        .bind(auth_user.user_id)
+        .bind(id)
+        .execute(&mut *tx)
+        .await;
+
+    match delete_result {
+        Ok(res) if res.rows_affected() > 0 => {},
+        Ok(_) => return StatusCode::NOT_FOUND, // 404 if the post was not reposted
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR,
+    }
+
+    // Update repost count
+    let update_result = sqlx::query("UPDATE posts SET reposts_count = reposts_count - 1 WHERE id = $1 RETURNING user_id")
+        .bind(id)
+        .fetch_one(&mut *tx)
+        .await;
+
+    let author_id: i32 = match update_result {
+        Ok(row) => row.get("user_id"),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR,
+    };
+
+    // Remove the notification of the author of the post
+    if let Err(status) = remove_notification(&mut tx, author_id, auth_user.user_id, NotificationKind::Repost, Some(id)).await {
+        return status;
+    }
+
     // Commit -> Apply both queries
     if tx.commit().await.is_err() {
         return StatusCode::INTERNAL_SERVER_ERROR;
@@ -251,3 +1323,18 @@ pub async fn unlike_post(Path(id): Path<i32>, Extension(auth_user): Extension<Au
 
     StatusCode::OK
 }
+
+/*
+ * This function is used to know if a post exists
+ * - This is not an hanlder, but an helper function
+ */
+pub(crate) async fn get_post_exists(pool: &PgPool, post_id: i32) -> Result<bool, StatusCode> {
+
+    let query = sqlx::query("SELECT 1 FROM posts WHERE id = $1")
+        .bind(post_id);
+
+    let row = query.fetch_optional(pool).await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(row.is_some())
+}
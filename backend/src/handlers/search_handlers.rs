@@ -0,0 +1,152 @@
+use axum::{extract::{State, Extension, Query}, Json, http::StatusCode};
+use sqlx::PgPool;
+
+use crate::models::search::{SearchParams, SearchPost, SearchUser, SearchResults};
+use crate::models::auth::AuthUser;
+use crate::auth::scopes::{POSTS_READ, USERS_READ};
+use crate::search::parse_search_query;
+use crate::handlers::{DEFAULT_LIMIT, DEFAULT_OFFSET};
+use crate::handlers::post_handlers::{POST_COLUMNS, POST_JOINS};
+use crate::handlers::block_handlers::{is_blocked, is_hidden};
+use crate::handlers::filter_handlers::{SEARCH_CONTEXT, NOT_FILTERED, FILTERED_COLUMN, active_filters};
+
+// Minimum trigram similarity of the users found, set for the % operator (pg_trgm default is 0.3)
+const USER_SIMILARITY_THRESHOLD: f32 = 0.2;
+
+/*
+ * Search posts and users
+ * - Posts: full text search (english & french), best rank first, with a highlighted snippet
+ * - Users: username or title similar to the text (trigrams), only when there is a text
+ * - Filters (posts only): from:<username>, since:<YYYY-MM-DD>, has:media, has:links
+ * - Users blocked by the connected user (or blocking him) are not found, nor their posts, the posts of muted users are hidden too
+ * - Posts matching his content filters (search context) are hidden, or marked with the filters
+ * @auth {None} - no authorization needed
+ * @param {q} - the search query
+ */
+pub async fn search(Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>, Query(params): Query<SearchParams>) -> Result<Json<SearchResults>, StatusCode> {
+
+    // If the API token doesn't have the posts:read & users:read scopes, return 403
+    if auth_user.is_connected && !(auth_user.has_scope(POSTS_READ) && auth_user.has_scope(USERS_READ)) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    // Return 400 if a filter is invalid
+    let search = parse_search_query(&params.q)
+        .map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    // Return 400 if there is nothing to search
+    if search.text.is_empty() && !search.has_filters() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    // Same as list, user -1 if not connected
+    let user_id = if auth_user.is_connected { auth_user.user_id } else { -1 };
+
+    let limit = params.limit.unwrap_or(DEFAULT_LIMIT);
+    let offset = params.offset.unwrap_or(DEFAULT_OFFSET);
+
+    let hidden_author = is_hidden("$1", "p.user_id");
+    let filters = active_filters(SEARCH_CONTEXT);
+
+    // Without text, only the filters are applied and the most recent posts come first
+    let sql = format!("
+        WITH languages AS (
+            SELECT CASE WHEN $2 = '' THEN NULL ELSE websearch_to_tsquery('english', $2) END AS english_query,
+                CASE WHEN $2 = '' THEN NULL ELSE websearch_to_tsquery('french', $2) END AS french_query
+        ),
+        search AS (
+            SELECT english_query, french_query, english_query || french_query AS query FROM languages
+        ),
+        {filters}
+        SELECT {POST_COLUMNS}, {FILTERED_COLUMN},
+            COALESCE(ts_rank_cd(p.search_vector, s.query), 0)::FLOAT4 AS rank,
+            -- Highlighted with the language that matched (the words are stemmed differently)
+            CASE WHEN s.query IS NULL THEN p.content
+                WHEN to_tsvector('english', p.content) @@ s.english_query
+                    THEN ts_headline('english', p.content, s.english_query, 'StartSel=<mark>, StopSel=</mark>, MaxFragments=2')
+                ELSE ts_headline('french', p.content, s.french_query, 'StartSel=<mark>, StopSel=</mark>, MaxFragments=2')
+            END AS snippet
+        FROM posts p
+        {POST_JOINS}
+        CROSS JOIN search s
+        WHERE (s.query IS NULL OR p.search_vector @@ s.query)
+            AND NOT {hidden_author}
+            AND {NOT_FILTERED}
+            AND ($3::TEXT IS NULL OR LOWER(u.username) = LOWER($3))
+            AND ($4::TIMESTAMP IS NULL OR p.created_at >= $4)
+            AND (NOT $5 OR EXISTS (SELECT 1 FROM media m WHERE m.post_id = p.id))
+            AND (NOT $6 OR EXISTS (SELECT 1 FROM post_urls pu WHERE pu.post_id = p.id))
+        ORDER BY rank DESC, p.created_at DESC, p.id DESC
+        LIMIT $7
+        OFFSET $8;
+    ");
+
+    let query = sqlx::query_as::<_, SearchPost>(&sql)
+        .bind(user_id)
+        .bind(&search.text)
+        .bind(&search.from)
+        .bind(search.since)
+        .bind(search.has_media)
+        .bind(search.has_links)
+        .bind(limit)
+        .bind(offset);
+
+    let posts = query.fetch_all(&pool).await
+        .map_err(|e| {
+            eprintln!("Error searching posts: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR // Return 500 if SQL error
+        })?;
+
+    // Filters are for posts, there is no user to search without text
+    let users = if search.text.is_empty() {
+        Vec::new()
+    } else {
+
+        // Start transaction, the similarity threshold of the % operator is only changed inside it
+        let mut tx = pool.begin().await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        sqlx::query("SELECT set_config('pg_trgm.similarity_threshold', $1, true);")
+            .bind(USER_SIMILARITY_THRESHOLD.to_string())
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| {
+                eprintln!("Error searching users: {:?}", e);
+                StatusCode::INTERNAL_SERVER_ERROR // Return 500 if SQL error
+            })?;
+
+        let sql = format!("
+            SELECT id, username, title, created_at, followers_count, similarity
+            FROM (
+                SELECT *, GREATEST(similarity(username, $1), similarity(COALESCE(title, ''), $1)) AS similarity
+                FROM users
+                WHERE username % $1 OR title % $1 OR username ILIKE $2
+            ) u
+            WHERE NOT {}
+            ORDER BY similarity DESC, followers_count DESC, id ASC
+            LIMIT $3
+            OFFSET $4;
+        ", is_blocked("$5", "u.id"));
+
+        let query = sqlx::query_as::<_, SearchUser>(&sql)
+        .bind(&search.text)
+        .bind(format!("{}%", search.text.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_"))) // usernames starting with the text
+        .bind(limit)
+        .bind(offset)
+        .bind(user_id);
+
+        let users = query.fetch_all(&mut *tx).await
+            .map_err(|e| {
+                eprintln!("Error searching users: {:?}", e);
+                StatusCode::INTERNAL_SERVER_ERROR // Return 500 if SQL error
+            })?;
+
+        // Commit -> Apply all queries
+        tx.commit().await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        users
+    };
+
+    Ok(Json(SearchResults { posts, users }))
+}
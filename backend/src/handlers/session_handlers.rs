@@ -0,0 +1,176 @@
+use axum::{extract::{Path, Extension, State}, Json, http::StatusCode};
+use sqlx::{PgPool, Row};
+
+use crate::models::session::Session;
+use crate::models::auth::AuthUser;
+
+/*
+ * List the active sessions (devices) of the connected user
+ * @auth {Connected} - only for connected users
+ */
+pub async fn list_sessions(Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>) -> Result<Json<Vec<Session>>, StatusCode> {
+
+    // If user is not connected we return 401
+    if !auth_user.is_connected {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    // API tokens can't manage the sessions, return 403
+    if !auth_user.is_session() {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let query = sqlx::query_as::<_, Session>("
+        SELECT id, user_agent, ip, created_at, last_seen_at, id = $2 AS is_current
+        FROM sessions
+        WHERE user_id = $1 AND revoked_at IS NULL
+        ORDER BY last_seen_at DESC, id DESC;
+    ")
+    .bind(auth_user.user_id)
+    .bind(auth_user.session_id);
+
+    let sessions = query.fetch_all(&pool).await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?; // Return 500 if SQL request failed
+
+    Ok(Json(sessions))
+}
+
+/*
+ * Revoke one session of the connected user
+ * @auth {Connected} - users can only revoke there own sessions
+ * @param {id} - session id you want to revoke
+ */
+pub async fn revoke_session(Path(id): Path<i32>, Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>) -> StatusCode {
+
+    // If user is not connected we return 401
+    if !auth_user.is_connected {
+        return StatusCode::UNAUTHORIZED;
+    }
+
+    // API tokens can't manage the sessions, return 403
+    if !auth_user.is_session() {
+        return StatusCode::FORBIDDEN;
+    }
+
+    // Start transaction
+    let mut tx = match pool.begin().await {
+        Ok(t) => t,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR,
+    };
+
+    // Revoke the session, only if it belongs to the connected user
+    let revoke_result = sqlx::query("UPDATE sessions SET revoked_at = NOW() WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL")
+        .bind(id)
+        .bind(auth_user.user_id)
+        .execute(&mut *tx)
+        .await;
+
+    match revoke_result {
+        Ok(res) if res.rows_affected() > 0 => {},
+        Ok(_) => return StatusCode::NOT_FOUND, // 404 if no active session found
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR,
+    }
+
+    // The session's refresh tokens can't be used anymore
+    let tokens_result = sqlx::query("UPDATE refresh_tokens SET revoked_at = NOW() WHERE session_id = $1 AND revoked_at IS NULL")
+        .bind(id)
+        .execute(&mut *tx)
+        .await;
+
+    if tokens_result.is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    }
+
+    // Commit -> Apply both queries
+    if tx.commit().await.is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    }
+
+    StatusCode::NO_CONTENT
+}
+
+/*
+ * Revoke all sessions of the connected user except the current one
+ * @auth {Connected} - only for connected users
+ */
+pub async fn revoke_other_sessions(Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>) -> StatusCode {
+
+    // If user is not connected we return 401
+    if !auth_user.is_connected {
+        return StatusCode::UNAUTHORIZED;
+    }
+
+    // API tokens can't manage the sessions, return 403
+    if !auth_user.is_session() {
+        return StatusCode::FORBIDDEN;
+    }
+
+    let query = sqlx::query("
+        WITH revoked AS (
+            UPDATE sessions SET revoked_at = NOW()
+            WHERE user_id = $1 AND id <> $2 AND revoked_at IS NULL
+            RETURNING id
+        )
+        UPDATE refresh_tokens SET revoked_at = NOW()
+        WHERE revoked_at IS NULL AND session_id IN (SELECT id FROM revoked);
+    ")
+    .bind(auth_user.user_id)
+    .bind(auth_user.session_id);
+
+    match query.execute(&pool).await {
+
+        Ok(_) => StatusCode::NO_CONTENT, // 204
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR, // 500
+    }
+}
+
+/*
+ * Create a new session for an user and return its id
+ * - Returns 403 if the user is suspended
+ * - This is not an hanlder, but an helper function
+ */
+pub async fn create_session(pool: &PgPool, user_id: i32, user_agent: Option<&str>, ip: &str) -> Result<i32, StatusCode> {
+
+    let query = sqlx::query("
+        INSERT INTO sessions (user_id, user_agent, ip)
+        SELECT id, $2, $3 FROM users WHERE id = $1 AND suspended_at IS NULL
+        RETURNING id;
+    ")
+    .bind(user_id)
+    .bind(user_agent)
+    .bind(ip);
+
+    let row = query.fetch_optional(pool).await
+        .map_err(|e| {
+            eprintln!("Error creating session: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::FORBIDDEN)?;
+
+    Ok(row.get("id"))
+}
+
+/*
+ * Check that a session is still active and mark it as seen
+ * - This is not an hanlder, but an helper function used by the auth middleware
+ */
+pub async fn touch_session(pool: &PgPool, session_id: i32, user_id: i32) -> bool {
+
+    let query = sqlx::query("
+        UPDATE sessions SET last_seen_at = NOW()
+        WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL;
+    ")
+    .bind(session_id)
+    .bind(user_id);
+
+    match query.execute(pool).await {
+
+        Ok(res) => res.rows_affected() > 0,
+
+        // If there was an error, we consider the session as not valid
+        Err(e) => {
+            eprintln!("Error while checking session: {e}");
+            false
+        }
+    }
+}
@@ -0,0 +1,168 @@
+use std::collections::HashSet;
+use std::convert::Infallible;
+use std::time::Duration;
+use axum::{extract::{State, Extension, Query}, http::{StatusCode, HeaderMap}, response::sse::{Event, KeepAlive, Sse}};
+use futures_util::Stream;
+use sqlx::{PgPool, PgConnection};
+use crate::models::event::{StreamEvent, StreamQuery};
+use crate::models::auth::AuthUser;
+use crate::auth::scopes::POSTS_READ;
+use crate::auth::middleware::authenticate;
+use crate::handlers::block_handlers::get_hidden_user_ids;
+use crate::stream::{EventSender, POST_EVENT, RELATIONS_EVENT, RESUME_OVERLAP_SECONDS};
+
+// Most events sent again to a reconnecting client, after that it gets a "reset" event and must reload
+const MAX_RESUMED_EVENTS: i64 = 500;
+
+// How often an open stream checks its session or API token, it's closed once revoked (or the access token expired)
+const AUTH_CHECK_SECONDS: u64 = 60;
+
+/*
+ * Server-Sent Events of the connected user: new posts, likes counts, his notifications and messages
+ * - Each event has an id, a reconnecting client sends the last one (Last-Event-ID header, or ?last_event_id=)
+ *   to get the events it missed
+ * - Ids are not in commit order, so the events of the few seconds before the last one are sent again:
+ *   the client must ignore the ids it already got
+ * - New posts of the users blocked (in any direction) or muted by the user are not sent
+ * - The stream is closed when the token is not valid anymore, the client reconnects with a new one
+ * @auth {Conneceted} - only for conneceted users
+ */
+pub async fn stream(Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>, State(events): State<EventSender>, Query(query): Query<StreamQuery>, headers: HeaderMap) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, StatusCode> {
+
+    // Return 401 if user not connected
+    if !auth_user.is_connected {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    // If the API token doesn't have the posts:read scope, return 403
+    if !auth_user.has_scope(POSTS_READ) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    // Return 400 if the Last-Event-ID header is not an id
+    let last_event_id = match headers.get("last-event-id") {
+        Some(value) => Some(value.to_str().ok().and_then(|id| id.parse::<i64>().ok()).ok_or(StatusCode::BAD_REQUEST)?),
+        None => query.last_event_id,
+    };
+
+    // Subscribe before reading the missed events, so nothing is lost between both
+    let mut receiver = events.subscribe();
+    let user_id = auth_user.user_id;
+
+    let missed = match last_event_id {
+        Some(id) => {
+            sqlx::query_as::<_, StreamEvent>("
+                SELECT id, kind, user_id, data FROM events
+                WHERE (id > $1 OR (id < $1 AND created_at >= (SELECT created_at FROM events WHERE id = $1) - make_interval(secs => $4)))
+                    AND (user_id IS NULL OR user_id = $2)
+                ORDER BY id
+                LIMIT $3;
+            ")
+            .bind(id)
+            .bind(user_id)
+            .bind(MAX_RESUMED_EVENTS + 1)
+            .bind(RESUME_OVERLAP_SECONDS)
+            .fetch_all(&pool)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        }
+        None => Vec::new(),
+    };
+
+    // Reloaded when the user blocks or mutes someone (or is blocked)
+    let mut hidden_user_ids = get_hidden_user_ids(&pool, user_id).await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let stream = async_stream::stream! {
+
+        // Too many missed events, the client must reload instead
+        if missed.len() as i64 > MAX_RESUMED_EVENTS {
+            yield Ok(Event::default().event("reset").data("{}"));
+        } else {
+            for event in missed.iter().filter(|event| !is_hidden_post(event, &hidden_user_ids)) {
+                yield Ok(to_sse(event));
+            }
+        }
+
+        // Events received while reading the missed ones are sent once
+        let sent: HashSet<i64> = missed.iter().map(|event| event.id).collect();
+
+        let mut auth_check = tokio::time::interval(Duration::from_secs(AUTH_CHECK_SECONDS));
+        auth_check.tick().await; // the first tick is immediate, the token was just checked
+
+        loop {
+
+            let event = tokio::select! {
+
+                // Stops if the client is too slow (Lagged): it will resume with its last event id
+                result = receiver.recv() => match result {
+                    Ok(event) => event,
+                    Err(_) => break,
+                },
+
+                // Stops if the session or the API token was revoked
+                _ = auth_check.tick() => {
+                    if authenticate(&pool, &headers).await.user_id != user_id {
+                        break;
+                    }
+                    continue;
+                }
+            };
+
+            if event.user_id.is_some_and(|id| id != user_id) || sent.contains(&event.id) {
+                continue;
+            }
+
+            if event.kind == RELATIONS_EVENT {
+                if let Ok(user_ids) = get_hidden_user_ids(&pool, user_id).await {
+                    hidden_user_ids = user_ids;
+                }
+            }
+
+            if is_hidden_post(&event, &hidden_user_ids) {
+                continue;
+            }
+
+            yield Ok(to_sse(&event));
+        }
+    };
+
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+// A new post of an hidden user
+fn is_hidden_post(event: &StreamEvent, hidden_user_ids: &HashSet<i32>) -> bool {
+
+    event.kind == POST_EVENT && event.data.0.get("user_id")
+        .and_then(|id| id.as_i64())
+        .is_some_and(|id| hidden_user_ids.contains(&(id as i32)))
+}
+
+fn to_sse(event: &StreamEvent) -> Event {
+
+    Event::default()
+        .id(event.id.to_string())
+        .event(&event.kind)
+        .data(event.data.0.to_string())
+}
+
+/*
+ * Add an event for the clients of /stream, it's sent when the transaction commits
+ * - user_id: only this user gets it, None for everyone
+ * - This is not an hanlder, but an helper function
+ */
+pub async fn publish_event(conn: &mut PgConnection, kind: &str, user_id: Option<i32>, data: serde_json::Value) -> Result<(), StatusCode> {
+
+    sqlx::query("INSERT INTO events (kind, user_id, data) VALUES ($1, $2, $3)")
+        .bind(kind)
+        .bind(user_id)
+        .bind(sqlx::types::Json(data))
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| {
+            eprintln!("Error publishing event: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    Ok(())
+}
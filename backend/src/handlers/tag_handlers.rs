@@ -0,0 +1,31 @@
+use axum::{extract::{State, Query}, Json, http::StatusCode};
+use sqlx::PgPool;
+
+use crate::models::tag::Trend;
+use crate::handlers::{DEFAULT_LIMIT, PaginationQuery};
+
+/*
+ * List the trending hashtags, the fastest growing first
+ * - Read from the trending_hashtags view, recomputed in background (see trends::refresh_trends)
+ * @auth {None} - no authorization needed
+ */
+pub async fn list_trends(State(pool): State<PgPool>, Query(pagination): Query<PaginationQuery>) -> Result<Json<Vec<Trend>>, StatusCode> {
+
+    let limit = pagination.limit.unwrap_or(DEFAULT_LIMIT);
+
+    let query = sqlx::query_as::<_, Trend>("
+        SELECT name AS tag, recent_count, baseline_rate::FLOAT8 AS baseline_rate, score::FLOAT8 AS score
+        FROM trending_hashtags
+        ORDER BY score DESC, recent_count DESC, name ASC
+        LIMIT $1;
+    ")
+    .bind(limit);
+
+    let trends = query.fetch_all(&pool).await
+        .map_err(|e| {
+            eprintln!("Error fetching trends: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR // Return 500 if SQL error
+        })?;
+
+    Ok(Json(trends))
+}
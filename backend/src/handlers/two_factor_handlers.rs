@@ -0,0 +1,325 @@
+use std::net::SocketAddr;
+use axum::{Json, extract::{State, Extension, ConnectInfo}, http::{StatusCode, HeaderMap}};
+use sqlx::{PgPool, Row};
+
+use crate::models::auth::{AuthUser, TokenResponse, TwoFactorLoginRequest, TwoFactorCodeRequest, TwoFactorEnrollResponse, RecoveryCodesResponse};
+use crate::auth::token_handler::{verify_challenge_jwt, hash_token};
+use crate::auth::totp_handler::{generate_secret, get_otpauth_uri, verify_code, generate_recovery_codes};
+use crate::auth::login_guard::{LoginPolicy, ip_key, two_factor_key, ensure_not_locked, record_failure, clear_failures};
+use crate::handlers::auth_handlers::start_session;
+
+/*
+ * Start enabling 2FA : create a new secret for the connected user
+ * - 2FA is only enabled once a first code is sent to /auth/2fa/confirm
+ * @auth {Connected} - only for connected users
+ */
+pub async fn enroll(Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>) -> Result<Json<TwoFactorEnrollResponse>, StatusCode> {
+
+    // If user is not connected we return 401
+    if !auth_user.is_connected {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    // API tokens can't manage 2FA, return 403
+    if !auth_user.is_session() {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    // If 2FA is already enabled return 409, it must be disabled first
+    if get_has_two_factor(&pool, auth_user.user_id).await? {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    let secret = generate_secret();
+
+    // Replace any previous unconfirmed secret
+    let query = sqlx::query("
+        WITH enrolled AS (
+            INSERT INTO user_totp (user_id, secret) VALUES ($1, $2)
+            ON CONFLICT (user_id) DO UPDATE SET secret = $2, created_at = NOW(), confirmed_at = NULL, last_used_step = NULL
+        )
+        SELECT username FROM users WHERE id = $1;
+    ")
+    .bind(auth_user.user_id)
+    .bind(&secret);
+
+    let row = query.fetch_one(&pool).await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let username: String = row.get("username");
+
+    let otpauth_uri = get_otpauth_uri(&secret, &username)
+        .ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Json(TwoFactorEnrollResponse { secret, otpauth_uri }))
+}
+
+/*
+ * Finish enabling 2FA with a first code, and get the recovery codes
+ * @auth {Connected} - only for connected users
+ * @param {TwoFactorCodeRequest} - a code from the authenticator app
+ */
+pub async fn confirm(Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>, Json(payload): Json<TwoFactorCodeRequest>) -> Result<Json<RecoveryCodesResponse>, StatusCode> {
+
+    // If user is not connected we return 401
+    if !auth_user.is_connected {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    // API tokens can't manage 2FA, return 403
+    if !auth_user.is_session() {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let query = sqlx::query("SELECT secret, confirmed_at IS NOT NULL AS is_confirmed FROM user_totp WHERE user_id = $1")
+        .bind(auth_user.user_id);
+
+    let row = query.fetch_optional(&pool).await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?; // 404 if enroll was not called
+
+    let secret: String = row.get("secret");
+    let is_confirmed: bool = row.get("is_confirmed");
+
+    // Already enabled, return 409
+    if is_confirmed {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    let step = verify_code(&secret, payload.code.trim(), None)
+        .ok_or(StatusCode::UNAUTHORIZED)?; // 401 if wrong code
+
+    let recovery_codes = generate_recovery_codes();
+
+    // Start transaction
+    let mut tx = pool.begin().await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    // Enable 2FA
+    sqlx::query("UPDATE user_totp SET confirmed_at = NOW(), last_used_step = $2 WHERE user_id = $1")
+        .bind(auth_user.user_id)
+        .bind(step)
+        .execute(&mut *tx)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    // Replace the recovery codes
+    sqlx::query("DELETE FROM recovery_codes WHERE user_id = $1")
+        .bind(auth_user.user_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let code_hashes: Vec<String> = recovery_codes.iter().map(|code| hash_token(code)).collect();
+
+    sqlx::query("INSERT INTO recovery_codes (user_id, code_hash) SELECT $1, UNNEST($2::VARCHAR[])")
+        .bind(auth_user.user_id)
+        .bind(&code_hashes)
+        .execute(&mut *tx)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    // Commit -> Apply all queries
+    tx.commit().await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Json(RecoveryCodesResponse { recovery_codes }))
+}
+
+/*
+ * Disable 2FA, a valid code (or recovery code) is needed
+ * @auth {Connected} - only for connected users
+ * @param {TwoFactorCodeRequest} - a code from the authenticator app or a recovery code
+ */
+pub async fn disable(Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>, Json(payload): Json<TwoFactorCodeRequest>) -> StatusCode {
+
+    // If user is not connected we return 401
+    if !auth_user.is_connected {
+        return StatusCode::UNAUTHORIZED;
+    }
+
+    // API tokens can't manage 2FA, return 403
+    if !auth_user.is_session() {
+        return StatusCode::FORBIDDEN;
+    }
+
+    let policy = LoginPolicy::from_env();
+    let account = two_factor_key(auth_user.user_id);
+
+    // Return 429 if there was too many wrong codes (same lock as the login)
+    if let Err(status) = ensure_not_locked(&pool, std::slice::from_ref(&account)).await {
+        return status;
+    }
+
+    match check_second_factor(&pool, auth_user.user_id, &payload.code).await {
+        Ok(true) => {},
+        Ok(false) => {
+
+            if let Err(status) = record_failure(&pool, &policy, &account, policy.max_failures).await {
+                return status;
+            }
+
+            return StatusCode::UNAUTHORIZED; // 401 if wrong code or 2FA not enabled
+        },
+        Err(status) => return status,
+    }
+
+    if let Err(status) = clear_failures(&pool, &account).await {
+        return status;
+    }
+
+    let query = sqlx::query("
+        WITH deleted AS (DELETE FROM recovery_codes WHERE user_id = $1)
+        DELETE FROM user_totp WHERE user_id = $1;
+    ")
+    .bind(auth_user.user_id);
+
+    match query.execute(&pool).await {
+
+        Ok(_) => StatusCode::NO_CONTENT, // 204
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR, // 500
+    }
+}
+
+/*
+ * Second step of the login when 2FA is enabled
+ * @auth {None} - the challenge token from /auth/login is the authorization
+ * @param {TwoFactorLoginRequest} - the challenge token and a code (or a recovery code)
+ */
+pub async fn login_2fa(State(pool): State<PgPool>, ConnectInfo(addr): ConnectInfo<SocketAddr>, headers: HeaderMap, Json(payload): Json<TwoFactorLoginRequest>) -> Result<Json<TokenResponse>, StatusCode> {
+
+    // Return 401 if the challenge is not valid or expired
+    let claims = verify_challenge_jwt(&payload.challenge_token)
+        .map_err(|_| StatusCode::UNAUTHORIZED)?;
+
+    // Return 401 if the challenge was already exchanged for a session
+    if get_is_challenge_used(&pool, &claims.jti).await? {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    let policy = LoginPolicy::from_env();
+    let account = two_factor_key(claims.sub);
+    let ip = ip_key(&addr.ip().to_string());
+
+    // Return 429 if there was too many wrong codes
+    ensure_not_locked(&pool, &[account.clone(), ip.clone()]).await?;
+
+    if !check_second_factor(&pool, claims.sub, &payload.code).await? {
+
+        record_failure(&pool, &policy, &account, policy.max_failures).await?;
+        record_failure(&pool, &policy, &ip, policy.ip_max_failures).await?;
+
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    clear_failures(&pool, &account).await?;
+
+    // Use the challenge, return 401 if another request used it in the meantime
+    if !use_challenge(&pool, &claims.jti, claims.exp).await? {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    let tokens = start_session(&pool, claims.sub, &addr, &headers).await?;
+
+    Ok(Json(tokens))
+}
+
+/*
+ * This function is used to know if an user enabled 2FA
+ * - This is not an hanlder, but an helper function
+ */
+pub async fn get_has_two_factor(pool: &PgPool, user_id: i32) -> Result<bool, StatusCode> {
+
+    let query = sqlx::query("SELECT 1 FROM user_totp WHERE user_id = $1 AND confirmed_at IS NOT NULL")
+        .bind(user_id);
+
+    let row = query.fetch_optional(pool).await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(row.is_some())
+}
+
+/*
+ * This function is used to know if a 2FA challenge token was already used
+ * - This is not an hanlder, but an helper function
+ */
+async fn get_is_challenge_used(pool: &PgPool, jti: &str) -> Result<bool, StatusCode> {
+
+    let query = sqlx::query("SELECT 1 FROM used_challenges WHERE jti = $1")
+        .bind(jti);
+
+    let row = query.fetch_optional(pool).await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(row.is_some())
+}
+
+/*
+ * Mark a 2FA challenge token as used, returns false if it already was
+ * - The rows of the expired tokens are deleted at the same time
+ * - This is not an hanlder, but an helper function
+ */
+async fn use_challenge(pool: &PgPool, jti: &str, exp: usize) -> Result<bool, StatusCode> {
+
+    let query = sqlx::query("
+        WITH expired AS (DELETE FROM used_challenges WHERE expires_at < NOW())
+        INSERT INTO used_challenges (jti, expires_at) VALUES ($1, to_timestamp($2))
+        ON CONFLICT (jti) DO NOTHING;
+    ")
+    .bind(jti)
+    .bind(exp as f64);
+
+    let result = query.execute(pool).await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/*
+ * Check a TOTP code or consume a recovery code of the user
+ * - Returns false if the user didn't enable 2FA
+ * - This is not an hanlder, but an helper function
+ */
+async fn check_second_factor(pool: &PgPool, user_id: i32, code: &str) -> Result<bool, StatusCode> {
+
+    let query = sqlx::query("SELECT secret, last_used_step FROM user_totp WHERE user_id = $1 AND confirmed_at IS NOT NULL")
+        .bind(user_id);
+
+    let row = match query.fetch_optional(pool).await {
+        Ok(Some(row)) => row,
+        Ok(None) => return Ok(false),
+        Err(_) => return Err(StatusCode::INTERNAL_SERVER_ERROR),
+    };
+
+    let secret: String = row.get("secret");
+    let last_used_step: Option<i64> = row.get("last_used_step");
+
+    let code = code.trim().to_lowercase();
+
+    // TOTP code, save its step so it can't be used again
+    if let Some(step) = verify_code(&secret, &code, last_used_step) {
+
+        let query = sqlx::query("
+            UPDATE user_totp SET last_used_step = $2
+            WHERE user_id = $1 AND (last_used_step IS NULL OR last_used_step < $2);
+        ")
+        .bind(user_id)
+        .bind(step);
+
+        let result = query.execute(pool).await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        // Another request used the same code at the same time
+        return Ok(result.rows_affected() > 0);
+    }
+
+    // Recovery code, each one can only be used once
+    let query = sqlx::query("UPDATE recovery_codes SET used_at = NOW() WHERE user_id = $1 AND code_hash = $2 AND used_at IS NULL")
+        .bind(user_id)
+        .bind(hash_token(&code));
+
+    let result = query.execute(pool).await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(result.rows_affected() > 0)
+}
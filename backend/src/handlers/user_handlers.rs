@@ -1,36 +1,44 @@
+use std::sync::Arc;
 use axum::{extract::{Path, Extension, State, Form, Query}, Json, http::StatusCode};
 use argon2::{Argon2, PasswordHasher, password_hash::SaltString};
 use rand::rngs::OsRng;
-use sqlx::PgPool;
+use sqlx::{PgPool, Row};
 
-use crate::models::user::{User, FormCreateUser, FormUpdateUser};
+use crate::models::user::{User, FormCreateUser, FormUpdateUser, UserSettings, UpdateSettingsRequest};
 use crate::models::auth::AuthUser;
-use crate::handlers::{DEFAULT_LIMIT, DEFAULT_OFFSET, PaginationQuery, auth_handlers::get_is_admin};
+use crate::auth::scopes::{USERS_READ, USERS_WRITE};
+use crate::auth::permissions::{USER_LIST, USER_DELETE, USER_SUSPEND, USER_UPDATE_ANY, USER_ROLE_ASSIGN, DEFAULT_ROLE, require_permission, get_has_permission, get_role_id};
+use crate::handlers::{DEFAULT_LIMIT, DEFAULT_OFFSET, PaginationQuery};
+use crate::handlers::email_handlers::{send_verification_mail, create_verification_mail};
+use crate::mail::{Mailer, send_in_background};
 
 /*
  * List all users data from database
- * @auth {Admin} - only for admin users
+ * @auth {Permission} - only for users with the user.list permission
  */
 pub async fn list(Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>, Query(pagination): Query<PaginationQuery>) -> Result<Json<Vec<User>>, StatusCode> {
 
-    let is_admin = get_is_admin(&pool, &auth_user).await;
+    // Return 401 if not connected, 403 without the user.list permission
+    require_permission(&pool, &auth_user, USER_LIST).await?;
 
-    // If user is not admin we return 401
-    if !is_admin {
-        return Err(StatusCode::UNAUTHORIZED);
+    // If the API token doesn't have the users:read scope, return 403
+    if !auth_user.has_scope(USERS_READ) {
+        return Err(StatusCode::FORBIDDEN);
     }
 
     let limit = pagination.limit.unwrap_or(DEFAULT_LIMIT); 
     let offset = pagination.offset.unwrap_or(DEFAULT_OFFSET);
 
     let query = sqlx::query_as::<_, User>("
-        SELECT id, username, email, title, created_at, is_admin FROM users
-        ORDER BY created_at DESC, id DESC
+        SELECT u.id, u.username, u.email, u.title, u.created_at, r.name AS role, r.name = 'admin' AS is_admin, u.email_verified_at, u.suspended_at, u.followers_count, u.following_count
+        FROM users u
+        JOIN roles r ON r.id = u.role_id
+        ORDER BY u.created_at DESC, u.id DESC
         LIMIT $1
         OFFSET $2;
     ")
-    .bind(&limit)
-    .bind(&offset);
+    .bind(limit)
+    .bind(offset);
 
     let users = query.fetch_all(&pool).await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?; // Return 500 if SQL request failed
@@ -40,19 +48,26 @@ pub async fn list(Extension(auth_user): Extension<AuthUser>, State(pool): State<
 
 /*
  * List a specific user data from database
- * @auth {Admin} - only for admin users
+ * @auth {Permission} - only for users with the user.list permission
  * @param {id} - target user id
  */
 pub async fn get_by_id(Path(id): Path<i32>, Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>) -> Result<Json<User>, StatusCode> {
 
-    let is_admin = get_is_admin(&pool, &auth_user).await;
+    // Return 401 if not connected, 403 without the user.list permission
+    require_permission(&pool, &auth_user, USER_LIST).await?;
 
-    // If user is not admin we return 401
-    if !is_admin {
-        return Err(StatusCode::UNAUTHORIZED);
+    // If the API token doesn't have the users:read scope, return 403
+    if !auth_user.has_scope(USERS_READ) {
+        return Err(StatusCode::FORBIDDEN);
     }
 
-    let query = sqlx::query_as::<_, User>("SELECT id, username, email, title, created_at, is_admin FROM users WHERE id = $1").bind(id);
+    let query = sqlx::query_as::<_, User>("
+        SELECT u.id, u.username, u.email, u.title, u.created_at, r.name AS role, r.name = 'admin' AS is_admin, u.email_verified_at, u.suspended_at, u.followers_count, u.following_count
+        FROM users u
+        JOIN roles r ON r.id = u.role_id
+        WHERE u.id = $1;
+    ")
+    .bind(id);
 
     let user = query.fetch_one(&pool).await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?; // Return 500 if SQL request failed
@@ -62,15 +77,14 @@ pub async fn get_by_id(Path(id): Path<i32>, Extension(auth_user): Extension<Auth
 
 /*
  * Create an new user in the database and returns it
+ * - A mail is sent to verify the email
  * @auth {None} - no authorization needed
  * @param {FormCreateUser} - form input data
  */
-pub async fn create_user( Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>, Form(payload): Form<FormCreateUser>) -> Result<Json<User>, StatusCode> {
+pub async fn create_user( Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>, State(mailer): State<Arc<dyn Mailer>>, Form(payload): Form<FormCreateUser>) -> Result<Json<User>, StatusCode> {
 
-    // If some is trying to create an admin user but is not admin return 401
-    if payload.is_admin && !get_is_admin(&pool, &auth_user).await {
-        return Err(StatusCode::UNAUTHORIZED);
-    }
+    // Giving another role than the default one needs the user.role.assign permission
+    let role_id = get_updated_role_id(&pool, &auth_user, None, payload.role.as_deref()).await?;
 
     // Get password data
     let password = &payload.password;
@@ -85,38 +99,60 @@ pub async fn create_user( Extension(auth_user): Extension<AuthUser>, State(pool)
         .to_string();
 
     let query = sqlx::query_as::<_, User>("
-        INSERT INTO users (username, email, password, title, is_admin)
-        VALUES ($1, $2, $3, $4, $5)
-        RETURNING id, username, email, title, created_at, is_admin;
+        WITH created AS (
+            INSERT INTO users (username, email, password, title, role_id)
+            VALUES ($1, $2, $3, $4, COALESCE($5, (SELECT id FROM roles WHERE name = $6)))
+            RETURNING *
+        )
+        SELECT u.id, u.username, u.email, u.title, u.created_at, r.name AS role, r.name = 'admin' AS is_admin, u.email_verified_at, u.suspended_at, u.followers_count, u.following_count
+        FROM created u
+        JOIN roles r ON r.id = u.role_id;
     ")
     .bind(&payload.username)
     .bind(&payload.email)
     .bind(&password_hash)
     .bind(&payload.title)
-    .bind(&payload.is_admin);
+    .bind(role_id)
+    .bind(DEFAULT_ROLE);
 
-    let user = query.fetch_one(&pool).await
+    // Start transaction, the user is only created with his verification token
+    let mut tx = pool.begin().await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let user = query.fetch_one(&mut *tx).await
         .map_err(|e| {
             eprintln!("Error creating user: {:?}", e);
             StatusCode::INTERNAL_SERVER_ERROR
         })?; // Return 500 if SQL request failed
 
+    let mail = create_verification_mail(&mut tx, user.id, user.username.clone(), user.email.clone()).await?;
+
+    // Commit -> Apply all queries
+    tx.commit().await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    // A mail error doesn't fail the request, the user can ask for it again (/auth/verify-email/resend)
+    send_in_background(mailer, mail);
+
     // Return the created user
     Ok(Json(user))
 }
 
 /*
  * Delete an user from the database
- * @auth {Admin} - only for admin users
+ * @auth {Permission} - only for users with the user.delete permission
  * @param {id} - user id you want to delete
  */
 pub async fn delete_user(Path(id): Path<i32>, Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>) -> StatusCode {
 
-    let is_admin = get_is_admin(&pool, &auth_user).await;
+    // Return 401 if not connected, 403 without the user.delete permission
+    if let Err(status) = require_permission(&pool, &auth_user, USER_DELETE).await {
+        return status;
+    }
 
-    // If user is not admin we return 401
-    if !is_admin {
-        return StatusCode::UNAUTHORIZED;
+    // If the API token doesn't have the users:write scope, return 403
+    if !auth_user.has_scope(USERS_WRITE) {
+        return StatusCode::FORBIDDEN;
     }
 
     let query = sqlx::query("DELETE FROM users WHERE id = $1;").bind(id);
@@ -133,78 +169,246 @@ pub async fn delete_user(Path(id): Path<i32>, Extension(auth_user): Extension<Au
 
 /*
  * Update an user from the database
- * @auth {Admin, Connected} - admin can modify all users data and users can only modify there own
+ * - If the email changed, it must be verified again
+ * @auth {Permission, Connected} - users with the user.update.any permission can modify all users data and users can only modify there own
  * @param {id} - user id you want to update
  * @param {FormUpdateUser} - form input data
  */
-pub async fn update_user(Path(id): Path<i32>, Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>, Form(payload): Form<FormUpdateUser>) -> StatusCode {
+pub async fn update_user(Path(id): Path<i32>, Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>, State(mailer): State<Arc<dyn Mailer>>, Form(payload): Form<FormUpdateUser>) -> StatusCode {
 
-    let is_admin = get_is_admin(&pool, &auth_user).await;
+    let can_update_any = match get_has_permission(&pool, &auth_user, USER_UPDATE_ANY).await {
+        Ok(can_update_any) => can_update_any,
+        Err(status) => return status,
+    };
 
-    let is_authorized = (auth_user.is_connected && auth_user.user_id == id) || is_admin;
+    let is_authorized = (auth_user.is_connected && auth_user.user_id == id) || can_update_any;
 
-    // If user is not admin or is not connect on his account return 401
+    // If user can't update other users and is not connect on his account return 401
     if !is_authorized {
         return StatusCode::UNAUTHORIZED;
     }
 
-    // If some is trying to set an user to admin but is not an admin return 401
-    if payload.is_admin && !is_admin {
-        return StatusCode::UNAUTHORIZED;
+    // If the API token doesn't have the users:write scope, return 403
+    if !auth_user.has_scope(USERS_WRITE) {
+        return StatusCode::FORBIDDEN;
+    }
+
+    // Only a login session can change the password, not an API token (return 403)
+    if payload.password.is_some() && !auth_user.is_session() {
+        return StatusCode::FORBIDDEN;
     }
 
+    // Same for the email: a new email can receive a password reset mail (return 403)
+    if !auth_user.is_session() {
+
+        let email_result = sqlx::query("SELECT email FROM users WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&pool)
+            .await;
+
+        match email_result {
+            Ok(Some(row)) if row.get::<String, _>("email") != payload.email => return StatusCode::FORBIDDEN,
+            Ok(Some(_)) => {},
+            Ok(None) => return StatusCode::NOT_FOUND,
+            Err(_) => return StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    // Changing the role needs the user.role.assign permission (403), None keeps the current role
+    let role_id = match get_updated_role_id(&pool, &auth_user, Some(id), payload.role.as_deref()).await {
+        Ok(role_id) => role_id,
+        Err(status) => return status,
+    };
+
     // Check if a new password is provided in the payload
-    if let Some(password) = payload.password {
+    let result = if let Some(password) = &payload.password {
 
         // Generate and Hash the password
         let salt = SaltString::generate(&mut OsRng);
-        let password_hash = Argon2::default()
-            .hash_password(password.as_bytes(), &salt)
-            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
-            .unwrap().to_string();
+        let password_hash = match Argon2::default().hash_password(password.as_bytes(), &salt) {
+            Ok(hash) => hash.to_string(),
+            Err(_) => return StatusCode::INTERNAL_SERVER_ERROR,
+        };
 
         // Create SQL query
-        let query = sqlx::query("UPDATE users SET username = $1, email = $2, password = $3, title = $4, is_admin = $5 WHERE id = $6")
-            .bind(&payload.username)
-            .bind(&payload.email)
-            .bind(&password_hash)
-            .bind(&payload.title)
-            .bind(&payload.is_admin)
-            .bind(id);
-
+        // - Setting columns still reads the old values, so email_verified_at is only reset if the email changed
+        let query = sqlx::query("
+            WITH old AS (SELECT email FROM users WHERE id = $6)
+            UPDATE users SET
+                username = $1,
+                email = $2,
+                password = $3,
+                title = $4,
+                role_id = COALESCE($5, users.role_id),
+                email_verified_at = CASE WHEN users.email = $2 THEN users.email_verified_at ELSE NULL END
+            FROM old
+            WHERE users.id = $6
+            RETURNING old.email <> users.email AS email_changed;
+        ")
+        .bind(&payload.username)
+        .bind(&payload.email)
+        .bind(&password_hash)
+        .bind(&payload.title)
+        .bind(role_id)
+        .bind(id);
 
         // Execute query
-        let result = query.execute(&pool).await;
-
-        match result {
-
-            Ok(res) if res.rows_affected() > 0 => StatusCode::NO_CONTENT, // 204
-            Ok(_) => StatusCode::NOT_FOUND, // 404
-            Err(_) => StatusCode::INTERNAL_SERVER_ERROR, // 500
-        }
+        query.fetch_optional(&pool).await
 
     // If there is no password in the payload
     } else {
 
         // Create query
-        let query = sqlx::query("UPDATE users SET username = $1, email = $2, title = $3, is_admin = $4 WHERE id = $5")
-            .bind(&payload.username)
-            .bind(&payload.email)
-            .bind(&payload.title)
-            .bind(&payload.is_admin)
-            .bind(id);
+        let query = sqlx::query("
+            WITH old AS (SELECT email FROM users WHERE id = $5)
+            UPDATE users SET
+                username = $1,
+                email = $2,
+                title = $3,
+                role_id = COALESCE($4, users.role_id),
+                email_verified_at = CASE WHEN users.email = $2 THEN users.email_verified_at ELSE NULL END
+            FROM old
+            WHERE users.id = $5
+            RETURNING old.email <> users.email AS email_changed;
+        ")
+        .bind(&payload.username)
+        .bind(&payload.email)
+        .bind(&payload.title)
+        .bind(role_id)
+        .bind(id);
 
         // Execute query
-        let result = query.execute(&pool).await;
+        query.fetch_optional(&pool).await
+    };
+
+    match result {
+
+        Ok(Some(row)) => {
+
+            let email_changed: bool = row.get("email_changed");
 
-        match result {
+            // Send a mail to the new address to verify it
+            if email_changed {
+                if let Err(status) = send_verification_mail(&pool, mailer, id, payload.username, payload.email).await {
+                    return status;
+                }
+            }
 
-            Ok(res) if res.rows_affected() > 0 => StatusCode::NO_CONTENT, // 204
-            Ok(_) => StatusCode::NOT_FOUND, // 404
-            Err(_) => StatusCode::INTERNAL_SERVER_ERROR, // 500
+            StatusCode::NO_CONTENT // 204
         }
+        Ok(None) => StatusCode::NOT_FOUND, // 404
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR, // 500
     }
 }
+
+/*
+ * Suspend an user, he is disconnected and can't log in anymore
+ * - Users who can suspend can't be suspended (moderators can't suspend each other or the admins)
+ * @auth {Permission} - only for users with the user.suspend permission
+ * @param {id} - user id you want to suspend
+ */
+pub async fn suspend_user(Path(id): Path<i32>, Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>) -> StatusCode {
+
+    // Return 401 if not connected, 403 without the user.suspend permission
+    if let Err(status) = require_permission(&pool, &auth_user, USER_SUSPEND).await {
+        return status;
+    }
+
+    // If the API token doesn't have the users:write scope, return 403
+    if !auth_user.has_scope(USERS_WRITE) {
+        return StatusCode::FORBIDDEN;
+    }
+
+    // Start transaction
+    let mut tx = match pool.begin().await {
+        Ok(t) => t,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR,
+    };
+
+    let query = sqlx::query("
+        SELECT
+            u.suspended_at IS NOT NULL AS is_suspended,
+            EXISTS (
+                SELECT 1 FROM role_permissions rp
+                JOIN permissions p ON p.id = rp.permission_id
+                WHERE rp.role_id = u.role_id AND p.name = $2
+            ) AS can_suspend
+        FROM users u
+        WHERE u.id = $1
+        FOR UPDATE;
+    ")
+    .bind(id)
+    .bind(USER_SUSPEND);
+
+    let row = match query.fetch_optional(&mut *tx).await {
+        Ok(Some(row)) => row,
+        Ok(None) => return StatusCode::NOT_FOUND, // 404 if no user found
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR,
+    };
+
+    let is_suspended: bool = row.get("is_suspended");
+    let can_suspend: bool = row.get("can_suspend");
+
+    // Return 403 if the user can suspend too, 409 if he is already suspended
+    if can_suspend {
+        return StatusCode::FORBIDDEN;
+    }
+
+    if is_suspended {
+        return StatusCode::CONFLICT;
+    }
+
+    // Suspend the user and revoke all his sessions (his API tokens are refused while suspended)
+    let query = sqlx::query("
+        WITH suspended AS (UPDATE users SET suspended_at = NOW() WHERE id = $1),
+        revoked AS (
+            UPDATE sessions SET revoked_at = NOW()
+            WHERE user_id = $1 AND revoked_at IS NULL
+        )
+        UPDATE refresh_tokens SET revoked_at = NOW()
+        WHERE user_id = $1 AND revoked_at IS NULL;
+    ")
+    .bind(id);
+
+    if query.execute(&mut *tx).await.is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    }
+
+    // Commit -> Apply all queries
+    match tx.commit().await {
+        Ok(_) => StatusCode::NO_CONTENT, // 204
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+/*
+ * Lift the suspension of an user
+ * @auth {Permission} - only for users with the user.suspend permission
+ * @param {id} - user id you want to unsuspend
+ */
+pub async fn unsuspend_user(Path(id): Path<i32>, Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>) -> StatusCode {
+
+    // Return 401 if not connected, 403 without the user.suspend permission
+    if let Err(status) = require_permission(&pool, &auth_user, USER_SUSPEND).await {
+        return status;
+    }
+
+    // If the API token doesn't have the users:write scope, return 403
+    if !auth_user.has_scope(USERS_WRITE) {
+        return StatusCode::FORBIDDEN;
+    }
+
+    let query = sqlx::query("UPDATE users SET suspended_at = NULL WHERE id = $1 AND suspended_at IS NOT NULL")
+        .bind(id);
+
+    match query.execute(&pool).await {
+
+        Ok(res) if res.rows_affected() > 0 => StatusCode::NO_CONTENT, // 204
+        Ok(_) => StatusCode::NOT_FOUND, // 404 if no suspended user found
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR, // 500
+    }
+}
+
 /*
  * Get connected user data
  * @auth {Connected} - only for connected users
@@ -216,14 +420,130 @@ pub async fn get_connected(Extension(auth_user): Extension<AuthUser>, State(pool
         return Err(StatusCode::UNAUTHORIZED);
     }
 
+    // If the API token doesn't have the users:read scope, return 403
+    if !auth_user.has_scope(USERS_READ) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
     // Get connected user id
     let id = auth_user.user_id;
 
-    let query = sqlx::query_as::<_, User>("SELECT id, username, email, title, created_at, is_admin FROM users WHERE id = $1")
-        .bind(id);
+    let query = sqlx::query_as::<_, User>("
+        SELECT u.id, u.username, u.email, u.title, u.created_at, r.name AS role, r.name = 'admin' AS is_admin, u.email_verified_at, u.suspended_at, u.followers_count, u.following_count
+        FROM users u
+        JOIN roles r ON r.id = u.role_id
+        WHERE u.id = $1;
+    ")
+    .bind(id);
 
     let user = query.fetch_one(&pool).await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?; // returns 500 if SQL error
 
     Ok(Json(user))
 }
+
+/*
+ * Get the id of the role asked in a creation or an update, and check the connected user can give it
+ * - Returns None if no role was given (default role, or role unchanged)
+ * - This is not an hanlder, but an helper function
+ */
+async fn get_updated_role_id(pool: &PgPool, auth_user: &AuthUser, user_id: Option<i32>, role: Option<&str>) -> Result<Option<i32>, StatusCode> {
+
+    let Some(role) = role else {
+        return Ok(None);
+    };
+
+    // Return 400 if the role doesn't exist
+    let role_id = get_role_id(pool, role).await?
+        .ok_or(StatusCode::BAD_REQUEST)?;
+
+    // Current role of the user (the default one for a new user)
+    let current_role_id = match user_id {
+
+        Some(user_id) => {
+
+            let query = sqlx::query("SELECT role_id FROM users WHERE id = $1")
+                .bind(user_id);
+
+            let row = query.fetch_optional(pool).await
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+                .ok_or(StatusCode::NOT_FOUND)?;
+
+            row.get("role_id")
+        }
+
+        None => get_role_id(pool, DEFAULT_ROLE).await?
+            .ok_or(StatusCode::INTERNAL_SERVER_ERROR)?,
+    };
+
+    // Sending the current role is not a change
+    if role_id != current_role_id {
+        require_permission(pool, auth_user, USER_ROLE_ASSIGN).await?;
+    }
+
+    Ok(Some(role_id))
+}
+
+// Values of users.dm_policy
+pub const DM_POLICIES: [&str; 3] = ["everyone", "following", "nobody"];
+
+/*
+ * Get the privacy settings of the connected user
+ * @auth {Connected} - only for connected users
+ */
+pub async fn get_settings(Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>) -> Result<Json<UserSettings>, StatusCode> {
+
+    // If user is not connected we return 401
+    if !auth_user.is_connected {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    // If the API token doesn't have the users:read scope, return 403
+    if !auth_user.has_scope(USERS_READ) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let query = sqlx::query_as::<_, UserSettings>("SELECT dm_policy FROM users WHERE id = $1")
+        .bind(auth_user.user_id);
+
+    let settings = query.fetch_one(&pool).await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?; // returns 500 if SQL error
+
+    Ok(Json(settings))
+}
+
+/*
+ * Change the privacy settings of the connected user
+ * @auth {Connected} - only for connected users
+ * @param {UpdateSettingsRequest} - the settings to change
+ */
+pub async fn update_settings(Extension(auth_user): Extension<AuthUser>, State(pool): State<PgPool>, Json(payload): Json<UpdateSettingsRequest>) -> Result<Json<UserSettings>, StatusCode> {
+
+    // If user is not connected we return 401
+    if !auth_user.is_connected {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    // If the API token doesn't have the users:write scope, return 403
+    if !auth_user.has_scope(USERS_WRITE) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    // Return 400 if the value is unknown
+    if payload.dm_policy.as_deref().is_some_and(|policy| !DM_POLICIES.contains(&policy)) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let query = sqlx::query_as::<_, UserSettings>("
+        UPDATE users SET dm_policy = COALESCE($2, dm_policy)
+        WHERE id = $1
+        RETURNING dm_policy;
+    ")
+    .bind(auth_user.user_id)
+    .bind(payload.dm_policy);
+
+    let settings = query.fetch_one(&pool).await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?; // returns 500 if SQL error
+
+    Ok(Json(settings))
+}
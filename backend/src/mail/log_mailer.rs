@@ -0,0 +1,54 @@
+use async_trait::async_trait;
+use tokio::io::AsyncWriteExt;
+
+use crate::mail::{Mail, Mailer, MailError};
+
+/*
+ * Mailer that doesn't send anything, mails are written in a file or in stdout
+ * - Useful when working locally or for tests
+ */
+pub struct LogMailer {
+    path: Option<String>,
+}
+
+impl LogMailer {
+
+    pub fn new(path: Option<String>) -> Self {
+
+        LogMailer { path }
+    }
+}
+
+#[async_trait]
+impl Mailer for LogMailer {
+
+    async fn send(&self, mail: Mail) -> Result<(), MailError> {
+
+        let entry = format!(
+            "---- MAIL {} ----\nTo: {}\nSubject: {}\n\n{}\n\n",
+            chrono::Utc::now().to_rfc3339(),
+            mail.to,
+            mail.subject,
+            mail.body
+        );
+
+        match &self.path {
+
+            // Append the mail at the end of the file
+            Some(path) => {
+
+                let mut file = tokio::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .await?;
+
+                file.write_all(entry.as_bytes()).await?;
+            }
+
+            None => print!("{entry}"),
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,66 @@
+use std::env;
+use std::sync::Arc;
+use async_trait::async_trait;
+
+pub mod smtp_mailer;
+pub mod log_mailer;
+
+use smtp_mailer::SmtpMailer;
+use log_mailer::LogMailer;
+
+pub type MailError = Box<dyn std::error::Error + Send + Sync>;
+
+// A mail to send to an user
+#[derive(Debug, Clone)]
+pub struct Mail {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/*
+ * Everything able to send mails (SMTP server, log file, ...)
+ * - The implementation used is choosen at startup with the MAILER env variable
+ */
+#[async_trait]
+pub trait Mailer: Send + Sync {
+
+    async fn send(&self, mail: Mail) -> Result<(), MailError>;
+}
+
+/*
+ * Create the mailer configured in the env
+ * - MAILER=smtp : send mails with SMTP_HOST, SMTP_PORT, SMTP_USERNAME, SMTP_PASSWORD, SMTP_TLS
+ * - MAILER=log (default) : write mails to MAIL_LOG_FILE, or to stdout if not set
+ */
+pub fn mailer_from_env() -> Arc<dyn Mailer> {
+
+    match env::var("MAILER").as_deref() {
+
+        Ok("smtp") => Arc::new(SmtpMailer::from_env()),
+
+        _ => Arc::new(LogMailer::new(env::var("MAIL_LOG_FILE").ok())),
+    }
+}
+
+/*
+ * Send a mail in background, the user doesn't have to wait for the SMTP server
+ * - A failed mail is only logged, the user can ask for it again
+ */
+pub fn send_in_background(mailer: Arc<dyn Mailer>, mail: Mail) {
+
+    tokio::spawn(async move {
+        let subject = mail.subject.clone();
+        if let Err(e) = mailer.send(mail).await {
+            eprintln!("Error sending mail \"{subject}\": {e}");
+        }
+    });
+}
+
+/*
+ * Base URL of the client, used to create the links sent by mail
+ */
+pub fn app_url() -> String {
+
+    env::var("APP_URL").unwrap_or_else(|_| String::from("http://127.0.0.1:8000"))
+}
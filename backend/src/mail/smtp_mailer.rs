@@ -0,0 +1,63 @@
+use std::env;
+use async_trait::async_trait;
+use lettre::{
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+    message::header::ContentType,
+    transport::smtp::authentication::Credentials,
+};
+
+use crate::mail::{Mail, Mailer, MailError};
+
+/*
+ * Mailer sending mails to a SMTP server
+ * - With SMTP_TLS=false it can be used with a local SMTP catcher (mailpit, MailHog, ...)
+ */
+pub struct SmtpMailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: String,
+}
+
+impl SmtpMailer {
+
+    pub fn from_env() -> Self {
+
+        let host = env::var("SMTP_HOST").unwrap_or_else(|_| String::from("localhost"));
+        let port: u16 = env::var("SMTP_PORT").ok().and_then(|port| port.parse().ok()).unwrap_or(1025);
+        let use_tls = env::var("SMTP_TLS").map(|value| value == "true").unwrap_or(false);
+
+        let mut builder = if use_tls {
+            AsyncSmtpTransport::<Tokio1Executor>::relay(&host).expect("SMTP_HOST must be a valid host")
+        } else {
+            AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&host)
+        };
+
+        builder = builder.port(port);
+
+        // Credentials are optional (a local catcher doesn't need them)
+        if let (Ok(username), Ok(password)) = (env::var("SMTP_USERNAME"), env::var("SMTP_PASSWORD")) {
+            builder = builder.credentials(Credentials::new(username, password));
+        }
+
+        let from = env::var("MAIL_FROM").unwrap_or_else(|_| String::from("FEUR <no-reply@feur.local>"));
+
+        SmtpMailer { transport: builder.build(), from }
+    }
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+
+    async fn send(&self, mail: Mail) -> Result<(), MailError> {
+
+        let message = Message::builder()
+            .from(self.from.parse()?)
+            .to(mail.to.parse()?)
+            .subject(mail.subject)
+            .header(ContentType::TEXT_PLAIN)
+            .body(mail.body)?;
+
+        self.transport.send(message).await?;
+
+        Ok(())
+    }
+}
@@ -5,19 +5,44 @@ use sqlx::postgres::PgPoolOptions;
 use std::env;
 use std::time::Duration;
 use tokio::time::sleep;
-use tower_http::cors::{CorsLayer, Any};
+use tower_http::cors::{CorsLayer, AllowCredentials, AllowHeaders, AllowMethods, AllowOrigin};
 use axum::routing::get;
 
 pub mod handlers;
 pub mod models;
 pub mod routes;
 pub mod auth;
+pub mod mail;
+pub mod oidc;
+pub mod entities;
+pub mod trends;
+pub mod search;
+pub mod storage;
+pub mod media;
+pub mod stream;
+pub mod state;
 
 use crate::routes::user_routes;
 use crate::routes::post_routes;
 use crate::routes:: auth_routes;
+use crate::routes::tag_routes;
+use crate::routes::trend_routes;
+use crate::routes::search_routes;
+use crate::routes::media_routes;
+use crate::routes::notification_routes;
+use crate::routes::stream_routes;
+use crate::routes::conversation_routes;
 
 use crate::handlers::ping;
+use crate::handlers::auth_handlers::jwks;
+use crate::auth::login_guard::init_dummy_hash;
+use crate::auth::jwt_keys::init_jwt_keys;
+use crate::mail::{mailer_from_env, app_url};
+use crate::oidc::oidc_from_env;
+use crate::trends::refresh_trends;
+use crate::storage::storage_from_env;
+use crate::stream::{event_channel, listen_events, purge_events};
+use crate::state::AppState;
 
 /*
  * Try to connect to the database with multiplie tries
@@ -65,11 +90,19 @@ async fn main() {
     // Ensure env variable are accessible
     dotenv().ok();
 
-    // Configure CORS layer
+    // Configure CORS layer (any origin, but only APP_URL can send the cookies, ex: the OIDC state)
+    let app_origin = reqwest::Url::parse(&app_url()).expect("APP_URL must be a valid URL").origin().ascii_serialization();
     let cors = CorsLayer::new()
-        .allow_origin(Any)
-        .allow_methods(Any)
-        .allow_headers(Any);
+        .allow_origin(AllowOrigin::mirror_request())
+        .allow_methods(AllowMethods::mirror_request())
+        .allow_headers(AllowHeaders::mirror_request())
+        .allow_credentials(AllowCredentials::predicate(move |origin, _| origin.as_bytes() == app_origin.as_bytes()));
+
+    // Prepare the constant time login
+    init_dummy_hash();
+
+    // Load the JWT signing keys (stops here if they are invalid)
+    init_jwt_keys();
 
     // Get database_url from env variable
     let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
@@ -77,13 +110,38 @@ async fn main() {
     // Try getting DB connnection
     let pool = wait_for_db(&database_url, 10, 2).await;
 
+    // Recompute the trending hashtags in background
+    tokio::spawn(refresh_trends(pool.clone()));
+
+    // Receive the events of all the instances (LISTEN), for the clients of /stream
+    let events = event_channel();
+    tokio::spawn(listen_events(pool.clone(), events.clone()));
+    tokio::spawn(purge_events(pool.clone()));
+
+    // Create the state shared by all handlers
+    let state = AppState {
+        pool,
+        mailer: mailer_from_env(),
+        oidc: oidc_from_env(),
+        storage: storage_from_env(),
+        events,
+    };
+
     // Create http router with all paths and routes
     let app = Router::new()
         .route("/", get(ping)) // Ping route (used to check if backend is up)
-        .nest("/users", user_routes::routes()) // API routes
-        .nest("/posts", post_routes::routes())
-        .nest("/auth", auth_routes::routes())
-        .with_state(pool.clone())
+        .route("/.well-known/jwks.json", get(jwks)) // Public keys of the JWTs
+        .nest("/users", user_routes::routes(state.clone())) // API routes
+        .nest("/posts", post_routes::routes(state.clone()))
+        .nest("/auth", auth_routes::routes(state.clone()))
+        .nest("/tags", tag_routes::routes(state.clone()))
+        .nest("/trends", trend_routes::routes())
+        .nest("/search", search_routes::routes(state.clone()))
+        .nest("/media", media_routes::routes(state.clone()))
+        .nest("/notifications", notification_routes::routes(state.clone()))
+        .nest("/stream", stream_routes::routes(state.clone()))
+        .nest("/conversations", conversation_routes::routes(state.clone()))
+        .with_state(state)
         .layer(cors);
 
     // Create new adress where the API is gonna listen
@@ -94,6 +152,6 @@ async fn main() {
     // Create a new listener for this adress
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
 
-    // Run the API
-    axum::serve(listener, app).await.unwrap();
+    // Run the API (with the client address, used to save the IP of the sessions)
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await.unwrap();
 }
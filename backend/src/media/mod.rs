@@ -0,0 +1,208 @@
+use std::env;
+use std::io::Cursor;
+use image::{DynamicImage, ImageDecoder, ImageFormat, ImageReader, Limits};
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::webp::WebPEncoder;
+
+/*
+ * Validation and processing of the uploaded images
+ * - The format is read from the magic bytes, the name and the Content-Type sent by the client are ignored
+ * - The image is decoded and encoded again: the metadata (EXIF, GPS position, ...) are not kept,
+ *   the EXIF orientation is applied before
+ */
+
+// Largest width or height accepted, to not decode huge images
+const MAX_DIMENSION: u32 = 8000;
+
+// Largest width or height of the thumbnails
+const THUMBNAIL_SIZE: u32 = 400;
+
+// Limits of the uploads, can be overridden with MEDIA_MAX_BYTES & MEDIA_MAX_PER_POST
+const DEFAULT_MEDIA_MAX_BYTES: usize = 10 * 1024 * 1024;
+const DEFAULT_MEDIA_MAX_PER_POST: usize = 4;
+
+pub fn max_upload_bytes() -> usize {
+
+    env::var("MEDIA_MAX_BYTES").ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_MEDIA_MAX_BYTES)
+}
+
+pub fn max_media_per_post() -> usize {
+
+    env::var("MEDIA_MAX_PER_POST").ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_MEDIA_MAX_PER_POST)
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum MediaError {
+    UnsupportedType, // not a JPEG, PNG or WebP image
+    InvalidImage,    // corrupted or too large image
+}
+
+pub struct ProcessedImage {
+    pub data: Vec<u8>,
+    pub content_type: &'static str,
+    pub extension: &'static str,
+    pub width: u32,
+    pub height: u32,
+    pub thumbnail: Vec<u8>, // always a JPEG
+}
+
+/*
+ * Check an uploaded image, remove its metadata and create its thumbnail
+ * - CPU heavy, must be called with spawn_blocking
+ */
+pub fn process_image(bytes: &[u8]) -> Result<ProcessedImage, MediaError> {
+
+    let format = image::guess_format(bytes)
+        .map_err(|_| MediaError::UnsupportedType)?;
+
+    let (content_type, extension) = match format {
+        ImageFormat::Jpeg => ("image/jpeg", "jpg"),
+        ImageFormat::Png => ("image/png", "png"),
+        ImageFormat::WebP => ("image/webp", "webp"),
+        _ => return Err(MediaError::UnsupportedType),
+    };
+
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_DIMENSION);
+    limits.max_image_height = Some(MAX_DIMENSION);
+
+    let mut reader = ImageReader::with_format(Cursor::new(bytes), format);
+    reader.limits(limits);
+
+    let mut decoder = reader.into_decoder()
+        .map_err(|_| MediaError::InvalidImage)?;
+
+    let orientation = decoder.orientation()
+        .map_err(|_| MediaError::InvalidImage)?;
+
+    let mut image = DynamicImage::from_decoder(decoder)
+        .map_err(|_| MediaError::InvalidImage)?;
+
+    image.apply_orientation(orientation);
+
+    // Same format as the upload, without the metadata
+    let data = match format {
+        ImageFormat::Jpeg => encode_jpeg(&image, 90)?,
+        ImageFormat::WebP => {
+            let mut data = Vec::new();
+            DynamicImage::ImageRgba8(image.to_rgba8())
+                .write_with_encoder(WebPEncoder::new_lossless(&mut data))
+                .map_err(|_| MediaError::InvalidImage)?;
+            data
+        }
+        _ => {
+            let mut data = Vec::new();
+            image.write_to(&mut Cursor::new(&mut data), ImageFormat::Png)
+                .map_err(|_| MediaError::InvalidImage)?;
+            data
+        }
+    };
+
+    let thumbnail = encode_jpeg(&image.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE), 80)?;
+
+    Ok(ProcessedImage {
+        data,
+        content_type,
+        extension,
+        width: image.width(),
+        height: image.height(),
+        thumbnail,
+    })
+}
+
+fn encode_jpeg(image: &DynamicImage, quality: u8) -> Result<Vec<u8>, MediaError> {
+
+    let mut data = Vec::new();
+
+    // JPEG has no transparency
+    DynamicImage::ImageRgb8(image.to_rgb8())
+        .write_with_encoder(JpegEncoder::new_with_quality(&mut data, quality))
+        .map_err(|_| MediaError::InvalidImage)?;
+
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::RgbImage;
+
+    fn jpeg(width: u32, height: u32) -> Vec<u8> {
+        encode_jpeg(&DynamicImage::ImageRgb8(RgbImage::new(width, height)), 90).unwrap()
+    }
+
+    // Add an EXIF segment with an orientation (6 = rotated 90° clockwise) after the start of a JPEG
+    fn with_exif_orientation(jpeg: &[u8], orientation: u16) -> Vec<u8> {
+
+        let mut tiff = b"II*\0".to_vec();
+        tiff.extend(8u32.to_le_bytes());                  // first IFD offset
+        tiff.extend(1u16.to_le_bytes());                  // one entry
+        tiff.extend(0x0112u16.to_le_bytes());             // orientation tag
+        tiff.extend(3u16.to_le_bytes());                  // SHORT
+        tiff.extend(1u32.to_le_bytes());                  // count
+        tiff.extend((orientation as u32).to_le_bytes());  // value
+        tiff.extend(0u32.to_le_bytes());                  // no next IFD
+
+        let mut segment = b"Exif\0\0".to_vec();
+        segment.extend(tiff);
+
+        let mut result = jpeg[..2].to_vec();
+        result.extend([0xFF, 0xE1]);
+        result.extend(((segment.len() + 2) as u16).to_be_bytes());
+        result.extend(segment);
+        result.extend(&jpeg[2..]);
+        result
+    }
+
+    #[test]
+    fn detects_the_type_from_the_content() {
+
+        let image = process_image(&jpeg(16, 8)).unwrap();
+
+        assert_eq!((image.content_type, image.extension), ("image/jpeg", "jpg"));
+        assert_eq!((image.width, image.height), (16, 8));
+
+        let mut png = Vec::new();
+        DynamicImage::ImageRgb8(RgbImage::new(4, 4)).write_to(&mut Cursor::new(&mut png), ImageFormat::Png).unwrap();
+
+        assert_eq!(process_image(&png).unwrap().content_type, "image/png");
+    }
+
+    #[test]
+    fn rejects_other_files() {
+
+        assert_eq!(process_image(b"<svg xmlns=\"http://www.w3.org/2000/svg\"/>").err(), Some(MediaError::UnsupportedType));
+        assert_eq!(process_image(b"GIF89a......").err(), Some(MediaError::UnsupportedType));
+        assert_eq!(process_image(b"").err(), Some(MediaError::UnsupportedType));
+
+        // Right magic bytes, but not an image
+        let mut fake = jpeg(4, 4);
+        fake.truncate(20);
+        assert_eq!(process_image(&fake).err(), Some(MediaError::InvalidImage));
+    }
+
+    #[test]
+    fn removes_exif_and_applies_the_orientation() {
+
+        let upload = with_exif_orientation(&jpeg(16, 8), 6);
+        assert!(upload.windows(4).any(|bytes| bytes == b"Exif"));
+
+        let image = process_image(&upload).unwrap();
+
+        assert!(!image.data.windows(4).any(|bytes| bytes == b"Exif"));
+        assert_eq!((image.width, image.height), (8, 16));
+    }
+
+    #[test]
+    fn creates_small_thumbnails() {
+
+        let image = process_image(&jpeg(1600, 800)).unwrap();
+        let thumbnail = image::load_from_memory(&image.thumbnail).unwrap();
+
+        assert_eq!((thumbnail.width(), thumbnail.height()), (THUMBNAIL_SIZE, THUMBNAIL_SIZE / 2));
+    }
+}
@@ -0,0 +1,29 @@
+use serde::{Deserialize, Serialize};
+use chrono::NaiveDateTime;
+
+// Struct representing a personal access token, without its value
+#[derive(sqlx::FromRow, Serialize, Debug)]
+pub struct ApiToken {
+    pub id: i32,
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub created_at: Option<NaiveDateTime>,
+    pub expires_at: Option<NaiveDateTime>, // None if the token never expires
+    pub last_used_at: Option<NaiveDateTime>
+}
+
+// JSON client must send to create a token
+#[derive(Deserialize)]
+pub struct CreateApiTokenRequest {
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub expires_in_days: Option<i64> // None for a token that never expires
+}
+
+// Response to a token creation, it's the only time the token value is given
+#[derive(Serialize)]
+pub struct CreatedApiToken {
+    #[serde(flatten)]
+    pub api_token: ApiToken,
+    pub token: String
+}
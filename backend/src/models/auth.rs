@@ -4,16 +4,50 @@ use serde::{Deserialize, Serialize};
 #[derive(Clone)]
 pub struct AuthUser {
     pub user_id: i32,
-    pub is_connected: bool
+    pub session_id: i32, // -1 if not connected or connected with an API token
+    pub is_connected: bool,
+    pub scopes: Option<Vec<String>> // None for a login session, the scopes of the token for an API token
+}
+
+impl AuthUser {
+
+    /*
+     * Check if the request is allowed to use a scope (see auth::scopes)
+     * - Login sessions can do everything, API tokens only what they were created for
+     */
+    pub fn has_scope(&self, scope: &str) -> bool {
+
+        self.is_connected && self.scopes.as_ref().is_none_or(|scopes| scopes.iter().any(|s| s == scope))
+    }
+
+    /*
+     * True if the user logged in with a password, and not with an API token
+     * - Managing the account security (sessions, tokens, 2FA, password) needs a login session
+     */
+    pub fn is_session(&self) -> bool {
+
+        self.is_connected && self.scopes.is_none()
+    }
 }
 
 // This is the claims structure used to store data in the JWT key
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: i32,   // user id
+    pub sid: i32,   // session id
     pub exp: usize  // expiration timestamp
 }
 
+// Claims of the token given after the password step when 2FA is enabled
+// - It has no session id, so it can't be used as an access token
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChallengeClaims {
+    pub sub: i32,    // user id
+    pub aud: String, // always "2fa"
+    pub jti: String, // random id, the token can only be used once
+    pub exp: usize   // expiration timestamp
+}
+
 // JSON client must send to attempt to an login request
 #[derive(Deserialize)]
 pub struct LoginRequest {
@@ -24,5 +58,86 @@ pub struct LoginRequest {
 // Response send to client when user sucessfully connected
 #[derive(Serialize)]
 pub struct TokenResponse {
+    pub token: String,         // short-lived access token (JWT)
+    pub refresh_token: String, // opaque token used to get a new access token
+}
+
+// Response send to client when the password is good but a 2FA code is needed
+#[derive(Serialize)]
+pub struct TwoFactorChallenge {
+    pub two_factor_required: bool,
+    pub challenge_token: String, // must be sent back with the code to /auth/login/2fa
+}
+
+// Response of a login request, depending on if the user enabled 2FA
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum LoginResponse {
+    Tokens(TokenResponse),
+    TwoFactorRequired(TwoFactorChallenge),
+}
+
+// JSON client must send to finish a login with 2FA
+// - code can be a TOTP code or a recovery code
+#[derive(Deserialize)]
+pub struct TwoFactorLoginRequest {
+    pub challenge_token: String,
+    pub code: String,
+}
+
+// JSON client must send to confirm or disable 2FA
+#[derive(Deserialize)]
+pub struct TwoFactorCodeRequest {
+    pub code: String,
+}
+
+// Response send to client when starting 2FA enrollment
+#[derive(Serialize)]
+pub struct TwoFactorEnrollResponse {
+    pub secret: String,      // base32 secret, if the user can't scan the URI
+    pub otpauth_uri: String, // otpauth:// URI, usually shown as a QR code
+}
+
+// Response send to client when 2FA is enabled, codes are only shown once
+#[derive(Serialize)]
+pub struct RecoveryCodesResponse {
+    pub recovery_codes: Vec<String>,
+}
+
+// JSON client must send to refresh his tokens or to logout
+#[derive(Deserialize)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
+
+// JSON client must send to receive a password reset mail
+#[derive(Deserialize)]
+pub struct ForgotPasswordRequest {
+    pub email: String,
+}
+
+// JSON client must send to set a new password with the token received by mail
+#[derive(Deserialize)]
+pub struct ResetPasswordRequest {
     pub token: String,
+    pub password: String,
+}
+
+// JSON client must send to verify his email with the token received by mail
+#[derive(Deserialize)]
+pub struct VerifyEmailRequest {
+    pub token: String,
+}
+
+// Response to start an OpenID Connect login, the client must open this URL
+#[derive(Serialize)]
+pub struct OidcAuthorizeResponse {
+    pub authorization_url: String,
+}
+
+// JSON client must send when the provider redirected the user back to it
+#[derive(Deserialize)]
+pub struct OidcCallbackRequest {
+    pub code: String,
+    pub state: String,
 }
@@ -0,0 +1,12 @@
+use serde::Serialize;
+use chrono::NaiveDateTime;
+
+// An user in the blocks or mutes list of the connected user
+#[derive(sqlx::FromRow, Serialize, Debug)]
+pub struct HiddenUser {
+    pub id: i32,
+    pub username: String,
+    pub title: Option<String>,
+    pub created_at: Option<NaiveDateTime>,
+    pub hidden_at: Option<NaiveDateTime> // when the block or mute was made
+}
@@ -0,0 +1,10 @@
+use serde::Serialize;
+
+use crate::models::post::PostWithUserData;
+
+// A page of the bookmarks of the connected user, most recently saved first
+#[derive(Serialize, Debug)]
+pub struct Bookmarks {
+    pub posts: Vec<PostWithUserData>,
+    pub next_cursor: Option<String> // None if it's the last page
+}
@@ -0,0 +1,70 @@
+use serde::{Serialize, Deserialize};
+use chrono::NaiveDateTime;
+use sqlx::types::Json;
+
+// A participant of a conversation, with his read marker
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Participant {
+    pub id: i32,
+    pub username: String,
+    pub title: Option<String>,
+    pub last_read_message_id: Option<i32> // None if he read nothing
+}
+
+// A message of a conversation
+#[derive(sqlx::FromRow, Serialize, Deserialize, Debug)]
+pub struct Message {
+    pub id: i32,
+    pub conversation_id: i32,
+    pub sender_id: Option<i32>, // None if the sender was deleted
+    pub sender_username: Option<String>,
+    pub content: String,
+    pub created_at: NaiveDateTime
+}
+
+// A conversation, as seen by one of its participants
+#[derive(sqlx::FromRow, Serialize, Debug)]
+pub struct Conversation {
+    pub id: i32,
+    pub is_group: bool,
+    pub title: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub last_message_at: NaiveDateTime,
+    pub participants: Json<Vec<Participant>>, // including the connected user
+    pub last_message: Option<Json<Message>>,
+    pub unread_count: i64 // messages of the others after his read marker
+}
+
+// A page of the conversations of the connected user, most recent activity first
+#[derive(Serialize, Debug)]
+pub struct ConversationPage {
+    pub conversations: Vec<Conversation>,
+    pub next_cursor: Option<String> // None if it's the last page
+}
+
+// A page of the history of a conversation, most recent first
+#[derive(Serialize, Debug)]
+pub struct MessagePage {
+    pub messages: Vec<Message>,
+    pub next_cursor: Option<String> // None if it's the last page
+}
+
+// JSON client must send to start a conversation
+// - One user for a one-to-one conversation (the existing one is returned if there is one), more for a group
+#[derive(Deserialize)]
+pub struct CreateConversationRequest {
+    pub user_ids: Vec<i32>, // the other participants
+    pub title: Option<String> // only used for groups
+}
+
+// Form client must send to send a message
+#[derive(Deserialize)]
+pub struct FormMessage {
+    pub content: String
+}
+
+// Form client must send to move his read marker
+#[derive(Deserialize)]
+pub struct FormRead {
+    pub message_id: i32 // last message read
+}
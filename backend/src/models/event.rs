@@ -0,0 +1,17 @@
+use serde::{Serialize, Deserialize};
+use sqlx::types::Json;
+
+// An event of the events table, also received as JSON from the "events" channel
+#[derive(sqlx::FromRow, Serialize, Deserialize, Debug, Clone)]
+pub struct StreamEvent {
+    pub id: i64,
+    pub kind: String,
+    pub user_id: Option<i32>, // only sent to this user, None for everyone
+    pub data: Json<serde_json::Value>
+}
+
+// Query of /stream, for clients that can't send the Last-Event-ID header
+#[derive(Deserialize)]
+pub struct StreamQuery {
+    pub last_event_id: Option<i64>
+}
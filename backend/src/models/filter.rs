@@ -0,0 +1,24 @@
+use serde::{Serialize, Deserialize};
+use chrono::NaiveDateTime;
+
+// A muted word, phrase or hashtag of the connected user
+#[derive(sqlx::FromRow, Serialize, Debug)]
+pub struct ContentFilter {
+    pub id: i32,
+    pub phrase: String,            // the tag is without #
+    pub is_hashtag: bool,
+    pub contexts: Vec<String>,     // home, notifications and/or search
+    pub action: String,            // hide or warn
+    pub expires_at: Option<NaiveDateTime>,
+    pub created_at: Option<NaiveDateTime>
+}
+
+// JSON client must send to add a filter, a phrase starting with # is a hashtag
+// The filter of the same phrase is replaced if there is one
+#[derive(Deserialize)]
+pub struct CreateFilterRequest {
+    pub phrase: String,
+    pub contexts: Vec<String>,
+    pub action: Option<String>,            // hide by default
+    pub expires_in_minutes: Option<i32>    // None: never expires
+}
@@ -0,0 +1,19 @@
+use serde::Serialize;
+use chrono::NaiveDateTime;
+
+// An user in a followers or following list
+#[derive(sqlx::FromRow, Serialize, Debug)]
+pub struct FollowUser {
+    pub id: i32,
+    pub username: String,
+    pub title: Option<String>,
+    pub created_at: Option<NaiveDateTime>,
+    pub followed_at: Option<NaiveDateTime> // when the follow was made
+}
+
+// A page of a followers or following list, with the total count
+#[derive(Serialize, Debug)]
+pub struct FollowList {
+    pub count: i32,
+    pub users: Vec<FollowUser>
+}
@@ -0,0 +1,19 @@
+use serde::{Serialize, Deserialize};
+
+// An uploaded image, as sent to the clients (also in PostWithUserData.media)
+#[derive(sqlx::FromRow, Serialize, Deserialize, Debug)]
+pub struct Media {
+    pub id: i32,
+    pub url: String,
+    pub thumbnail_url: String, // JPEG, at most 400x400
+    pub content_type: String,
+    pub width: i32,
+    pub height: i32,
+    pub alt_text: Option<String>
+}
+
+// JSON client must send to change the description of a media
+#[derive(Deserialize)]
+pub struct UpdateMediaRequest {
+    pub alt_text: Option<String>
+}
@@ -1,3 +1,16 @@
 pub mod user;
 pub mod post;
 pub mod auth;
+pub mod session;
+pub mod api_token;
+pub mod follow;
+pub mod tag;
+pub mod search;
+pub mod media;
+pub mod poll;
+pub mod bookmark;
+pub mod notification;
+pub mod event;
+pub mod conversation;
+pub mod block;
+pub mod filter;
@@ -0,0 +1,70 @@
+use serde::{Serialize, Deserialize};
+use chrono::NaiveDateTime;
+use sqlx::types::Json;
+
+// What happened, stored in notifications.kind
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotificationKind {
+    Like,    // post_id: the liked post
+    Repost,  // post_id: the reposted post
+    Follow,  // no post
+    Reply,   // post_id: the post replied to
+    Quote,   // post_id: the quoted post
+    Mention, // post_id: the post with the mention
+}
+
+impl NotificationKind {
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NotificationKind::Like => "like",
+            NotificationKind::Repost => "repost",
+            NotificationKind::Follow => "follow",
+            NotificationKind::Reply => "reply",
+            NotificationKind::Quote => "quote",
+            NotificationKind::Mention => "mention",
+        }
+    }
+
+    // Notifications with the same key are grouped while unread
+    pub fn group_key(&self, post_id: Option<i32>) -> String {
+        match post_id {
+            Some(post_id) => format!("{}:{post_id}", self.as_str()),
+            None => self.as_str().to_string(),
+        }
+    }
+}
+
+// An user who did the action of a notification
+#[derive(Serialize, Deserialize, Debug)]
+pub struct NotificationActor {
+    pub id: i32,
+    pub username: String,
+    pub title: Option<String>
+}
+
+// A notification, "actors[0] and (actors_count - 1) others liked your post"
+#[derive(sqlx::FromRow, Serialize, Debug)]
+pub struct Notification {
+    pub id: i32,
+    pub kind: String,
+    pub post_id: Option<i32>,
+    pub post_content: Option<String>,
+    pub actors: Json<Vec<NotificationActor>>, // the most recent ones first, at most 3
+    pub actors_count: i64,
+    pub updated_at: NaiveDateTime,
+    pub is_read: bool,
+    pub filtered: Option<Vec<String>> // phrases of the "warn" filters of the user matching the post
+}
+
+// A page of notifications, most recent first
+#[derive(Serialize, Debug)]
+pub struct NotificationPage {
+    pub notifications: Vec<Notification>,
+    pub next_cursor: Option<String> // None if it's the last page
+}
+
+#[derive(Serialize, Debug)]
+pub struct UnreadCount {
+    pub count: i64
+}
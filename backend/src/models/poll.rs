@@ -0,0 +1,27 @@
+use chrono::NaiveDateTime;
+use serde::{Serialize, Deserialize};
+
+// Poll of a post, as sent to the clients (in PostWithUserData.poll)
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Poll {
+    pub multiple_choice: bool,
+    pub closes_at: NaiveDateTime,
+    pub is_closed: bool,
+    pub voters_count: i32,
+    pub auth_has_voted: bool,
+    pub options: Vec<PollOption>
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PollOption {
+    pub id: i32,
+    pub label: String,
+    pub votes_count: Option<i32>, // null until the user votes or the poll closes
+    pub auth_is_voted: bool
+}
+
+// Form client must send to vote
+#[derive(Deserialize)]
+pub struct FormVote {
+    pub option_ids: String // comma separated ids of the chosen options (only one without multiple_choice)
+}
@@ -1,5 +1,9 @@
 use serde::{Serialize, Deserialize};
 use chrono::NaiveDateTime;
+use sqlx::types::Json;
+use crate::entities::EntityKind;
+use crate::models::media::Media;
+use crate::models::poll::Poll;
 
 // Basic Post Strct reprenseting the SQL table datas
 #[derive(sqlx::FromRow, Serialize, Debug)]
@@ -8,7 +12,14 @@ pub struct Post {
     pub user_id: i32,
     pub content: String,
     pub created_at: Option<NaiveDateTime>,
-    pub likes_count: i32
+    pub likes_count: i32,
+    pub parent_id: Option<i32>,
+    pub root_id: Option<i32>,
+    pub replies_count: i32,
+    pub quoted_post_id: Option<i32>,
+    pub reposts_count: i32,
+    pub quotes_count: i32,
+    pub edited_at: Option<NaiveDateTime>
 }
 
 // Struct used to send the post data with the user data
@@ -19,16 +30,114 @@ pub struct PostWithUserData {
     pub content: String,
     pub created_at: Option<NaiveDateTime>,
     pub likes_count: i32,
+    pub parent_id: Option<i32>,
+    pub replies_count: i32,
     pub user_id: i32,
     pub user_username: String,
     pub user_title: Option<String>,
     pub user_created_at: Option<NaiveDateTime>,
-    pub auth_is_liked: bool
+    pub auth_is_liked: bool,
+    pub reposts_count: i32,
+    pub quotes_count: i32,
+    pub auth_is_reposted: bool,
+    pub auth_is_bookmarked: bool,
+    pub edited_at: Option<NaiveDateTime>,
+    pub entities: Json<Vec<PostEntity>>,
+    pub media: Json<Vec<Media>>,
+    pub poll: Option<Json<Poll>>,
+
+    // Embedded post of a quote post (quoted_is_available is false if it was deleted, null if not a quote)
+    pub quoted_post_id: Option<i32>,
+    pub quoted_is_available: Option<bool>,
+    pub quoted_content: Option<String>,
+    pub quoted_created_at: Option<NaiveDateTime>,
+    pub quoted_user_id: Option<i32>,
+    pub quoted_user_username: Option<String>,
+
+    // Only in the feeds, when the entry is a repost
+    #[sqlx(default)]
+    pub reposted_by_user_id: Option<i32>,
+    #[sqlx(default)]
+    pub reposted_by_username: Option<String>,
+    #[sqlx(default)]
+    pub reposted_at: Option<NaiveDateTime>,
+
+    // Only in the bookmarks of the user
+    #[sqlx(default)]
+    pub bookmarked_at: Option<NaiveDateTime>,
+
+    // Only in the feeds and the search, the phrases of the "warn" filters of the user matching the post
+    #[sqlx(default)]
+    pub filtered: Option<Vec<String>>
 }
 
 // JSON client must send to create a post (only content is necessary, auth is handled by
 // middleware)
 #[derive(Deserialize)]
 pub struct FormPost {
-    pub content: String
+    pub content: String,
+    pub media_ids: Option<String>, // comma separated ids of images uploaded with /media (ex: "12,13")
+    pub poll_options: Option<String>, // one option per line, 2 to 4 options
+    pub poll_duration_minutes: Option<i32>, // time before the poll closes (default: 1 day)
+    pub poll_multiple_choice: Option<bool>
+}
+
+// Hashtag, mention or URL found in the content, so clients can render links
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PostEntity {
+    pub kind: EntityKind,
+    pub start: i32,           // offset in characters
+    pub end: i32,             // excluded
+    pub value: String,        // tag (lowercase), username or URL
+    pub user_id: Option<i32>  // mentioned user
+}
+
+// A previous version of an edited post
+#[derive(sqlx::FromRow, Serialize, Debug)]
+pub struct PostRevision {
+    pub id: i32,
+    pub content: String,
+    pub created_at: NaiveDateTime,
+    pub replaced_at: NaiveDateTime
+}
+
+// A post of a thread, or the place of a deleted one (is_available is always false, only its ids are left)
+#[derive(Serialize, Debug)]
+#[serde(untagged)]
+pub enum ThreadPost {
+    Available(Box<PostWithUserData>),
+    Unavailable { id: i32, parent_id: Option<i32>, is_available: bool }
+}
+
+impl ThreadPost {
+
+    pub fn id(&self) -> i32 {
+        match self {
+            ThreadPost::Available(post) => post.id,
+            ThreadPost::Unavailable { id, .. } => *id
+        }
+    }
+
+    pub fn parent_id(&self) -> Option<i32> {
+        match self {
+            ThreadPost::Available(post) => post.parent_id,
+            ThreadPost::Unavailable { parent_id, .. } => *parent_id
+        }
+    }
+}
+
+// A reply in a thread, with its own replies
+#[derive(Serialize, Debug)]
+pub struct ThreadNode {
+    #[serde(flatten)]
+    pub post: ThreadPost,
+    pub replies: Vec<ThreadNode>
+}
+
+// Conversation around a post: the posts it replies to (oldest first) and the tree of its replies
+#[derive(Serialize, Debug)]
+pub struct PostThread {
+    pub ancestors: Vec<ThreadPost>,
+    pub post: PostWithUserData,
+    pub replies: Vec<ThreadNode>
 }
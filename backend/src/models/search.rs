@@ -0,0 +1,39 @@
+use serde::{Serialize, Deserialize};
+use chrono::NaiveDateTime;
+
+use crate::models::post::PostWithUserData;
+
+// Parameters of /search (q can contain filters, see search::parse_search_query)
+#[derive(Deserialize)]
+pub struct SearchParams {
+    pub q: String,
+    pub offset: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+// A post found by a search, with the matching words highlighted in the snippet (<mark>...</mark>)
+#[derive(sqlx::FromRow, Serialize, Debug)]
+pub struct SearchPost {
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub post: PostWithUserData,
+    pub rank: f32,
+    pub snippet: String
+}
+
+// An user whose username or title looks like the searched text
+#[derive(sqlx::FromRow, Serialize, Debug)]
+pub struct SearchUser {
+    pub id: i32,
+    pub username: String,
+    pub title: Option<String>,
+    pub created_at: Option<NaiveDateTime>,
+    pub followers_count: i32,
+    pub similarity: f32
+}
+
+#[derive(Serialize, Debug)]
+pub struct SearchResults {
+    pub posts: Vec<SearchPost>,
+    pub users: Vec<SearchUser>
+}
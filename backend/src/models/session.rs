@@ -0,0 +1,13 @@
+use serde::Serialize;
+use chrono::NaiveDateTime;
+
+// Struct representing a login session (a device) of the connected user
+#[derive(sqlx::FromRow, Serialize, Debug)]
+pub struct Session {
+    pub id: i32,
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+    pub created_at: Option<NaiveDateTime>,
+    pub last_seen_at: Option<NaiveDateTime>,
+    pub is_current: bool // true if this is the session used to make the request
+}
@@ -0,0 +1,20 @@
+use serde::Serialize;
+
+use crate::models::post::PostWithUserData;
+
+// A trending hashtag, see the trending_hashtags view
+#[derive(sqlx::FromRow, Serialize, Debug)]
+pub struct Trend {
+    pub tag: String,
+    pub recent_count: i64,  // authors who used it in the last hour
+    pub baseline_rate: f64, // authors per hour during the 24 hours before
+    pub score: f64
+}
+
+// A page of the posts with a hashtag
+#[derive(Serialize, Debug)]
+pub struct TagPosts {
+    pub tag: String,
+    pub posts: Vec<PostWithUserData>,
+    pub next_cursor: Option<String> // None if it's the last page
+}
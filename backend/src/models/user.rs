@@ -9,7 +9,12 @@ pub struct User {
     pub email: String,
     pub title: Option<String>,
     pub created_at: Option<NaiveDateTime>,
-    pub is_admin: bool
+    pub role: String,   // user, moderator or admin
+    pub is_admin: bool, // true for the admin role
+    pub email_verified_at: Option<NaiveDateTime>,
+    pub suspended_at: Option<NaiveDateTime>,
+    pub followers_count: i32,
+    pub following_count: i32
 }
 
 // JSON client must send to create an user
@@ -19,7 +24,7 @@ pub struct FormCreateUser {
     pub email: String,
     pub password: String,
     pub title: Option<String>,
-    pub is_admin: bool
+    pub role: Option<String> // None for the default role (create) or to keep the current one (update)
 }
 
 #[derive(Deserialize)]
@@ -28,5 +33,17 @@ pub struct FormUpdateUser {
     pub email: String,
     pub password: Option<String>,
     pub title: Option<String>,
-    pub is_admin: bool
+    pub role: Option<String> // None for the default role (create) or to keep the current one (update)
+}
+
+// Privacy settings of the connected user
+#[derive(sqlx::FromRow, Serialize, Debug)]
+pub struct UserSettings {
+    pub dm_policy: String // who can message him: everyone, following (users he follows) or nobody
+}
+
+// JSON client must send to change his settings (None keeps the current value)
+#[derive(Deserialize)]
+pub struct UpdateSettingsRequest {
+    pub dm_policy: Option<String>
 }
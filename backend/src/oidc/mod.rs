@@ -0,0 +1,396 @@
+use std::env;
+use std::sync::Arc;
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation, jwk::JwkSet};
+use rand::{rngs::OsRng, RngCore};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use tokio::sync::OnceCell;
+
+use crate::mail::app_url;
+
+pub type OidcError = Box<dyn std::error::Error + Send + Sync>;
+
+// Default scopes asked to the provider, can be overridden with OIDC_SCOPES
+const DEFAULT_SCOPES: &str = "openid email profile";
+
+// Algorithms accepted for the ID tokens (never "none" or a shared secret)
+const ID_TOKEN_ALGORITHMS: [Algorithm; 7] = [
+    Algorithm::RS256, Algorithm::RS384, Algorithm::RS512,
+    Algorithm::PS256, Algorithm::ES256, Algorithm::ES384, Algorithm::EdDSA,
+];
+
+// Endpoints of the provider, read from {issuer}/.well-known/openid-configuration
+#[derive(Debug, Deserialize)]
+struct Discovery {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+#[derive(Deserialize)]
+struct TokenEndpointResponse {
+    id_token: String,
+}
+
+// Claims of the ID token we use
+#[derive(Debug, Clone, Deserialize)]
+pub struct IdTokenClaims {
+    pub sub: String,
+    pub nonce: Option<String>,
+    pub email: Option<String>,
+    #[serde(default)]
+    pub email_verified: bool,
+    pub preferred_username: Option<String>,
+    pub name: Option<String>,
+}
+
+/*
+ * An OpenID Connect provider (authorization code flow with PKCE)
+ * - Only http requests are made, so any issuer can be used (ex: a local mock issuer for tests)
+ */
+pub struct OidcProvider {
+    issuer: String,
+    client_id: String,
+    client_secret: Option<String>,
+    redirect_uri: String,
+    scopes: String,
+    http: reqwest::Client,
+    discovery: OnceCell<Discovery>, // fetched on first use, the provider may start after us
+}
+
+/*
+ * Create the provider configured in the env, None if OIDC_ISSUER is not set (OIDC login disabled)
+ * - OIDC_ISSUER, OIDC_CLIENT_ID, OIDC_CLIENT_SECRET (optional, for confidential clients)
+ * - OIDC_REDIRECT_URI : page of the client receiving the code (default: APP_URL/oidc/callback)
+ */
+pub fn oidc_from_env() -> Option<Arc<OidcProvider>> {
+
+    let issuer = env::var("OIDC_ISSUER").ok().filter(|issuer| !issuer.is_empty())?;
+
+    let client_id = env::var("OIDC_CLIENT_ID").expect("OIDC_CLIENT_ID must be set when OIDC_ISSUER is set");
+
+    Some(Arc::new(OidcProvider {
+        issuer: issuer.trim_end_matches('/').to_string(),
+        client_id,
+        client_secret: env::var("OIDC_CLIENT_SECRET").ok().filter(|secret| !secret.is_empty()),
+        redirect_uri: env::var("OIDC_REDIRECT_URI").unwrap_or_else(|_| format!("{}/oidc/callback", app_url())),
+        scopes: env::var("OIDC_SCOPES").unwrap_or_else(|_| DEFAULT_SCOPES.to_string()),
+        http: reqwest::Client::new(),
+        discovery: OnceCell::new(),
+    }))
+}
+
+impl OidcProvider {
+
+    pub fn issuer(&self) -> &str {
+        &self.issuer
+    }
+
+    async fn discovery(&self) -> Result<&Discovery, OidcError> {
+
+        self.discovery.get_or_try_init(|| async {
+
+            let url = format!("{}/.well-known/openid-configuration", self.issuer);
+
+            let discovery: Discovery = self.http.get(url).send().await?
+                .error_for_status()?
+                .json().await?;
+
+            // The provider must announce the issuer we trust
+            if discovery.issuer.trim_end_matches('/') != self.issuer {
+                return Err(format!("discovery issuer {} doesn't match OIDC_ISSUER", discovery.issuer).into());
+            }
+
+            Ok(discovery)
+        })
+        .await
+    }
+
+    /*
+     * URL where the user must be sent to log in on the provider
+     */
+    pub async fn authorization_url(&self, state: &str, nonce: &str, code_verifier: &str) -> Result<String, OidcError> {
+
+        let discovery = self.discovery().await?;
+
+        let mut url = reqwest::Url::parse(&discovery.authorization_endpoint)?;
+
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &self.client_id)
+            .append_pair("redirect_uri", &self.redirect_uri)
+            .append_pair("scope", &self.scopes)
+            .append_pair("state", state)
+            .append_pair("nonce", nonce)
+            .append_pair("code_challenge", &code_challenge(code_verifier))
+            .append_pair("code_challenge_method", "S256");
+
+        Ok(url.to_string())
+    }
+
+    /*
+     * Exchange an authorization code and return the verified claims of the ID token
+     */
+    pub async fn exchange_code(&self, code: &str, code_verifier: &str, nonce: &str) -> Result<IdTokenClaims, OidcError> {
+
+        let discovery = self.discovery().await?;
+
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", &self.redirect_uri),
+            ("client_id", &self.client_id),
+            ("code_verifier", code_verifier),
+        ];
+
+        if let Some(client_secret) = &self.client_secret {
+            form.push(("client_secret", client_secret));
+        }
+
+        let response: TokenEndpointResponse = self.http.post(&discovery.token_endpoint)
+            .form(&form)
+            .send().await?
+            .error_for_status()?
+            .json().await?;
+
+        let claims = self.verify_id_token(&response.id_token, &discovery.jwks_uri).await?;
+
+        // The ID token must be the one of this login
+        if claims.nonce.as_deref() != Some(nonce) {
+            return Err("invalid nonce in the ID token".into());
+        }
+
+        Ok(claims)
+    }
+
+    /*
+     * Check the signature (with the provider keys), the issuer, the audience and the expiration of an ID token
+     */
+    async fn verify_id_token(&self, id_token: &str, jwks_uri: &str) -> Result<IdTokenClaims, OidcError> {
+
+        let header = decode_header(id_token)?;
+
+        if !ID_TOKEN_ALGORITHMS.contains(&header.alg) {
+            return Err(format!("ID token algorithm {:?} is not allowed", header.alg).into());
+        }
+
+        // Keys are fetched each time, logins are rare and the provider can rotate them
+        let jwks: JwkSet = self.http.get(jwks_uri).send().await?
+            .error_for_status()?
+            .json().await?;
+
+        let jwk = match &header.kid {
+            Some(kid) => jwks.find(kid),
+            None if jwks.keys.len() == 1 => jwks.keys.first(),
+            None => None,
+        }
+        .ok_or("no key found for the ID token")?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&self.issuer, &format!("{}/", self.issuer)]);
+        validation.set_audience(&[&self.client_id]);
+
+        let data = decode::<IdTokenClaims>(id_token, &DecodingKey::from_jwk(jwk)?, &validation)?;
+
+        Ok(data.claims)
+    }
+}
+
+/*
+ * Generate a random value for the state, the nonce or the PKCE code verifier (256 bits, base64url)
+ */
+pub fn generate_random_value() -> String {
+
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/*
+ * PKCE code challenge of a verifier (S256 method, RFC 7636)
+ */
+fn code_challenge(code_verifier: &str) -> String {
+
+    URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+    use axum::{Form, Json, Router, extract::{Query, State}, http::StatusCode, response::Redirect, routing::{get, post}};
+    use ed25519_dalek::{SigningKey, pkcs8::EncodePrivateKey};
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use serde_json::{json, Value};
+
+    const CLIENT_ID: &str = "feur-test";
+    const REDIRECT_URI: &str = "http://127.0.0.1:8000/oidc/callback";
+
+    // Local mock issuer: approves every login, signs the ID tokens with an Ed25519 key
+    struct MockIssuer {
+        url: String,
+        encoding: EncodingKey,
+        jwk: Value,
+        codes: Mutex<HashMap<String, (String, String)>>, // code -> (code challenge, nonce)
+    }
+
+    async fn start_mock_issuer() -> String {
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        let signing_key = SigningKey::generate(&mut OsRng);
+
+        let issuer = Arc::new(MockIssuer {
+            url: url.clone(),
+            encoding: EncodingKey::from_ed_der(signing_key.to_pkcs8_der().unwrap().as_bytes()),
+            jwk: json!({
+                "kty": "OKP", "crv": "Ed25519", "alg": "EdDSA", "use": "sig", "kid": "mock-key",
+                "x": URL_SAFE_NO_PAD.encode(signing_key.verifying_key().as_bytes()),
+            }),
+            codes: Mutex::new(HashMap::new()),
+        });
+
+        let app = Router::new()
+            .route("/.well-known/openid-configuration", get(mock_discovery))
+            .route("/authorize", get(mock_authorize))
+            .route("/token", post(mock_token))
+            .route("/jwks", get(mock_jwks))
+            .with_state(issuer);
+
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        url
+    }
+
+    async fn mock_discovery(State(issuer): State<Arc<MockIssuer>>) -> Json<Value> {
+
+        Json(json!({
+            "issuer": issuer.url,
+            "authorization_endpoint": format!("{}/authorize", issuer.url),
+            "token_endpoint": format!("{}/token", issuer.url),
+            "jwks_uri": format!("{}/jwks", issuer.url),
+        }))
+    }
+
+    async fn mock_authorize(State(issuer): State<Arc<MockIssuer>>, Query(query): Query<HashMap<String, String>>) -> Redirect {
+
+        assert_eq!(query["client_id"], CLIENT_ID);
+        assert_eq!(query["code_challenge_method"], "S256");
+
+        let code = generate_random_value();
+        issuer.codes.lock().unwrap().insert(code.clone(), (query["code_challenge"].clone(), query["nonce"].clone()));
+
+        Redirect::to(&format!("{}?code={code}&state={}", query["redirect_uri"], query["state"]))
+    }
+
+    async fn mock_token(State(issuer): State<Arc<MockIssuer>>, Form(form): Form<HashMap<String, String>>) -> Result<Json<Value>, StatusCode> {
+
+        let (challenge, nonce) = issuer.codes.lock().unwrap().remove(&form["code"])
+            .ok_or(StatusCode::BAD_REQUEST)?;
+
+        // PKCE: the verifier must match the challenge sent to /authorize
+        if code_challenge(&form["code_verifier"]) != challenge || form["redirect_uri"] != REDIRECT_URI {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+
+        let now = chrono::Utc::now().timestamp();
+
+        let claims = json!({
+            "iss": issuer.url, "aud": form["client_id"], "sub": "mock-user", "iat": now, "exp": now + 300,
+            "nonce": nonce, "email": "mock@example.com", "email_verified": true, "preferred_username": "mock",
+        });
+
+        let mut header = Header::new(Algorithm::EdDSA);
+        header.kid = Some(String::from("mock-key"));
+
+        let id_token = encode(&header, &claims, &issuer.encoding).unwrap();
+
+        Ok(Json(json!({ "access_token": "mock", "token_type": "Bearer", "id_token": id_token })))
+    }
+
+    async fn mock_jwks(State(issuer): State<Arc<MockIssuer>>) -> Json<Value> {
+
+        Json(json!({ "keys": [issuer.jwk] }))
+    }
+
+    fn provider(issuer: &str) -> OidcProvider {
+
+        OidcProvider {
+            issuer: issuer.to_string(),
+            client_id: String::from(CLIENT_ID),
+            client_secret: None,
+            redirect_uri: String::from(REDIRECT_URI),
+            scopes: String::from(DEFAULT_SCOPES),
+            http: reqwest::Client::new(),
+            discovery: OnceCell::new(),
+        }
+    }
+
+    // Follow the authorization URL like the browser, return the code and the state given to OIDC_REDIRECT_URI
+    async fn authorize(provider: &OidcProvider, state: &str, nonce: &str, code_verifier: &str) -> (String, String) {
+
+        let url = provider.authorization_url(state, nonce, code_verifier).await.unwrap();
+
+        let client = reqwest::Client::builder().redirect(reqwest::redirect::Policy::none()).build().unwrap();
+        let response = client.get(url).send().await.unwrap();
+
+        let location = reqwest::Url::parse(response.headers()["location"].to_str().unwrap()).unwrap();
+        assert!(location.as_str().starts_with(REDIRECT_URI));
+
+        let query: HashMap<String, String> = location.query_pairs().into_owned().collect();
+
+        (query["code"].clone(), query["state"].clone())
+    }
+
+    #[tokio::test]
+    async fn logs_in_with_the_mock_issuer() {
+
+        let provider = provider(&start_mock_issuer().await);
+        let (state, nonce, code_verifier) = (generate_random_value(), generate_random_value(), generate_random_value());
+
+        let (code, returned_state) = authorize(&provider, &state, &nonce, &code_verifier).await;
+        assert_eq!(returned_state, state);
+
+        let claims = provider.exchange_code(&code, &code_verifier, &nonce).await.unwrap();
+
+        assert_eq!(claims.sub, "mock-user");
+        assert_eq!(claims.email.as_deref(), Some("mock@example.com"));
+        assert!(claims.email_verified);
+    }
+
+    #[tokio::test]
+    async fn rejects_a_wrong_code_verifier() {
+
+        let provider = provider(&start_mock_issuer().await);
+        let nonce = generate_random_value();
+
+        let (code, _) = authorize(&provider, "state", &nonce, &generate_random_value()).await;
+
+        assert!(provider.exchange_code(&code, &generate_random_value(), &nonce).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn rejects_an_id_token_of_another_login() {
+
+        let provider = provider(&start_mock_issuer().await);
+        let code_verifier = generate_random_value();
+
+        let (code, _) = authorize(&provider, "state", &generate_random_value(), &code_verifier).await;
+
+        assert!(provider.exchange_code(&code, &code_verifier, &generate_random_value()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn rejects_an_issuer_announcing_another_name() {
+
+        let issuer = start_mock_issuer().await;
+        let provider = provider(&issuer.replace("127.0.0.1", "localhost"));
+
+        assert!(provider.authorization_url("state", "nonce", "verifier").await.is_err());
+    }
+}
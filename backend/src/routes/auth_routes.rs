@@ -1,12 +1,50 @@
-use axum::{Router, routing::post};
-use sqlx::PgPool;
+use axum::{Router, routing::{get, post, delete}, middleware};
+use crate::state::AppState;
 
-use crate::handlers::auth_handlers::login;
+use crate::auth::middleware::get_auth_user;
+
+use crate::handlers::auth_handlers::{login, refresh, logout};
+use crate::handlers::password_handlers::{forgot_password, reset_password};
+use crate::handlers::email_handlers::{verify_email, resend_verification};
+use crate::handlers::two_factor_handlers::{enroll, confirm, disable, login_2fa};
+use crate::handlers::oidc_handlers::{oidc_authorize, oidc_callback};
+
+/*
+ * All routes that DOESNT need you to be auth
+ */
+fn public_routes() -> Router<AppState> {
+
+    Router::new()
+        .route("/login", post(login))
+        .route("/login/2fa", post(login_2fa))
+        .route("/refresh", post(refresh))
+        .route("/logout", post(logout))
+        .route("/password/forgot", post(forgot_password))
+        .route("/password/reset", post(reset_password))
+        .route("/verify-email", post(verify_email))
+        .route("/oidc/authorize", get(oidc_authorize))
+        .route("/oidc/callback", post(oidc_callback))
+}
+
+/*
+ * All routes that NEED you to be auth
+ * - Each request is gonna get trought a middleware to ensure the user authentification
+ */
+fn protected_routes(state: AppState) -> Router<AppState> {
+
+    Router::new()
+        .route("/verify-email/resend", post(resend_verification))
+        .route("/2fa/enroll", post(enroll))
+        .route("/2fa/confirm", post(confirm))
+        .route("/2fa", delete(disable))
+        .route_layer(middleware::from_fn_with_state(state, get_auth_user))
+}
 
 /*
  * Public function to expose routes for main.rs
  */
-pub fn routes() -> Router<PgPool> {
+pub fn routes(state: AppState) -> Router<AppState> {
 
-    Router::new().route("/login", post(login))
+    // Merge both public & protected routes
+    public_routes().merge(protected_routes(state))
 }
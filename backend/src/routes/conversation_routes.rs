@@ -0,0 +1,35 @@
+use axum::{routing::{get, post}, Router, middleware};
+use crate::state::AppState;
+
+use crate::auth::middleware::get_auth_user;
+
+use crate::handlers::conversation_handlers::{
+    list_conversations,
+    create_conversation,
+    get_by_id,
+    list_messages,
+    send_message,
+    mark_read
+};
+
+/*
+ * All routes that NEED you to be auth
+ * - Each request is gonna get trought a middleware to ensure the user authentification
+ */
+fn protected_routes(state: AppState) -> Router<AppState> {
+
+    Router::new()
+        .route("/", get(list_conversations).post(create_conversation))
+        .route("/{id}", get(get_by_id))
+        .route("/{id}/messages", get(list_messages).post(send_message))
+        .route("/{id}/read", post(mark_read))
+        .route_layer(middleware::from_fn_with_state(state, get_auth_user))
+}
+
+/*
+ * Public function to expose routes for main.rs
+ */
+pub fn routes(state: AppState) -> Router<AppState> {
+
+    protected_routes(state)
+}
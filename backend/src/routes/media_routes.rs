@@ -0,0 +1,42 @@
+use axum::{routing::{get, post, put}, Router, middleware, extract::DefaultBodyLimit};
+use crate::state::AppState;
+
+use crate::auth::middleware::get_auth_user;
+use crate::media::max_upload_bytes;
+
+use crate::handlers::media_handlers::{
+    upload_media,
+    update_media,
+    serve_media
+};
+
+/*
+ * All routes that DOESNT need you to be auth
+ */
+pub fn public_routes() -> Router<AppState> {
+
+    Router::new()
+        .route("/files/{*key}", get(serve_media))
+}
+
+/*
+ * All routes that NEED you to be auth
+ * - Each request is gonna get trought a middleware to ensure the user authentification
+ */
+fn protected_routes(state: AppState) -> Router<AppState> {
+
+    Router::new()
+        // The body can be larger than the default limit (2MB), with some room for the other fields
+        .route("/", post(upload_media).layer(DefaultBodyLimit::max(max_upload_bytes() + 64 * 1024)))
+        .route("/{id}", put(update_media))
+        .route_layer(middleware::from_fn_with_state(state, get_auth_user))
+}
+
+/*
+ * Public function to expose routes for main.rs
+ */
+pub fn routes(state: AppState) -> Router<AppState> {
+
+    // Merge both public & protected routes
+    public_routes().merge(protected_routes(state))
+}
@@ -1,3 +1,10 @@
 pub mod user_routes;
 pub mod post_routes;
 pub mod auth_routes;
+pub mod tag_routes;
+pub mod trend_routes;
+pub mod search_routes;
+pub mod media_routes;
+pub mod notification_routes;
+pub mod stream_routes;
+pub mod conversation_routes;
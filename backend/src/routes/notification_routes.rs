@@ -0,0 +1,33 @@
+use axum::{routing::{get, post}, Router, middleware};
+use crate::state::AppState;
+
+use crate::auth::middleware::get_auth_user;
+
+use crate::handlers::notification_handlers::{
+    list_notifications,
+    unread_count,
+    mark_read,
+    mark_all_read
+};
+
+/*
+ * All routes that NEED you to be auth
+ * - Each request is gonna get trought a middleware to ensure the user authentification
+ */
+fn protected_routes(state: AppState) -> Router<AppState> {
+
+    Router::new()
+        .route("/", get(list_notifications))
+        .route("/unread_count", get(unread_count))
+        .route("/read_all", post(mark_all_read))
+        .route("/{id}/read", post(mark_read))
+        .route_layer(middleware::from_fn_with_state(state, get_auth_user))
+}
+
+/*
+ * Public function to expose routes for main.rs
+ */
+pub fn routes(state: AppState) -> Router<AppState> {
+
+    protected_routes(state)
+}
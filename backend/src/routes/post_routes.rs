@@ -1,24 +1,39 @@
 use axum::{routing::{get, post, delete}, Router, middleware};
-use sqlx::PgPool;
+use crate::state::AppState;
 
 use crate::auth::middleware::get_auth_user;
 
 use crate::handlers::post_handlers::{
     list,
+    feed,
     get_by_id,
     create_post,
+    reply_post,
+    quote_post,
+    edit_post,
+    list_revisions,
+    get_thread,
     delete_post,
     like_post,
-    unlike_post
+    unlike_post,
+    repost_post,
+    unrepost_post
+};
+
+use crate::handlers::poll_handlers::vote_poll;
+
+use crate::handlers::bookmark_handlers::{
+    bookmark_post,
+    unbookmark_post
 };
 
 /*
  * All routes that DOESNT need you to be auth
  */
-pub fn public_routes() -> Router<PgPool> {
+pub fn public_routes() -> Router<AppState> {
 
+    // There is no public routes (get_by_id needs the user, to hide the posts of blocked users)
     Router::new()
-        .route("/{id}", get(get_by_id)) // Unused routes, still here but must update ()
 }
 
 
@@ -26,22 +41,34 @@ pub fn public_routes() -> Router<PgPool> {
  * All routes that NEED you to be auth
  * - Each request is gonna get trought a middleware to ensure the user authentification
  */
-fn protected_routes() -> Router<PgPool> {
+fn protected_routes(state: AppState) -> Router<AppState> {
 
     Router::new()
         .route("/", get(list))
+        .route("/explore", get(list))
+        .route("/feed", get(feed))
         .route("/create", post(create_post))
+        .route("/{id}/reply", post(reply_post))
+        .route("/{id}/quote", post(quote_post))
+        .route("/{id}", get(get_by_id).put(edit_post))
+        .route("/{id}/revisions", get(list_revisions))
+        .route("/{id}/thread", get(get_thread))
         .route("/delete/{id}", delete(delete_post))
         .route("/like/{id}", get(like_post))
         .route("/unlike/{id}", get(unlike_post))
-        .route_layer(middleware::from_fn(get_auth_user))
+        .route("/repost/{id}", post(repost_post))
+        .route("/unrepost/{id}", post(unrepost_post))
+        .route("/{id}/vote", post(vote_poll))
+        .route("/bookmark/{id}", post(bookmark_post))
+        .route("/unbookmark/{id}", post(unbookmark_post))
+        .route_layer(middleware::from_fn_with_state(state, get_auth_user))
 }
 
 /*
  * Public function to expose routes for main.rs
  */
-pub fn routes() -> Router<PgPool> {
+pub fn routes(state: AppState) -> Router<AppState> {
 
     // Merge both public & protected routes
-    public_routes().merge(protected_routes())
+    public_routes().merge(protected_routes(state))
 }
@@ -0,0 +1,25 @@
+use axum::{routing::get, Router, middleware};
+use crate::state::AppState;
+
+use crate::auth::middleware::get_auth_user;
+
+use crate::handlers::search_handlers::search;
+
+/*
+ * All routes that NEED you to be auth
+ * - Each request is gonna get trought a middleware to ensure the user authentification
+ */
+fn protected_routes(state: AppState) -> Router<AppState> {
+
+    Router::new()
+        .route("/", get(search))
+        .route_layer(middleware::from_fn_with_state(state, get_auth_user))
+}
+
+/*
+ * Public function to expose routes for main.rs
+ */
+pub fn routes(state: AppState) -> Router<AppState> {
+
+    protected_routes(state)
+}
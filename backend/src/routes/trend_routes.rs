@@ -0,0 +1,21 @@
+use axum::{routing::get, Router};
+use crate::state::AppState;
+
+use crate::handlers::tag_handlers::list_trends;
+
+/*
+ * All routes that DOESNT need you to be auth
+ */
+fn public_routes() -> Router<AppState> {
+
+    Router::new()
+        .route("/", get(list_trends)) // Trending hashtags
+}
+
+/*
+ * Public function to expose routes for main.rs
+ */
+pub fn routes() -> Router<AppState> {
+
+    public_routes()
+}
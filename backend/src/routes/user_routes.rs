@@ -1,5 +1,5 @@
 use axum::{routing::{get, post, delete, put}, Router, middleware};
-use sqlx::PgPool;
+use crate::state::AppState;
 
 use crate::auth::middleware::get_auth_user;
 
@@ -9,13 +9,53 @@ use crate::handlers::user_handlers::{
     create_user,
     delete_user,
     update_user,
-    get_connected
+    suspend_user,
+    unsuspend_user,
+    get_connected,
+    get_settings,
+    update_settings
+};
+
+use crate::handlers::session_handlers::{
+    list_sessions,
+    revoke_session,
+    revoke_other_sessions
+};
+
+use crate::handlers::follow_handlers::{
+    follow_user,
+    unfollow_user,
+    list_followers,
+    list_following
+};
+
+use crate::handlers::block_handlers::{
+    block_user,
+    unblock_user,
+    mute_user,
+    unmute_user,
+    list_blocks,
+    list_mutes
+};
+
+use crate::handlers::filter_handlers::{
+    list_filters,
+    create_filter,
+    delete_filter
+};
+
+use crate::handlers::bookmark_handlers::list_bookmarks;
+
+use crate::handlers::api_token_handlers::{
+    list_tokens,
+    create_token,
+    revoke_token
 };
 
 /*
  * All routes that DOESNT need you to be auth
  */
-fn public_routes() -> Router<PgPool> {
+fn public_routes() -> Router<AppState> {
 
     // There is no public routes (but there was !)
     Router::new()
@@ -25,24 +65,44 @@ fn public_routes() -> Router<PgPool> {
  * All routes that NEED you to be auth
  * - Each request is gonna get trought a middleware to ensure the user authentification
  */
-fn protected_routes() -> Router<PgPool> {
+fn protected_routes(state: AppState) -> Router<AppState> {
 
     Router::new()
         .route("/", get(list))
         .route("/{id}", get(get_by_id))
         .route("/me", get(get_connected))
+        .route("/me/sessions", get(list_sessions).delete(revoke_other_sessions))
+        .route("/me/sessions/{id}", delete(revoke_session))
+        .route("/me/tokens", get(list_tokens).post(create_token))
+        .route("/me/tokens/{id}", delete(revoke_token))
+        .route("/me/bookmarks", get(list_bookmarks))
+        .route("/me/settings", get(get_settings).put(update_settings))
+        .route("/me/blocks", get(list_blocks))
+        .route("/me/mutes", get(list_mutes))
+        .route("/me/filters", get(list_filters).post(create_filter))
+        .route("/me/filters/{id}", delete(delete_filter))
         .route("/create", post(create_user))
         .route("/delete/{id}", delete(delete_user))
         .route("/update/{id}", put(update_user))
-        .route_layer(middleware::from_fn(get_auth_user))
+        .route("/suspend/{id}", post(suspend_user))
+        .route("/unsuspend/{id}", post(unsuspend_user))
+        .route("/follow/{id}", post(follow_user))
+        .route("/unfollow/{id}", post(unfollow_user))
+        .route("/block/{id}", post(block_user))
+        .route("/unblock/{id}", post(unblock_user))
+        .route("/mute/{id}", post(mute_user))
+        .route("/unmute/{id}", post(unmute_user))
+        .route("/{id}/followers", get(list_followers))
+        .route("/{id}/following", get(list_following))
+        .route_layer(middleware::from_fn_with_state(state, get_auth_user))
 }
 
 /*
  * Public function to expose routes for main.rs
  */
-pub fn routes() -> Router<PgPool> {
+pub fn routes(state: AppState) -> Router<AppState> {
 
     // Merge boths routes
-    public_routes().merge(protected_routes())
+    public_routes().merge(protected_routes(state))
 }
 
@@ -0,0 +1,99 @@
+use chrono::{NaiveDate, NaiveDateTime};
+
+/*
+ * A search query split into its text and its filters
+ * - from:<username> (with or without @), since:<YYYY-MM-DD>, has:media, has:links
+ * - Everything else is the text, searched with websearch_to_tsquery ("quotes", OR, -word work)
+ */
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct SearchQuery {
+    pub text: String,
+    pub from: Option<String>,
+    pub since: Option<NaiveDateTime>,
+    pub has_media: bool,
+    pub has_links: bool,
+}
+
+impl SearchQuery {
+
+    pub fn has_filters(&self) -> bool {
+        self.from.is_some() || self.since.is_some() || self.has_media || self.has_links
+    }
+}
+
+/*
+ * Parse a search query, Err with the invalid filter if there is one
+ */
+pub fn parse_search_query(query: &str) -> Result<SearchQuery, String> {
+
+    let mut search = SearchQuery::default();
+    let mut words = Vec::new();
+
+    for word in query.split_whitespace() {
+
+        match word.split_once(':') {
+
+            Some(("from", username)) if !username.is_empty() => {
+                search.from = Some(username.trim_start_matches('@').to_string());
+            }
+
+            Some(("since", date)) => {
+                let date = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                    .map_err(|_| word.to_string())?;
+                search.since = Some(date.and_time(chrono::NaiveTime::MIN));
+            }
+
+            Some(("has", "media")) => search.has_media = true,
+            Some(("has", "links")) => search.has_links = true,
+            Some(("has", _)) => return Err(word.to_string()),
+
+            // Not a filter (ex: an URL or "12:30")
+            _ => words.push(word),
+        }
+    }
+
+    search.text = words.join(" ");
+
+    Ok(search)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_only() {
+
+        let search = parse_search_query("  rust   async ").unwrap();
+
+        assert_eq!(search, SearchQuery { text: String::from("rust async"), ..Default::default() });
+        assert!(!search.has_filters());
+    }
+
+    #[test]
+    fn filters() {
+
+        let search = parse_search_query("from:@Alice \"hello world\" since:2024-02-29 has:media has:links").unwrap();
+
+        assert_eq!(search.text, "\"hello world\"");
+        assert_eq!(search.from.as_deref(), Some("Alice"));
+        assert_eq!(search.since, NaiveDate::from_ymd_opt(2024, 2, 29).map(|date| date.and_time(chrono::NaiveTime::MIN)));
+        assert!(search.has_media && search.has_links);
+    }
+
+    #[test]
+    fn invalid_filters() {
+
+        assert_eq!(parse_search_query("since:2023-02-29"), Err(String::from("since:2023-02-29")));
+        assert_eq!(parse_search_query("rust has:cats"), Err(String::from("has:cats")));
+    }
+
+    #[test]
+    fn colons_in_the_text() {
+
+        let search = parse_search_query("meeting at 12:30 from: https://example.com").unwrap();
+
+        assert_eq!(search.text, "meeting at 12:30 from: https://example.com");
+        assert!(!search.has_filters());
+    }
+}
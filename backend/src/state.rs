@@ -0,0 +1,54 @@
+use std::sync::Arc;
+use axum::extract::FromRef;
+use sqlx::PgPool;
+
+use crate::mail::Mailer;
+use crate::oidc::OidcProvider;
+use crate::storage::Storage;
+use crate::stream::EventSender;
+
+// Everything shared between the handlers
+// - Handlers can still extract only what they need (ex: State<PgPool>)
+#[derive(Clone)]
+pub struct AppState {
+    pub pool: PgPool,
+    pub mailer: Arc<dyn Mailer>,
+    pub oidc: Option<Arc<OidcProvider>>, // None if OIDC login is not configured
+    pub storage: Arc<dyn Storage>,
+    pub events: EventSender, // events received from the "events" channel, for /stream
+}
+
+impl FromRef<AppState> for PgPool {
+
+    fn from_ref(state: &AppState) -> Self {
+        state.pool.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<dyn Mailer> {
+
+    fn from_ref(state: &AppState) -> Self {
+        state.mailer.clone()
+    }
+}
+
+impl FromRef<AppState> for Option<Arc<OidcProvider>> {
+
+    fn from_ref(state: &AppState) -> Self {
+        state.oidc.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<dyn Storage> {
+
+    fn from_ref(state: &AppState) -> Self {
+        state.storage.clone()
+    }
+}
+
+impl FromRef<AppState> for EventSender {
+
+    fn from_ref(state: &AppState) -> Self {
+        state.events.clone()
+    }
+}
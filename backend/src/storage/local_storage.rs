@@ -0,0 +1,71 @@
+use std::env;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use async_trait::async_trait;
+
+use crate::storage::{Storage, StorageError};
+
+/*
+ * Storage writing the files in a local directory
+ * - Files are served by the backend itself (GET /media/files/{key}), useful when working locally
+ */
+pub struct LocalStorage {
+    dir: PathBuf,
+    public_url: String,
+}
+
+impl LocalStorage {
+
+    /*
+     * MEDIA_DIR : directory of the files (default: ./media)
+     * MEDIA_PUBLIC_URL : URL where the files are served (default: http://127.0.0.1:8080/media/files)
+     */
+    pub fn from_env() -> Self {
+
+        LocalStorage {
+            dir: PathBuf::from(env::var("MEDIA_DIR").unwrap_or_else(|_| String::from("./media"))),
+            public_url: env::var("MEDIA_PUBLIC_URL")
+                .unwrap_or_else(|_| String::from("http://127.0.0.1:8080/media/files"))
+                .trim_end_matches('/')
+                .to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl Storage for LocalStorage {
+
+    async fn put(&self, key: &str, data: Vec<u8>, _content_type: &str) -> Result<(), StorageError> {
+
+        let path = self.dir.join(key);
+
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+
+        tokio::fs::write(path, data).await?;
+
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, StorageError> {
+
+        match tokio::fs::read(self.dir.join(key)).await {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError> {
+
+        match tokio::fs::remove_file(self.dir.join(key)).await {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn url(&self, key: &str) -> String {
+        format!("{}/{key}", self.public_url)
+    }
+}
@@ -0,0 +1,56 @@
+use std::env;
+use std::sync::Arc;
+use async_trait::async_trait;
+
+pub mod local_storage;
+pub mod s3_storage;
+
+use local_storage::LocalStorage;
+use s3_storage::S3Storage;
+
+pub type StorageError = Box<dyn std::error::Error + Send + Sync>;
+
+/*
+ * Everything able to keep the uploaded files (local directory, S3 bucket, ...)
+ * - The implementation used is choosen at startup with the STORAGE env variable
+ * - Keys are generated by the backend: only [a-z0-9], '/', '.', '_' and '-'
+ */
+#[async_trait]
+pub trait Storage: Send + Sync {
+
+    async fn put(&self, key: &str, data: Vec<u8>, content_type: &str) -> Result<(), StorageError>;
+
+    // None if there is no file with this key
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, StorageError>;
+
+    // Deleting a missing file is not an error
+    async fn delete(&self, key: &str) -> Result<(), StorageError>;
+
+    // Public URL of a file
+    fn url(&self, key: &str) -> String;
+}
+
+/*
+ * Create the storage configured in the env
+ * - STORAGE=s3 : S3 compatible bucket (AWS, MinIO, ...), see S3Storage::from_env
+ * - STORAGE=local (default) : files in MEDIA_DIR (default: ./media), served by the backend on /media/files
+ */
+pub fn storage_from_env() -> Arc<dyn Storage> {
+
+    match env::var("STORAGE").as_deref() {
+
+        Ok("s3") => Arc::new(S3Storage::from_env()),
+
+        _ => Arc::new(LocalStorage::from_env()),
+    }
+}
+
+/*
+ * Check a key given by a client, so it can't go out of the storage (ex: "../")
+ */
+pub fn is_valid_key(key: &str) -> bool {
+
+    !key.is_empty()
+        && key.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '/' | '.' | '_' | '-'))
+        && key.split('/').all(|part| !part.is_empty() && !part.starts_with('.'))
+}
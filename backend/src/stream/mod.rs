@@ -0,0 +1,153 @@
+use std::collections::BTreeSet;
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+use sqlx::PgPool;
+use sqlx::postgres::PgListener;
+use tokio::sync::broadcast;
+
+use crate::models::event::StreamEvent;
+
+/*
+ * Fan-out of the events to the clients connected to /stream
+ * - Events are inserted in the events table, in the transaction of the action (see publish_event)
+ * - A trigger sends them on the "events" channel when the transaction commits, so every backend instance gets them
+ * - Each instance listens to the channel and broadcasts them to its own clients
+ */
+
+// Kinds of events
+pub const POST_EVENT: &str = "post";                 // a new post, reply or quote
+pub const LIKES_EVENT: &str = "likes";               // the likes count of a post changed
+pub const NOTIFICATION_EVENT: &str = "notification"; // a new (or grouped) notification, only for its user
+pub const MESSAGE_EVENT: &str = "message";           // a new message, only for the other participants
+pub const RELATIONS_EVENT: &str = "relations";       // the user (un)blocked or (un)muted someone, or was (un)blocked (empty data)
+
+// Events kept in the broadcast channel for slow clients, they are disconnected after that (and resume)
+const BROADCAST_CAPACITY: usize = 1024;
+
+// Ids are taken at the insert, not at the commit: an event can be committed after one with a greater id.
+// So when resuming after an id, the events created this long before it are read again (and de-duplicated by id)
+pub const RESUME_OVERLAP_SECONDS: f64 = 30.0;
+
+// Ids of the last broadcast events remembered by the listener, to not send them again after a catch-up
+const RECENT_IDS_CAPACITY: usize = 10_000;
+
+// How long the events are kept for reconnecting clients, can be overridden with EVENTS_RETENTION_HOURS
+const DEFAULT_EVENTS_RETENTION_HOURS: i32 = 24;
+
+pub type EventSender = broadcast::Sender<Arc<StreamEvent>>;
+
+pub fn event_channel() -> EventSender {
+    broadcast::channel(BROADCAST_CAPACITY).0
+}
+
+/*
+ * Listen to the "events" channel forever and broadcast the events to the /stream handlers
+ * - After a lost connection, the events sent meanwhile are read from the table
+ * - Started in background by main.rs
+ */
+pub async fn listen_events(pool: PgPool, sender: EventSender) {
+
+    let mut last_id: Option<i64> = None;
+    let mut recent_ids: BTreeSet<i64> = BTreeSet::new();
+
+    loop {
+
+        let mut listener = match PgListener::connect_with(&pool).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                eprintln!("Error connecting the events listener: {e}");
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                continue;
+            }
+        };
+
+        if let Err(e) = listener.listen("events").await {
+            eprintln!("Error listening to events: {e}");
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            continue;
+        }
+
+        // Events missed while not listening (with the overlap window, see RESUME_OVERLAP_SECONDS)
+        if let Some(id) = last_id {
+
+            let missed = sqlx::query_as::<_, StreamEvent>("
+                SELECT id, kind, user_id, data FROM events
+                WHERE id > $1 OR created_at >= (SELECT created_at FROM events WHERE id = $1) - make_interval(secs => $2)
+                ORDER BY id;
+            ")
+            .bind(id)
+            .bind(RESUME_OVERLAP_SECONDS)
+            .fetch_all(&pool)
+            .await
+            .unwrap_or_default();
+
+            for event in missed {
+                broadcast(&sender, event, &mut last_id, &mut recent_ids);
+            }
+        }
+
+        loop {
+
+            let notification = match listener.recv().await {
+                Ok(notification) => notification,
+                Err(e) => {
+                    eprintln!("Error receiving events: {e}");
+                    break;
+                }
+            };
+
+            match serde_json::from_str::<StreamEvent>(notification.payload()) {
+                Ok(event) => broadcast(&sender, event, &mut last_id, &mut recent_ids),
+                Err(e) => eprintln!("Invalid event: {e}"),
+            }
+        }
+    }
+}
+
+/*
+ * Send an event to the /stream handlers, once
+ */
+fn broadcast(sender: &EventSender, event: StreamEvent, last_id: &mut Option<i64>, recent_ids: &mut BTreeSet<i64>) {
+
+    if !recent_ids.insert(event.id) {
+        return;
+    }
+
+    // Forget the oldest ids
+    if recent_ids.len() > RECENT_IDS_CAPACITY {
+        recent_ids.pop_first();
+    }
+
+    *last_id = (*last_id).max(Some(event.id));
+
+    let _ = sender.send(Arc::new(event)); // Err only if no client is connected
+}
+
+/*
+ * Delete the old events every hour, they can't be resumed anymore
+ * - Started in background by main.rs
+ */
+pub async fn purge_events(pool: PgPool) {
+
+    let hours = env::var("EVENTS_RETENTION_HOURS").ok()
+        .and_then(|value| value.parse().ok())
+        .filter(|hours| *hours > 0)
+        .unwrap_or(DEFAULT_EVENTS_RETENTION_HOURS);
+
+    let mut interval = tokio::time::interval(Duration::from_secs(3600));
+
+    loop {
+
+        interval.tick().await;
+
+        let result = sqlx::query("DELETE FROM events WHERE created_at < NOW() - make_interval(hours => $1)")
+            .bind(hours)
+            .execute(&pool)
+            .await;
+
+        if let Err(e) = result {
+            eprintln!("Error purging events: {e}");
+        }
+    }
+}
@@ -0,0 +1,34 @@
+use std::env;
+use std::time::Duration;
+use sqlx::PgPool;
+
+// How often the trending hashtags are recomputed, can be overridden with TRENDS_REFRESH_SECONDS
+const DEFAULT_TRENDS_REFRESH_SECONDS: u64 = 300;
+
+/*
+ * Recompute the trending_hashtags view forever, so /trends only reads it
+ * - Started in background by main.rs
+ */
+pub async fn refresh_trends(pool: PgPool) {
+
+    let seconds = env::var("TRENDS_REFRESH_SECONDS").ok()
+        .and_then(|value| value.parse().ok())
+        .filter(|seconds| *seconds > 0)
+        .unwrap_or(DEFAULT_TRENDS_REFRESH_SECONDS);
+
+    let mut interval = tokio::time::interval(Duration::from_secs(seconds));
+
+    loop {
+
+        interval.tick().await;
+
+        // Concurrently: the view can still be read during the refresh
+        let result = sqlx::query("REFRESH MATERIALIZED VIEW CONCURRENTLY trending_hashtags")
+            .execute(&pool)
+            .await;
+
+        if let Err(e) = result {
+            eprintln!("Error refreshing trends: {e}");
+        }
+    }
+}
@@ -1,3 +1,43 @@
+DROP TABLE IF EXISTS content_filters CASCADE;
+DROP TABLE IF EXISTS mutes CASCADE;
+DROP TABLE IF EXISTS blocks CASCADE;
+DROP TABLE IF EXISTS messages CASCADE;
+DROP TABLE IF EXISTS conversation_participants CASCADE;
+DROP TABLE IF EXISTS conversations CASCADE;
+DROP TABLE IF EXISTS events CASCADE;
+DROP FUNCTION IF EXISTS notify_event;
+DROP TABLE IF EXISTS notification_actors CASCADE;
+DROP TABLE IF EXISTS notifications CASCADE;
+DROP TABLE IF EXISTS bookmarks CASCADE;
+DROP TABLE IF EXISTS poll_votes CASCADE;
+DROP TABLE IF EXISTS poll_voters CASCADE;
+DROP TABLE IF EXISTS poll_options CASCADE;
+DROP TABLE IF EXISTS polls CASCADE;
+DROP TABLE IF EXISTS media CASCADE;
+DROP MATERIALIZED VIEW IF EXISTS trending_hashtags;
+DROP TABLE IF EXISTS post_urls CASCADE;
+DROP TABLE IF EXISTS post_mentions CASCADE;
+DROP TABLE IF EXISTS post_hashtags CASCADE;
+DROP TABLE IF EXISTS hashtags CASCADE;
+DROP TABLE IF EXISTS post_revisions CASCADE;
+DROP TABLE IF EXISTS reposts CASCADE;
+DROP TABLE IF EXISTS follows CASCADE;
+DROP TABLE IF EXISTS oidc_login_states CASCADE;
+DROP TABLE IF EXISTS user_identities CASCADE;
+DROP TABLE IF EXISTS api_tokens CASCADE;
+DROP TABLE IF EXISTS login_failures CASCADE;
+DROP TABLE IF EXISTS used_challenges CASCADE;
+DROP TABLE IF EXISTS recovery_codes CASCADE;
+DROP TABLE IF EXISTS user_totp CASCADE;
+DROP TABLE IF EXISTS email_verification_tokens CASCADE;
+DROP TABLE IF EXISTS password_reset_tokens CASCADE;
+DROP TABLE IF EXISTS refresh_tokens CASCADE;
+DROP TABLE IF EXISTS sessions CASCADE;
 DROP TABLE IF EXISTS user_likes CASCADE;
+DROP TABLE IF EXISTS deleted_posts CASCADE;
 DROP TABLE IF EXISTS posts CASCADE;
+DROP FUNCTION IF EXISTS keep_deleted_posts;
 DROP TABLE IF EXISTS users CASCADE;
+DROP TABLE IF EXISTS role_permissions CASCADE;
+DROP TABLE IF EXISTS permissions CASCADE;
+DROP TABLE IF EXISTS roles CASCADE;
@@ -2,6 +2,41 @@
 -- Force UTC timezone ! Important to not have weird time diff bugs
 SET TIME ZONE 'UTC';
 
+-- Trigram similarity, used to search the users
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+-- Roles (user, moderator, admin) and the named permissions they give
+CREATE TABLE roles (
+	id SERIAL PRIMARY KEY,
+	name VARCHAR (30) NOT NULL UNIQUE
+);
+
+CREATE TABLE permissions (
+	id SERIAL PRIMARY KEY,
+	name VARCHAR (50) NOT NULL UNIQUE -- e.g. "post.delete.any", see backend auth::permissions
+);
+
+CREATE TABLE role_permissions (
+	role_id INTEGER NOT NULL REFERENCES roles(id) ON DELETE CASCADE,
+	permission_id INTEGER NOT NULL REFERENCES permissions(id) ON DELETE CASCADE,
+	PRIMARY KEY (role_id, permission_id)
+);
+
+-- Default roles and permissions, needed by the API
+INSERT INTO roles (name) VALUES ('user'), ('moderator'), ('admin');
+
+INSERT INTO permissions (name) VALUES
+('post.delete.any'),
+('user.list'),
+('user.suspend'),
+('user.update.any'),
+('user.delete'),
+('user.role.assign');
+
+-- Moderators can only delete posts, admins can do everything
+INSERT INTO role_permissions (role_id, permission_id)
+SELECT r.id, p.id FROM roles r JOIN permissions p ON r.name = 'admin' OR (r.name = 'moderator' AND p.name = 'post.delete.any');
+
 CREATE TABLE users (
 	id SERIAL PRIMARY KEY,
 	username VARCHAR (50) NOT NULL UNIQUE,
@@ -9,17 +44,263 @@ CREATE TABLE users (
 	password VARCHAR (255) NOT NULL,
 	title VARCHAR (50),
 	created_at TIMESTAMP DEFAULT NOW(),
-	is_admin BOOLEAN NOT NULL DEFAULT FALSE
+	role_id INTEGER NOT NULL REFERENCES roles(id),
+	email_verified_at TIMESTAMP, -- NULL until the user opens the link sent to his email
+	suspended_at TIMESTAMP,      -- A suspended user can't log in anymore
+	followers_count INTEGER NOT NULL DEFAULT 0,
+	following_count INTEGER NOT NULL DEFAULT 0,
+	dm_policy VARCHAR(20) NOT NULL DEFAULT 'everyone' -- who can message the user: everyone, following (users he follows) or nobody
 );
 
+CREATE INDEX users_username_trgm_idx ON users USING GIN (username gin_trgm_ops);
+CREATE INDEX users_title_trgm_idx ON users USING GIN (title gin_trgm_ops);
+
 CREATE TABLE posts (
 	id SERIAL PRIMARY KEY,
 	user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
 	content TEXT NOT NULL,
 	created_at TIMESTAMP DEFAULT NOW(),
-	likes_count INTEGER NOT NULL DEFAULT 0
+	likes_count INTEGER NOT NULL DEFAULT 0,
+	parent_id INTEGER, -- post it replies to (kept if it's deleted, to show it as unavailable in the thread)
+	root_id INTEGER, -- first post of the conversation (kept if it's deleted), NULL for a post that is not a reply
+	replies_count INTEGER NOT NULL DEFAULT 0,
+	quoted_post_id INTEGER, -- post embedded by a quote post (kept if it's deleted, to show it as unavailable)
+	reposts_count INTEGER NOT NULL DEFAULT 0,
+	quotes_count INTEGER NOT NULL DEFAULT 0,
+	edited_at TIMESTAMP,
+	-- Full text search, in both languages of the app
+	search_vector TSVECTOR GENERATED ALWAYS AS (to_tsvector('english', content) || to_tsvector('french', content)) STORED
+);
+
+CREATE INDEX posts_search_idx ON posts USING GIN (search_vector);
+
+CREATE INDEX posts_parent_idx ON posts (parent_id);
+CREATE INDEX posts_root_idx ON posts (root_id);
+
+-- Deleted posts that still had replies, to keep their place in the thread (shown as unavailable)
+CREATE TABLE deleted_posts (
+	id INTEGER PRIMARY KEY, -- id of the deleted post
+	parent_id INTEGER, -- post it replied to
+	created_at TIMESTAMP
+);
+
+CREATE INDEX deleted_posts_parent_idx ON deleted_posts (parent_id);
+
+-- Keep the deleted posts with replies, and the deleted posts above them (a user's account deletes all of their posts at once)
+CREATE FUNCTION keep_deleted_posts() RETURNS TRIGGER AS $$
+BEGIN
+	INSERT INTO deleted_posts (id, parent_id, created_at)
+	WITH RECURSIVE kept AS (
+		SELECT o.id, o.parent_id, o.created_at
+		FROM old_posts o
+		WHERE EXISTS (SELECT 1 FROM posts r WHERE r.parent_id = o.id)
+			OR EXISTS (SELECT 1 FROM deleted_posts d WHERE d.parent_id = o.id)
+		UNION
+		SELECT o.id, o.parent_id, o.created_at
+		FROM old_posts o
+		JOIN kept k ON k.parent_id = o.id
+	)
+	SELECT id, parent_id, created_at FROM kept
+	ON CONFLICT (id) DO NOTHING;
+	RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER posts_keep_deleted AFTER DELETE ON posts REFERENCING OLD TABLE AS old_posts FOR EACH STATEMENT EXECUTE FUNCTION keep_deleted_posts();
+
+-- Entities parsed from the content of the posts (offsets in characters, end excluded)
+CREATE TABLE hashtags (
+	id SERIAL PRIMARY KEY,
+	name VARCHAR(100) UNIQUE NOT NULL -- lowercase, without #
+);
+
+CREATE TABLE post_hashtags (
+	post_id INTEGER NOT NULL REFERENCES posts(id) ON DELETE CASCADE,
+	hashtag_id INTEGER NOT NULL REFERENCES hashtags(id) ON DELETE CASCADE,
+	start_index INTEGER NOT NULL,
+	end_index INTEGER NOT NULL,
+	PRIMARY KEY (post_id, start_index)
+);
+
+CREATE INDEX post_hashtags_hashtag_idx ON post_hashtags (hashtag_id);
+
+-- Only the mentions of existing users are kept
+CREATE TABLE post_mentions (
+	post_id INTEGER NOT NULL REFERENCES posts(id) ON DELETE CASCADE,
+	user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+	start_index INTEGER NOT NULL,
+	end_index INTEGER NOT NULL,
+	PRIMARY KEY (post_id, start_index)
+);
+
+CREATE INDEX post_mentions_user_idx ON post_mentions (user_id);
+
+CREATE TABLE post_urls (
+	post_id INTEGER NOT NULL REFERENCES posts(id) ON DELETE CASCADE,
+	url TEXT NOT NULL,
+	start_index INTEGER NOT NULL,
+	end_index INTEGER NOT NULL,
+	PRIMARY KEY (post_id, start_index)
+);
+
+-- Uploaded images, attached to a post when it's created (post_id NULL before)
+CREATE TABLE media (
+	id SERIAL PRIMARY KEY,
+	user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+	post_id INTEGER REFERENCES posts(id) ON DELETE CASCADE,
+	position SMALLINT, -- order in the post
+	storage_key TEXT NOT NULL,
+	thumbnail_key TEXT NOT NULL,
+	url TEXT NOT NULL,
+	thumbnail_url TEXT NOT NULL,
+	content_type VARCHAR(50) NOT NULL,
+	width INTEGER NOT NULL,
+	height INTEGER NOT NULL,
+	size_bytes INTEGER NOT NULL,
+	alt_text TEXT,
+	created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX media_post_idx ON media (post_id);
+
+-- Notifications of likes, reposts, follows, replies, quotes and mentions
+-- Similar ones are grouped while unread (ex: "X and 4 others liked your post"): they have the same group_key
+CREATE TABLE notifications (
+	id SERIAL PRIMARY KEY,
+	user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE, -- who receives it
+	kind VARCHAR(20) NOT NULL, -- like, repost, follow, reply, quote or mention
+	post_id INTEGER REFERENCES posts(id) ON DELETE CASCADE, -- liked or reposted post, or the reply, quote, mention
+	group_key TEXT NOT NULL, -- kind:post_id (kind for follows)
+	updated_at TIMESTAMP NOT NULL DEFAULT NOW(), -- last time an actor was added
+	read_at TIMESTAMP
+);
+
+CREATE UNIQUE INDEX notifications_unread_group_idx ON notifications (user_id, group_key) WHERE read_at IS NULL;
+CREATE INDEX notifications_user_idx ON notifications (user_id, updated_at DESC, id DESC);
+
+-- Users who did the action of a notification
+CREATE TABLE notification_actors (
+	notification_id INTEGER NOT NULL REFERENCES notifications(id) ON DELETE CASCADE,
+	actor_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+	created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+	PRIMARY KEY (notification_id, actor_id)
+);
+
+-- Events pushed to the clients connected to /stream (new posts, likes counts, notifications)
+-- They are kept a while, so reconnecting clients can resume with Last-Event-ID
+CREATE TABLE events (
+	id BIGSERIAL PRIMARY KEY,
+	kind VARCHAR(20) NOT NULL,
+	user_id INTEGER REFERENCES users(id) ON DELETE CASCADE, -- only sent to this user, NULL for everyone
+	data JSONB NOT NULL,
+	created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX events_created_at_idx ON events (created_at);
+
+-- Send each event to the backend instances listening on the "events" channel (when the transaction commits)
+CREATE FUNCTION notify_event() RETURNS TRIGGER AS $$
+BEGIN
+	PERFORM pg_notify('events', json_build_object('id', NEW.id, 'kind', NEW.kind, 'user_id', NEW.user_id, 'data', NEW.data)::TEXT);
+	RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER events_notify AFTER INSERT ON events FOR EACH ROW EXECUTE FUNCTION notify_event();
+
+-- Private conversations, between two users or a small group
+CREATE TABLE conversations (
+	id SERIAL PRIMARY KEY,
+	is_group BOOLEAN NOT NULL,
+	title VARCHAR(100), -- only for groups
+	direct_key VARCHAR(30) UNIQUE, -- "<smallest user id>:<largest user id>" for one-to-one conversations, so there is only one
+	created_by INTEGER REFERENCES users(id) ON DELETE SET NULL,
+	created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+	last_message_at TIMESTAMP NOT NULL DEFAULT NOW() -- creation time until the first message
+);
+
+CREATE TABLE conversation_participants (
+	conversation_id INTEGER NOT NULL REFERENCES conversations(id) ON DELETE CASCADE,
+	user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+	joined_at TIMESTAMP NOT NULL DEFAULT NOW(),
+	last_read_message_id INTEGER, -- read marker, NULL if nothing was read
+	PRIMARY KEY (conversation_id, user_id)
+);
+
+CREATE INDEX conversation_participants_user_idx ON conversation_participants (user_id);
+
+CREATE TABLE messages (
+	id SERIAL PRIMARY KEY,
+	conversation_id INTEGER NOT NULL REFERENCES conversations(id) ON DELETE CASCADE,
+	sender_id INTEGER REFERENCES users(id) ON DELETE SET NULL,
+	content TEXT NOT NULL,
+	created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX messages_conversation_idx ON messages (conversation_id, id DESC);
+
+-- Poll of a post, the results are shown once the user voted or the poll is closed
+CREATE TABLE polls (
+	post_id INTEGER PRIMARY KEY REFERENCES posts(id) ON DELETE CASCADE,
+	multiple_choice BOOLEAN NOT NULL DEFAULT FALSE,
+	closes_at TIMESTAMP NOT NULL,
+	voters_count INTEGER NOT NULL DEFAULT 0
+);
+
+CREATE TABLE poll_options (
+	id SERIAL PRIMARY KEY,
+	post_id INTEGER NOT NULL REFERENCES polls(post_id) ON DELETE CASCADE,
+	position SMALLINT NOT NULL,
+	label VARCHAR(100) NOT NULL,
+	votes_count INTEGER NOT NULL DEFAULT 0,
+	UNIQUE (post_id, position)
+);
+
+-- A user votes once per poll (for several options if multiple_choice)
+CREATE TABLE poll_voters (
+	post_id INTEGER NOT NULL REFERENCES polls(post_id) ON DELETE CASCADE,
+	user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+	voted_at TIMESTAMP NOT NULL DEFAULT NOW(),
+	PRIMARY KEY (post_id, user_id)
+);
+
+CREATE TABLE poll_votes (
+	option_id INTEGER NOT NULL REFERENCES poll_options(id) ON DELETE CASCADE,
+	user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+	PRIMARY KEY (option_id, user_id)
+);
+
+-- Previous versions of the edited posts
+CREATE TABLE post_revisions (
+	id SERIAL PRIMARY KEY,
+	post_id INTEGER NOT NULL REFERENCES posts(id) ON DELETE CASCADE,
+	content TEXT NOT NULL,
+	created_at TIMESTAMP NOT NULL, -- when this version was written
+	replaced_at TIMESTAMP NOT NULL DEFAULT NOW()
 );
 
+CREATE INDEX post_revisions_post_idx ON post_revisions (post_id);
+
+CREATE TABLE reposts (
+	user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+	post_id INTEGER NOT NULL REFERENCES posts(id) ON DELETE CASCADE,
+	created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+	PRIMARY KEY (user_id, post_id)
+);
+
+CREATE INDEX reposts_post_idx ON reposts (post_id);
+CREATE INDEX reposts_created_at_idx ON reposts (created_at);
+
+-- Saved posts, only visible by the user who saved them
+CREATE TABLE bookmarks (
+	user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+	post_id INTEGER NOT NULL REFERENCES posts(id) ON DELETE CASCADE,
+	created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+	PRIMARY KEY (user_id, post_id)
+);
+
+CREATE INDEX bookmarks_user_created_at_idx ON bookmarks (user_id, created_at DESC, post_id DESC);
+
 CREATE TABLE user_likes (
 	id SERIAL PRIMARY KEY,
 	user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
@@ -27,3 +308,195 @@ CREATE TABLE user_likes (
 	liked_at TIMESTAMP DEFAULT NOW(),
 	UNIQUE (user_id, post_id)
 );
+
+-- Follow graph: follower_id follows followed_id
+CREATE TABLE follows (
+	follower_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+	followed_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+	created_at TIMESTAMP DEFAULT NOW(),
+	PRIMARY KEY (follower_id, followed_id),
+	CHECK (follower_id <> followed_id)
+);
+
+CREATE INDEX follows_followed_idx ON follows (followed_id);
+
+-- blocker_id and blocked_id don't see each other anymore (posts, notifications, search, messages)
+CREATE TABLE blocks (
+	blocker_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+	blocked_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+	created_at TIMESTAMP DEFAULT NOW(),
+	PRIMARY KEY (blocker_id, blocked_id),
+	CHECK (blocker_id <> blocked_id)
+);
+
+CREATE INDEX blocks_blocked_idx ON blocks (blocked_id);
+
+-- The posts and notifications of muted_id are hidden from the feeds of muter_id, he doesn't know it
+CREATE TABLE mutes (
+	muter_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+	muted_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+	created_at TIMESTAMP DEFAULT NOW(),
+	PRIMARY KEY (muter_id, muted_id),
+	CHECK (muter_id <> muted_id)
+);
+
+-- Words and hashtags an user doesn't want to see, in some contexts (home, notifications, search)
+-- action: hide the matching posts, or return them marked with the filters they match (warn)
+CREATE TABLE content_filters (
+	id SERIAL PRIMARY KEY,
+	user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+	phrase VARCHAR(100) NOT NULL, -- a word or a phrase, or a tag (lowercase, without #)
+	is_hashtag BOOLEAN NOT NULL DEFAULT FALSE,
+	contexts TEXT[] NOT NULL,
+	action VARCHAR(10) NOT NULL DEFAULT 'hide',
+	expires_at TIMESTAMP, -- NULL: never, expired filters are ignored
+	created_at TIMESTAMP DEFAULT NOW(),
+	-- Matched with posts.search_vector (same languages, so "cats" matches "cat"), NULL for a tag
+	query TSQUERY GENERATED ALWAYS AS (
+		CASE WHEN NOT is_hashtag THEN phraseto_tsquery('english', phrase) || phraseto_tsquery('french', phrase) END
+	) STORED,
+	UNIQUE (user_id, is_hashtag, phrase)
+);
+
+-- One row per login (device)
+CREATE TABLE sessions (
+	id SERIAL PRIMARY KEY,
+	user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+	user_agent TEXT,
+	ip VARCHAR (45),
+	created_at TIMESTAMP DEFAULT NOW(),
+	last_seen_at TIMESTAMP DEFAULT NOW(),
+	revoked_at TIMESTAMP
+);
+
+CREATE INDEX sessions_user_idx ON sessions (user_id);
+
+CREATE TABLE refresh_tokens (
+	id SERIAL PRIMARY KEY,
+	user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+	session_id INTEGER NOT NULL REFERENCES sessions(id) ON DELETE CASCADE,
+	family_id UUID NOT NULL DEFAULT gen_random_uuid(), -- All tokens rotated from the same login share a family
+	token_hash VARCHAR (64) NOT NULL UNIQUE,            -- SHA-256 of the token, the raw value is never stored
+	created_at TIMESTAMP DEFAULT NOW(),
+	expires_at TIMESTAMP NOT NULL,
+	used_at TIMESTAMP,                                  -- Set when the token has been rotated
+	revoked_at TIMESTAMP
+);
+
+CREATE INDEX refresh_tokens_family_idx ON refresh_tokens (family_id);
+
+CREATE TABLE password_reset_tokens (
+	id SERIAL PRIMARY KEY,
+	user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+	token_hash VARCHAR (64) NOT NULL UNIQUE, -- SHA-256 of the token sent by mail
+	created_at TIMESTAMP DEFAULT NOW(),
+	expires_at TIMESTAMP NOT NULL,
+	used_at TIMESTAMP                        -- A token can only be used once
+);
+
+CREATE TABLE email_verification_tokens (
+	id SERIAL PRIMARY KEY,
+	user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+	email VARCHAR (100) NOT NULL,            -- The address the token was sent to
+	token_hash VARCHAR (64) NOT NULL UNIQUE, -- SHA-256 of the token sent by mail
+	created_at TIMESTAMP DEFAULT NOW(),
+	expires_at TIMESTAMP NOT NULL,
+	used_at TIMESTAMP
+);
+
+-- TOTP (RFC 6238) second factor of an user
+CREATE TABLE user_totp (
+	user_id INTEGER PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+	secret VARCHAR (64) NOT NULL,  -- base32 encoded
+	created_at TIMESTAMP DEFAULT NOW(),
+	confirmed_at TIMESTAMP,        -- NULL until a first code is validated, 2FA is only enabled after that
+	last_used_step BIGINT          -- A code can't be used twice
+);
+
+CREATE TABLE recovery_codes (
+	id SERIAL PRIMARY KEY,
+	user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+	code_hash VARCHAR (64) NOT NULL, -- SHA-256 of the code
+	used_at TIMESTAMP
+);
+
+CREATE INDEX recovery_codes_user_idx ON recovery_codes (user_id);
+
+-- 2FA challenge tokens already exchanged for a session, each one can only be used once
+CREATE TABLE used_challenges (
+	jti VARCHAR (64) PRIMARY KEY, -- id of the challenge token
+	expires_at TIMESTAMP NOT NULL -- the token is rejected after this anyway, the row can be deleted
+);
+
+-- Failed login counters, per account (username) and per IP
+CREATE TABLE login_failures (
+	key VARCHAR (120) PRIMARY KEY, -- "user:<username>", "ip:<address>" or "2fa:<user id>"
+	failures INTEGER NOT NULL DEFAULT 0,
+	last_failure_at TIMESTAMP NOT NULL DEFAULT NOW(),
+	locked_until TIMESTAMP
+);
+
+-- Personal access tokens, for scripts and bots
+CREATE TABLE api_tokens (
+	id SERIAL PRIMARY KEY,
+	user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+	name VARCHAR (100) NOT NULL,
+	token_hash VARCHAR (64) NOT NULL UNIQUE, -- SHA-256 of the token, only shown once at creation
+	scopes VARCHAR (30)[] NOT NULL,          -- e.g. {posts:write,users:read}
+	created_at TIMESTAMP DEFAULT NOW(),
+	expires_at TIMESTAMP,                    -- NULL if the token never expires
+	last_used_at TIMESTAMP,
+	revoked_at TIMESTAMP
+);
+
+CREATE INDEX api_tokens_user_idx ON api_tokens (user_id);
+
+-- Accounts of external identity providers (OpenID Connect) linked to an user
+CREATE TABLE user_identities (
+	id SERIAL PRIMARY KEY,
+	user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+	issuer VARCHAR (255) NOT NULL,
+	subject VARCHAR (255) NOT NULL, -- "sub" claim, the id of the user for the provider
+	email VARCHAR (100),
+	created_at TIMESTAMP DEFAULT NOW(),
+	last_login_at TIMESTAMP DEFAULT NOW(),
+	UNIQUE (issuer, subject)
+);
+
+CREATE INDEX user_identities_user_idx ON user_identities (user_id);
+
+-- OpenID Connect logins waiting for the provider callback
+CREATE TABLE oidc_login_states (
+	state_hash VARCHAR (64) PRIMARY KEY, -- SHA-256 of the state sent to the provider
+	code_verifier VARCHAR (128) NOT NULL, -- PKCE secret, sent with the code
+	nonce VARCHAR (64) NOT NULL,          -- Must be in the ID token
+	created_at TIMESTAMP DEFAULT NOW(),
+	expires_at TIMESTAMP NOT NULL
+);
+
+-- Trending hashtags, refreshed by the backend every TRENDS_REFRESH_SECONDS
+-- The velocity of a tag is the number of authors using it in the last hour, compared to its hourly
+-- average over the 24 hours before (the baseline), so usual tags don't stay on top
+CREATE MATERIALIZED VIEW trending_hashtags AS
+WITH uses AS (
+	SELECT
+		ph.hashtag_id,
+		COUNT(DISTINCT p.user_id) FILTER (WHERE p.created_at >= NOW() - INTERVAL '1 hour') AS recent_count,
+		COUNT(DISTINCT p.user_id) FILTER (WHERE p.created_at < NOW() - INTERVAL '1 hour') AS baseline_count
+	FROM post_hashtags ph
+	JOIN posts p ON p.id = ph.post_id
+	WHERE p.created_at >= NOW() - INTERVAL '25 hours'
+	GROUP BY ph.hashtag_id
+)
+SELECT
+	h.id AS hashtag_id,
+	h.name,
+	u.recent_count,
+	u.baseline_count / 24.0 AS baseline_rate,
+	(u.recent_count - u.baseline_count / 24.0) / SQRT(u.baseline_count / 24.0 + 1) AS score
+FROM uses u
+JOIN hashtags h ON h.id = u.hashtag_id
+WHERE u.recent_count >= 2 AND u.recent_count > u.baseline_count / 24.0;
+
+-- Needed to refresh it concurrently
+CREATE UNIQUE INDEX trending_hashtags_idx ON trending_hashtags (hashtag_id);
@@ -1,30 +1,35 @@
 /**************************
 * USERS TEST DATA INSERTION
 ***************************/
-INSERT INTO users (username, email, password, is_admin) VALUES
-('admin', 'admin@secure.com', '$argon2id$v=19$m=19456,t=2,p=1$jbj6R/pN/iKJi/Hi8VqJYg$WYmcgwNDmfwcxGcqTO4IGOArJhuYtAv/pwdH2GOMROc', TRUE),
-('User1', 'user1@example.com', 'password1', FALSE),
-('User2', 'user2@example.com', 'password2', FALSE),
-('User3', 'user3@example.com', 'password3', FALSE),
-('User4', 'user4@example.com', 'password4', FALSE),
-('User5', 'user5@example.com', 'password5', FALSE),
-('User6', 'user6@example.com', 'password6', FALSE),
-('User7', 'user7@example.com', 'password7', FALSE),
-('User8', 'user8@example.com', 'password8', FALSE),
-('User9', 'user9@example.com', 'password9', FALSE),
-('User10', 'user10@example.com', 'password10', FALSE),
-('Admin1', 'admin1@example.com', 'adminpass1', TRUE),
-('Admin2', 'admin2@example.com', 'adminpass2', TRUE),
-('User11', 'user11@example.com', 'password11', FALSE),
-('User12', 'user12@example.com', 'password12', FALSE),
-('User13', 'user13@example.com', 'password13', FALSE),
-('User14', 'user14@example.com', 'password14', FALSE),
-('User15', 'user15@example.com', 'password15', FALSE),
-('User16', 'user16@example.com', 'password16', FALSE),
-('User17', 'user17@example.com', 'password17', FALSE),
-('User18', 'user18@example.com', 'password18', FALSE),
-('User19', 'user19@example.com', 'password19', FALSE),
-('User20', 'user20@example.com', 'password20', FALSE);
+-- role_id : 1 = user, 2 = moderator, 3 = admin
+INSERT INTO users (username, email, password, role_id) VALUES
+('admin', 'admin@secure.com', '$argon2id$v=19$m=19456,t=2,p=1$jbj6R/pN/iKJi/Hi8VqJYg$WYmcgwNDmfwcxGcqTO4IGOArJhuYtAv/pwdH2GOMROc', 3),
+('User1', 'user1@example.com', 'password1', 1),
+('User2', 'user2@example.com', 'password2', 1),
+('User3', 'user3@example.com', 'password3', 1),
+('User4', 'user4@example.com', 'password4', 1),
+('User5', 'user5@example.com', 'password5', 1),
+('User6', 'user6@example.com', 'password6', 1),
+('User7', 'user7@example.com', 'password7', 1),
+('User8', 'user8@example.com', 'password8', 1),
+('User9', 'user9@example.com', 'password9', 1),
+('User10', 'user10@example.com', 'password10', 1),
+('Admin1', 'admin1@example.com', 'adminpass1', 3),
+('Admin2', 'admin2@example.com', 'adminpass2', 3),
+('User11', 'user11@example.com', 'password11', 1),
+('User12', 'user12@example.com', 'password12', 1),
+('User13', 'user13@example.com', 'password13', 1),
+('User14', 'user14@example.com', 'password14', 1),
+('User15', 'user15@example.com', 'password15', 1),
+('User16', 'user16@example.com', 'password16', 1),
+('User17', 'user17@example.com', 'password17', 1),
+('User18', 'user18@example.com', 'password18', 1),
+('User19', 'user19@example.com', 'password19', 1),
+('User20', 'user20@example.com', 'password20', 1),
+('Moderator1', 'moderator1@example.com', 'moderatorpass1', 2);
+
+-- Test accounts are considered as verified
+UPDATE users SET email_verified_at = NOW();
 
 /**************************
 * POSTS TEST DATA INSERTION
@@ -110,3 +115,47 @@ INSERT INTO posts (user_id, content, likes_count) VALUES
 (18, 'User18 asks about favorite travel destinations', 25),
 (19, 'User19 shares tips on socializing', 3),
 (20, 'User20 discusses the importance of kindness', 15);
+
+/****************************
+* REPLIES TEST DATA INSERTION
+*****************************/
+INSERT INTO posts (user_id, content, parent_id, root_id) VALUES
+(2, 'User2 replies to User1', 1, 1),
+(3, 'User3 also replies to User1', 1, 1),
+(1, 'User1 answers User2', 81, 1), -- 81 is the first reply
+(5, 'User5 replies to User2', 2, 2);
+
+UPDATE posts SET replies_count = (SELECT COUNT(*) FROM posts r WHERE r.parent_id = posts.id);
+
+/****************************
+* REPOSTS TEST DATA INSERTION
+*****************************/
+INSERT INTO posts (user_id, content, quoted_post_id) VALUES
+(3, 'User3 quotes User5', 5);
+
+INSERT INTO reposts (user_id, post_id) VALUES
+(2, 6),
+(3, 5),
+(5, 1);
+
+UPDATE posts SET
+	reposts_count = (SELECT COUNT(*) FROM reposts r WHERE r.post_id = posts.id),
+	quotes_count = (SELECT COUNT(*) FROM posts q WHERE q.quoted_post_id = posts.id);
+
+/****************************
+* FOLLOWS TEST DATA INSERTION
+*****************************/
+INSERT INTO follows (follower_id, followed_id) VALUES
+(1, 2),
+(1, 3),
+(1, 5),
+(2, 1),
+(2, 3),
+(3, 1),
+(4, 1),
+(5, 6),
+(6, 5);
+
+UPDATE users SET
+	followers_count = (SELECT COUNT(*) FROM follows WHERE followed_id = users.id),
+	following_count = (SELECT COUNT(*) FROM follows WHERE follower_id = users.id);
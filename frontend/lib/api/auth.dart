@@ -4,6 +4,7 @@ import 'package:shared_preferences/shared_preferences.dart';
 import 'package:http/http.dart' as http;
 
 import '../auth/authProvider.dart';
+import '../auth/tokenHandler.dart';
 
 //TODO: Improve the return logic of the function to have differents messages
 Future<bool> login(String username, String password) async {
@@ -25,12 +26,13 @@ Future<bool> login(String username, String password) async {
       case 200: // OK -> Add token to shared_preferences and return true
         final tokenResponse = jsonDecode(response.body);
         final token = tokenResponse['token'];
-        if (token == null) {
+        final refreshToken = tokenResponse['refresh_token'];
+        if (token == null || refreshToken == null) {
 
-          print("No field 'Token' in response body");
+          print("No field 'Token' or 'Refresh token' in response body");
           return false;
         }
-        await AuthProvider().login(token);
+        await AuthProvider().login(token, refreshToken);
         print(tokenResponse);
         return true;
 
@@ -60,3 +62,92 @@ Future<void> logout() async {
   await AuthProvider().logout();
 }
 
+
+// Refresh running, so concurrent requests wait for it instead of sending the same refresh token twice
+// (the API revokes the whole session if a refresh token is used again)
+Future<bool>? _refreshing;
+
+// Exchange the refresh token for new tokens, logout if the session is over
+Future<bool> refreshSession() {
+
+  _refreshing ??= _refreshSession().whenComplete(() => _refreshing = null);
+
+  return _refreshing!;
+}
+
+Future<bool> _refreshSession() async {
+
+  final refreshToken = TokenHandler().refreshToken;
+
+  if (refreshToken == null)
+    return false;
+
+  try {
+
+    final response = await http.post(Uri.parse('http://127.0.0.1:8080/auth/refresh'),
+      headers: {
+        'Content-Type': 'application/json',
+      },
+      body: jsonEncode({
+        'refresh_token': refreshToken,
+      }),
+    );
+
+    if (response.statusCode == 200) {
+
+      final tokenResponse = jsonDecode(response.body);
+      await TokenHandler().setTokens(tokenResponse['token'], tokenResponse['refresh_token']);
+      return true;
+    }
+
+    print('Failed to refresh session: ${response.statusCode} - ${response.body}');
+
+    // Session expired or revoked -> logout
+    if (response.statusCode == 401)
+      await AuthProvider().logout();
+
+    return false;
+
+  } catch (error) {
+
+    print('Error occurred while refreshing session: $error');
+    return false;
+  }
+}
+
+// Send a request with the access token, refresh it and send the request again if it expired (401)
+Future<http.Response> sendWithToken(Future<http.Response> Function(String? token) request) async {
+
+  final response = await request(TokenHandler().token);
+
+  if (response.statusCode != 401 || TokenHandler().refreshToken == null)
+    return response;
+
+  if (!await refreshSession())
+    return response;
+
+  return await request(TokenHandler().token);
+}
+
+// Revoke the session of the refresh token on the API
+Future<void> revokeSession(String refreshToken) async {
+
+  try {
+
+    final response = await http.post(Uri.parse('http://127.0.0.1:8080/auth/logout'),
+      headers: {
+        'Content-Type': 'application/json',
+      },
+      body: jsonEncode({
+        'refresh_token': refreshToken,
+      }),
+    );
+
+    if (response.statusCode != 204)
+      print('Failed to logout: ${response.statusCode} - ${response.body}');
+
+  } catch (error) {
+
+    print('Error occurred while logging out: $error');
+  }
+}
@@ -5,6 +5,7 @@ import 'package:http/http.dart' as http;
 
 import '../models/post.dart';
 import '../auth/tokenHandler.dart';
+import 'auth.dart';
 
 Future<Post> fetchPost(int id) async {
 
@@ -26,15 +27,12 @@ Future<Post> fetchPost(int id) async {
 
 Future<List<Post>> fetchPosts({int limit = 20, int offset = 0}) async {
 
-  // Get user token
-  final token = TokenHandler().token;
-
-  final response = await http.get(
+  final response = await sendWithToken((token) => http.get(
     Uri.parse('http://127.0.0.1:8080/posts?limit=$limit&offset=$offset'), headers: {
       'Authorization': 'Bearer $token',
       'Content-Type': 'application/x-www-form-urlencoded',
     },
-  );
+  ));
 
   if (response.statusCode == 200) {
 
@@ -52,18 +50,15 @@ Future<bool> deletePost(Post post) async {
 
   final id = post.id;
 
-  // Get user token
-  final token = TokenHandler().token;
-
   try {
 
-    final response = await http.delete(
+    final response = await sendWithToken((token) => http.delete(
       Uri.parse('http://127.0.0.1:8080/posts/delete/$id'),
       headers: {
         'Authorization': 'Bearer $token',  // Include token in the Authorization header
         'Content-Type': 'application/x-www-form-urlencoded',
       }
-    );
+    ));
 
     // Check for 204 cause API is returning 204 if delete is successfull
     bool success = response.statusCode == 204;
@@ -84,16 +79,13 @@ Future<bool> deletePost(Post post) async {
 
 Future<Post?> createPost(String content) async {
 
-  // Get user token
-  final token = TokenHandler().token;
-
   // If there is no token return error
-  if (token == null) {
+  if (TokenHandler().token == null) {
     print('CreatePost > NoToken: User must be connected to create a post.');
     return null;
   }
 
-  final response = await http.post(
+  final response = await sendWithToken((token) => http.post(
 
   Uri.parse('http://127.0.0.1:8080/posts/create'),
     headers: {
@@ -103,7 +95,7 @@ Future<Post?> createPost(String content) async {
     body: {
       'content': content
     }
-  );
+  ));
 
   // If we did receive an OK (200) response, we return created post
   if (response.statusCode == 200)
@@ -116,25 +108,22 @@ Future<Post?> createPost(String content) async {
 
 Future<bool> likePost(int id) async {
 
-  // Get user token
-  final token = TokenHandler().token;
-
   // If there is no token return error
-  if (token == null) {
+  if (TokenHandler().token == null) {
     print('LikePost > NoToken: User must be connected to create a post.');
     return false;
   }
 
   try {
 
-    final response = await http.get(
+    final response = await sendWithToken((token) => http.get(
 
     Uri.parse('http://127.0.0.1:8080/posts/like/$id'),
       headers: {
         'Authorization': 'Bearer $token',  // Include token in the Authorization header
         'Content-Type': 'application/x-www-form-urlencoded',
       }
-    );
+    ));
 
     // Log status code
     switch (response.statusCode) {
@@ -166,25 +155,22 @@ Future<bool> likePost(int id) async {
 
 Future<bool> unlikePost(int id) async {
 
-  // Get user token
-  final token = TokenHandler().token;
-
   // If there is no token return error
-  if (token == null) {
+  if (TokenHandler().token == null) {
     print('LikePost > NoToken: User must be connected to create a post.');
     return false;
   }
 
   try {
 
-    final response = await http.get(
+    final response = await sendWithToken((token) => http.get(
 
     Uri.parse('http://127.0.0.1:8080/posts/unlike/$id'),
       headers: {
         'Authorization': 'Bearer $token',  // Include token in the Authorization header
         'Content-Type': 'application/x-www-form-urlencoded',
       }
-    );
+    ));
 
     // Log status code
     switch (response.statusCode) {
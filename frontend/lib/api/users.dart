@@ -5,19 +5,17 @@ import 'package:http/http.dart' as http;
 
 import '../models/user.dart';
 import '../auth/tokenHandler.dart';
+import 'auth.dart';
 
 Future<User> fetchUser(int id) async {
 
-  // Get user token
-  final token = TokenHandler().token;
-
-  final response = await http.get(
+  final response = await sendWithToken((token) => http.get(
     Uri.parse('http://127.0.0.1:8080/users/$id'),
     headers: {
       'Authorization': 'Bearer $token',
       'Content-Type': 'application/x-www-form-urlencoded',
     }
-  );
+  ));
 
   if (response.statusCode == 200) {
     // If the server did return a 200 OK response,
@@ -33,16 +31,13 @@ Future<User> fetchUser(int id) async {
 
 Future<List<User>> fetchUsers({int limit = 20, int offset = 0}) async {
 
-  // Get user token
-  final token = TokenHandler().token;
-
-  final response = await http.get(
+  final response = await sendWithToken((token) => http.get(
     Uri.parse('http://127.0.0.1:8080/users?limit=$limit&offset=$offset'),
     headers: {
       'Authorization': 'Bearer $token',
       'Content-Type': 'application/x-www-form-urlencoded',
     }
-  );
+  ));
 
   if (response.statusCode == 200) {
 
@@ -60,18 +55,15 @@ Future<bool> deleteUser(User user) async {
 
   final id = user.id;
 
-  // Get user token
-  final token = TokenHandler().token;
-
   try {
 
-    final response = await http.delete(
+    final response = await sendWithToken((token) => http.delete(
       Uri.parse('http://127.0.0.1:8080/users/delete/$id'),
       headers: {
         'Authorization': 'Bearer $token',
         'Content-Type': 'application/x-www-form-urlencoded',
       }
-    );
+    ));
 
     // Check for 204 cause API is returning 204 if delete is successfull
     bool success = response.statusCode == 204;
@@ -106,7 +98,7 @@ Future<User?> createUser(String username, String email, String password, String?
         'email': email,
         'password': password,
         'title': title ?? 'null',
-        'is_admin': isAdmin ? 'true' : 'false',
+        'role': isAdmin ? 'admin' : 'user',
       }
   );
 
@@ -122,16 +114,13 @@ Future<bool> updateUser(User user, String? password) async {
 
   final id = user.id;
 
-  // Get user token
-  final token = TokenHandler().token;
-
   try {
 
     final Map<String, String> body = {
       'username': user.username,
       'email': user.email,
       'title': user.title ?? 'null',
-      'is_admin': user.isAdmin ? 'true' : 'false',
+      'role': user.role,
     };
 
     // Include password only if it's provided
@@ -139,14 +128,14 @@ Future<bool> updateUser(User user, String? password) async {
       body['password'] = password;
     }
 
-    final response = await http.put(
+    final response = await sendWithToken((token) => http.put(
       Uri.parse('http://127.0.0.1:8080/users/update/$id'),
       headers: {
         'Authorization': 'Bearer $token',
         'Content-Type': 'application/x-www-form-urlencoded',
       },
       body: body
-    );
+    ));
 
     // Check for 202 : OK
     bool success = response.statusCode == 204;
@@ -180,13 +169,13 @@ Future<User> fetchConnectedUser() async {
     throw Exception('User is not connected.');
 
   // Try getting connected user data
-  final response = await http.get(
+  final response = await sendWithToken((token) => http.get(
     Uri.parse('http://127.0.0.1:8080/users/me'),
     headers: {
       'Authorization': 'Bearer $token',  // Include token in the Authorization header
       'Content-Type': 'application/json',
     },
-  );
+  ));
 
   // Handle the response
   switch (response.statusCode) {
@@ -3,6 +3,7 @@ import 'tokenHandler.dart';
 
 import '../models/user.dart';
 import '../api/users.dart';
+import '../api/auth.dart';
 
 class AuthProvider extends ChangeNotifier {
 
@@ -57,9 +58,9 @@ class AuthProvider extends ChangeNotifier {
   }
 
   // Connexion (après login)
-  Future<void> login(String token) async {
+  Future<void> login(String token, String refreshToken) async {
 
-    await TokenHandler().setToken(token);
+    await TokenHandler().setTokens(token, refreshToken);
 
     try {
 
@@ -78,6 +79,12 @@ class AuthProvider extends ChangeNotifier {
   // Déconnexion
   Future<void> logout() async {
 
+    // Revoke the session on the API, so the tokens cannot be used anymore
+    final refreshToken = TokenHandler().refreshToken;
+
+    if (refreshToken != null)
+      await revokeSession(refreshToken);
+
     await TokenHandler().clear();
 
     _user = null;
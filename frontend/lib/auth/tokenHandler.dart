@@ -4,6 +4,7 @@ import 'package:shared_preferences/shared_preferences.dart';
 class TokenHandler {
 
   static const _key = 'token';
+  static const _refreshKey = 'refresh_token';
 
   static final TokenHandler _instance = TokenHandler._internal();
 
@@ -12,6 +13,7 @@ class TokenHandler {
   TokenHandler._internal();
 
   String? _token;
+  String? _refreshToken;
   bool _initialized = false;
 
   Future<void> init() async {
@@ -24,6 +26,7 @@ class TokenHandler {
     final prefs = await SharedPreferences.getInstance();
 
     _token = prefs.getString(_key);
+    _refreshToken = prefs.getString(_refreshKey);
 
     print("TokenHandler Intialized.");
   }
@@ -31,6 +34,8 @@ class TokenHandler {
   // synchronous fast getter after init()
   String? get token => _token;
 
+  String? get refreshToken => _refreshToken;
+
   Future<void> setToken(String? value) async {
 
     _token = value;
@@ -44,8 +49,24 @@ class TokenHandler {
       await prefs.setString(_key, value);
   }
 
+  // The refresh token is rotated by the API, store the new one with each new access token
+  Future<void> setTokens(String? value, String? refreshValue) async {
+
+    await setToken(value);
+
+    _refreshToken = refreshValue;
+
+    final prefs = await SharedPreferences.getInstance();
+
+    if (refreshValue == null)
+      await prefs.remove(_refreshKey);
+
+    else
+      await prefs.setString(_refreshKey, refreshValue);
+  }
+
   Future<void> clear() async {
 
-    await setToken(null);
+    await setTokens(null, null);
   }
 }
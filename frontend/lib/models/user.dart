@@ -8,6 +8,7 @@ class User {
   final String? title;
   final DateTime createdAt;
   final bool isAdmin;
+  final String role; // user, moderator or admin
 
   const User({
     required this.id,
@@ -16,6 +17,7 @@ class User {
     this.title,
     required this.createdAt,
     this.isAdmin = false,
+    this.role = 'user',
   });
 
   factory User.fromJson(Map<String, dynamic> json) {
@@ -26,6 +28,7 @@ class User {
       title: json['title'] as String?,
       createdAt: DateTime.parse(json['created_at'] as String),
       isAdmin: json['is_admin'] as bool,
+      role: json['role'] as String,
     );
   }
 }
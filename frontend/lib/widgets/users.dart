@@ -496,6 +496,8 @@ class _UserFormState extends State<UserForm> {
         email: email,
         title: title,
         isAdmin: isAdmin,
+        // Keep the moderator role if the admin checkbox didn't change
+        role: isAdmin ? 'admin' : (widget.user!.role == 'admin' ? 'user' : widget.user!.role),
         createdAt: widget.user!.createdAt,
       );
 